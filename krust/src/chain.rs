@@ -0,0 +1,171 @@
+extern crate nalgebra as na;
+use na::{Vector3, Matrix4, Isometry3, DMatrix};
+use std::fmt;
+use crate::matrices::{generate_matrices_with_joints, radius_offsets, offset_length, generate_forward_matrices_tree, generate_backward_matrices, chain_parents, joint_matrix, jacobian_with_tool, wrap_continuous, JointType};
+
+/// The arm every solver moves: its joints, their limits and the matrices for the current joint values.
+/// Joint i is preceded by offsets[i] and hangs off parents[i], a serial chain by default.
+/// The end effector of a serial chain is the tool transform after the last joint
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chain {
+
+    pub axes: Vec<Vector3<f32>>,
+    pub radii: Vec<f32>,
+    pub offsets: Vec<Isometry3<f32>>,
+    pub joint_types: Vec<JointType>,
+    /// The joint each joint hangs off, None for the origin
+    pub parents: Vec<Option<usize>>,
+    pub thetas: Vec<f32>,
+    pub origin: Matrix4<f32>,
+    /// Fixed transform from the last joint to the end effector
+    pub tool: Isometry3<f32>,
+
+    pub min_angles: Vec<f32>,
+    pub max_angles: Vec<f32>,
+
+    pub arm_length: f32,
+    pub end_effector: Matrix4<f32>,
+
+    pub mats: Vec<Matrix4<f32>>,
+    pub forward_mats: Vec<Matrix4<f32>>,
+    /// Backward partial products along the serial chain, unused by trees
+    pub backward_mats: Vec<Matrix4<f32>>,
+
+}
+
+impl Chain {
+
+    /// A serial chain of revolute joints with straight links of length radii along z
+    pub fn new(origin: Matrix4<f32>, thetas: &[f32], axes: &[Vector3<f32>], radii: &[f32], min_angles: &[f32], max_angles: &[f32]) -> Chain {
+
+        // Make sure arm properties have the same length
+        assert!(thetas.len() == axes.len() && thetas.len() == radii.len() && thetas.len() == min_angles.len() && thetas.len() == max_angles.len(),
+        "Vector lengths unequal! angles: {}, axes: {}, radii: {}, min angles: {}, max angles: {}", thetas.len(), axes.len(), radii.len(), min_angles.len(), max_angles.len());
+
+        let mut chain = Chain {
+            origin,
+            thetas: thetas.to_vec(),
+            axes: axes.to_vec(),
+            radii: radii.to_vec(),
+            offsets: radius_offsets(radii),
+            joint_types: vec![JointType::Revolute; thetas.len()],
+            parents: chain_parents(thetas.len()),
+            tool: Isometry3::identity(),
+
+            min_angles: min_angles.to_vec(),
+            max_angles: max_angles.to_vec(),
+
+            arm_length: radii.iter().sum(),
+            end_effector: origin,
+
+            mats: vec![],
+            forward_mats: vec![],
+            backward_mats: vec![],
+        };
+
+        chain.update();
+        chain
+    }
+
+    /// Number of joints
+    pub fn len(&self) -> usize {
+        self.thetas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.thetas.is_empty()
+    }
+
+    /// Replace the straight links from radii with an arbitrary fixed offset before each joint,
+    /// for offset elbows and shoulders. radii become the lengths of the offsets
+    pub fn set_offsets(&mut self, offsets: &[Isometry3<f32>]) {
+
+        assert!(offsets.len() == self.len(),
+        "Vector lengths unequal! angles: {}, offsets: {}", self.len(), offsets.len());
+
+        self.offsets = offsets.to_vec();
+        self.radii = offsets.iter().map(offset_length).collect();
+        self.arm_length = self.radii.iter().sum::<f32>() + offset_length(&self.tool);
+
+        self.update();
+    }
+
+    /// Set the type of each joint, values of prismatic joints are distances along their axis.
+    /// Continuous joints lose their limits and have their angles wrapped into (-pi, pi]
+    pub fn set_joint_types(&mut self, joint_types: &[JointType]) {
+
+        assert!(joint_types.len() == self.len(),
+        "Vector lengths unequal! angles: {}, joint types: {}", self.len(), joint_types.len());
+
+        self.joint_types = joint_types.to_vec();
+        for (i, joint_type) in joint_types.iter().enumerate() {
+            if *joint_type == JointType::Continuous {
+                self.min_angles[i] = f32::NEG_INFINITY;
+                self.max_angles[i] = f32::INFINITY;
+            }
+        }
+        wrap_continuous(&mut self.thetas, joint_types);
+
+        self.update();
+    }
+
+    /// Hold the end effector at a fixed transform from the last joint, e.g. a tool or the trailing transform of a classic DH table
+    pub fn set_tool(&mut self, tool: &Isometry3<f32>) {
+        self.tool = *tool;
+        self.arm_length = self.radii.iter().sum::<f32>() + offset_length(tool);
+
+        self.update();
+    }
+
+    /// Hang each joint off a parent instead of the joint before it, making the chain a tree
+    pub fn set_parents(&mut self, parents: &[Option<usize>]) {
+
+        assert!(parents.len() == self.len(),
+        "Vector lengths unequal! angles: {}, parents: {}", self.len(), parents.len());
+
+        self.parents = parents.to_vec();
+
+        self.update();
+    }
+
+    /// Generate mats and the end-effector position for the current configuration
+    pub fn update(&mut self) {
+        self.mats = self.matrices(&self.thetas);
+        self.forward_mats = generate_forward_matrices_tree(&self.mats, &self.parents);
+        self.backward_mats = generate_backward_matrices(&self.mats);
+
+        self.end_effector = self.tool_pose(&self.forward_mats);
+    }
+
+    /// End-effector pose for forward matrices of the chain
+    pub fn tool_pose(&self, forward_mats: &[Matrix4<f32>]) -> Matrix4<f32> {
+        forward_mats[forward_mats.len() - 1] * self.tool.to_homogeneous()
+    }
+
+    /// Geometric jacobian of the end effector for forward matrices of the chain
+    pub fn jacobian(&self, forward_mats: &[Matrix4<f32>]) -> DMatrix<f32> {
+        jacobian_with_tool(forward_mats, &self.axes, &self.joint_types, &self.tool)
+    }
+
+    /// Matrices of the chain moved to other joint values, [origin, joint 0, joint 1, ...]
+    pub fn matrices(&self, thetas: &[f32]) -> Vec<Matrix4<f32>> {
+        generate_matrices_with_joints(self.origin, thetas, &self.axes, &self.offsets, &self.joint_types)
+    }
+
+    /// Forward matrices of the chain moved to other joint values
+    pub fn forward_matrices(&self, thetas: &[f32]) -> Vec<Matrix4<f32>> {
+        generate_forward_matrices_tree(&self.matrices(thetas), &self.parents)
+    }
+
+    /// Matrix of joint i moved to value, including the offset before it
+    pub fn joint_matrix(&self, i: usize, value: f32) -> Matrix4<f32> {
+        joint_matrix(self.joint_types[i], value, &self.axes[i], &self.offsets[i])
+    }
+
+}
+
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,"Origin: {}, Axes: {:?}, Radii: {:?}, Thetas: {:?}", self.origin, self.axes, self.radii, self.thetas)
+    }
+}
@@ -1,69 +1,505 @@
 extern crate nalgebra as na;
-use std::vec;
+use std::{vec, borrow::Cow};
 
-use na::{Vector3, Matrix4, Isometry3};
+use na::{Vector3, Point3, Matrix3, Matrix4, Isometry3, UnitQuaternion, Translation3, Unit};
 use ncollide3d::query;
-use ncollide3d::bounding_volume::{self, BoundingVolume, BoundingSphere};
-use ncollide3d::shape::Cuboid;
-use ncollide3d::math::Vector;
-use crate::matrices::transform_matrix;
+use ncollide3d::bounding_volume::{self, BoundingVolume, BoundingSphere, AABB};
+use ncollide3d::shape::{self as ncollide_shape, Cuboid, Ball, Capsule, Cylinder, ConvexHull, TriMesh, SupportMap, FeatureId};
+use ncollide3d::math::{Vector, Point};
+use serde::{Serialize, Deserialize};
+use crate::{matrices::{transform_matrix, chain_parents, JointType}, mesh::is_solid};
+
+/// How far a pose matrix may be from a rigid transform
+const POSE_TOLERANCE: f32 = 1e-3;
+/// Relative and absolute margin past the gap when looking for the closest points to a mesh
+const PREDICTION_SLACK: f32 = 1e-4;
+
+/// Signed distance between two colliders and the closest point on each
+type Approach = (f32, Point<f32>, Point<f32>);
+
+/// Isometries, colliders and bounding spheres of each arm link in a pose, colliders are only owned for stretched links
+type ArmShapes<'a> = (Vec<Isometry3<f32>>, Vec<Cow<'a, Collider>>, Vec<BoundingSphere<f32>>);
+
+/// Collision shape of a link or obstacle, centred on its frame.
+/// Capsules and cylinders run along the z axis, like the link boxes, and mesh points are in the frame itself
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all="snake_case", tag="type")]
+pub enum Shape {
+    Box { half_extents: Vector3<f32> },
+    Sphere { radius: f32 },
+    Capsule { half_height: f32, radius: f32 },
+    Cylinder { half_height: f32, radius: f32 },
+    /// Solid convex hull of the points
+    ConvexHull { points: Vec<Vector3<f32>> },
+    /// Triangle surface, only touching the surface counts as a collision
+    TriMesh { vertices: Vec<Vector3<f32>>, indices: Vec<[usize; 3]> },
+    /// Union of shapes in the same frame, e.g. the convex parts of a mesh
+    Compound { parts: Vec<Shape> },
+}
+
+impl Shape {
+
+    /// Half the length of the shape along z, not counting the caps of a capsule or sphere. Meshes have none
+    pub fn half_length(&self) -> f32 {
+        match self {
+            Shape::Box { half_extents } => half_extents.z,
+            Shape::Capsule { half_height, .. } | Shape::Cylinder { half_height, .. } => *half_height,
+            _ => 0.0,
+        }
+    }
+
+    /// Whether the shape can grow along z with a telescoping link, meshes can't
+    pub fn stretches(&self) -> bool {
+        !matches!(self, Shape::ConvexHull { .. } | Shape::TriMesh { .. } | Shape::Compound { .. })
+    }
+
+    /// The shape grown by stretch along z, a stretched sphere becomes a capsule
+    pub fn stretched(&self, stretch: f32) -> Shape {
+        match self {
+            Shape::Box { half_extents } => Shape::Box { half_extents: half_extents + Vector3::new(0.0, 0.0, stretch / 2.0) },
+            Shape::Sphere { radius } if stretch > 0.0 => Shape::Capsule { half_height: stretch / 2.0, radius: *radius },
+            Shape::Capsule { half_height, radius } => Shape::Capsule { half_height: half_height + stretch / 2.0, radius: *radius },
+            Shape::Cylinder { half_height, radius } => Shape::Cylinder { half_height: half_height + stretch / 2.0, radius: *radius },
+            shape => shape.clone(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        let sizes: Vec<f32> = match self {
+            Shape::Box { half_extents } => half_extents.iter().copied().collect(),
+            Shape::Sphere { radius } => vec![*radius],
+            Shape::Capsule { half_height, radius } | Shape::Cylinder { half_height, radius } => vec![*half_height, *radius],
+            Shape::ConvexHull { points } => {
+                if !is_solid(points) {
+                    return Err("Convex hull points must not all lie on a plane".to_string());
+                }
+                vec![]
+            },
+            Shape::TriMesh { vertices, indices } => {
+                if indices.is_empty() {
+                    return Err("Triangle mesh must have at least one triangle".to_string());
+                }
+                if indices.iter().flatten().any(|index| *index >= vertices.len()) {
+                    return Err(format!("Triangle mesh indices must be less than the {} vertices", vertices.len()));
+                }
+                vec![]
+            },
+            Shape::Compound { parts } => {
+                if parts.is_empty() {
+                    return Err("Compound must have at least one part".to_string());
+                }
+                return parts.iter().try_for_each(Shape::validate);
+            },
+        };
+        if sizes.iter().any(|size| !size.is_finite() || *size < 0.0) {
+            return Err(format!("{:?} must have finite, non-negative sizes", self));
+        }
+        let points: &[Vector3<f32>] = match self {
+            Shape::ConvexHull { points } => points,
+            Shape::TriMesh { vertices, .. } => vertices,
+            _ => &[],
+        };
+        if points.iter().any(|point| !point.iter().all(|coordinate| coordinate.is_finite())) {
+            return Err("Mesh points must be finite".to_string());
+        }
+        Ok(())
+    }
+
+    fn collider(&self) -> Collider {
+        match self {
+            Shape::Box { half_extents } => Collider::Cuboid(Cuboid::new(vector_convert(half_extents))),
+            Shape::Sphere { radius } => Collider::Ball(Ball::new(*radius)),
+            Shape::Capsule { half_height, radius } => Collider::Capsule(Capsule::new(*half_height, *radius)),
+            Shape::Cylinder { half_height, radius } => Collider::Cylinder(SolidCylinder(Cylinder::new(*half_height, *radius))),
+            Shape::ConvexHull { points } => {
+                let points: Vec<Point<f32>> = points.iter().map(|point| Point::from(vector_convert(point))).collect();
+                Collider::ConvexHull(ConvexHull::try_from_points(&points).expect("Convex hull of the points failed"))
+            },
+            Shape::TriMesh { vertices, indices } => Collider::TriMesh(TriMesh::new(
+                vertices.iter().map(|vertex| Point::from(vector_convert(vertex))).collect(),
+                indices.iter().map(|[a, b, c]| Point3::new(*a, *b, *c)).collect(),
+                None,
+            )),
+            Shape::Compound { parts } => Collider::Compound(parts.iter().map(Shape::collider).collect()),
+        }
+    }
+}
+
+/// Closest approach between a link collider and an obstacle, or between two link colliders
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Proximity {
+    /// Signed distance, negative by the penetration depth when the colliders overlap
+    pub distance: f32,
+    pub link: usize,
+    /// Index of the obstacle, or of the other link for self queries
+    pub other: usize,
+    /// Closest points in the world frame, the deepest ones when the colliders overlap
+    pub link_point: Vector3<f32>,
+    pub other_point: Vector3<f32>,
+}
+
+/// Placement of a collider, an obstacle in the world or a link collider in its link frame:
+/// a translation, a translation and rotation, or a homogeneous matrix.
+/// In JSON a translation is [x, y, z], a matrix is 16 numbers column by column and an isometry is {"rotation": [i, j, k, w], "translation": [x, y, z]}
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum ColliderPose {
+    Translation(Vector3<f32>),
+    Isometry(Isometry3<f32>),
+    Matrix(Matrix4<f32>),
+}
+
+impl ColliderPose {
+
+    /// The pose as an isometry, None if a matrix is not a rigid transform.
+    /// Matrices are snapped to the nearest rotation, so ones rounded on the way in still work
+    pub fn to_isometry(&self) -> Option<Isometry3<f32>> {
+        match self {
+            ColliderPose::Translation(translation) => Some(Isometry3::translation(translation.x, translation.y, translation.z)),
+            ColliderPose::Isometry(isometry) => Some(*isometry),
+            ColliderPose::Matrix(matrix) => {
+                let rotation: Matrix3<f32> = matrix.fixed_slice::<3, 3>(0, 0).into_owned();
+                let rigid: bool = (rotation.transpose() * rotation - Matrix3::identity()).amax() < POSE_TOLERANCE
+                && rotation.determinant() > 0.0
+                && (matrix.fixed_slice::<1, 4>(3, 0) - Matrix4::identity().fixed_slice::<1, 4>(3, 0)).amax() < POSE_TOLERANCE;
+
+                rigid.then(|| Isometry3::from_parts(
+                    Translation3::from(matrix.fixed_slice::<3, 1>(0, 3).into_owned()),
+                    UnitQuaternion::from_matrix(&rotation),
+                ))
+            },
+        }
+    }
+}
+
+impl From<Vector3<f32>> for ColliderPose {
+    fn from(translation: Vector3<f32>) -> ColliderPose {
+        ColliderPose::Translation(translation)
+    }
+}
+
+impl From<Isometry3<f32>> for ColliderPose {
+    fn from(isometry: Isometry3<f32>) -> ColliderPose {
+        ColliderPose::Isometry(isometry)
+    }
+}
+
+impl From<Matrix4<f32>> for ColliderPose {
+    fn from(matrix: Matrix4<f32>) -> ColliderPose {
+        ColliderPose::Matrix(matrix)
+    }
+}
+
+/// ncollide cylinders are only support maps, this lets them go through the generic queries alongside meshes
+#[derive(Clone)]
+struct SolidCylinder(Cylinder<f32>);
+
+impl ncollide_shape::Shape<f32> for SolidCylinder {
+
+    fn aabb(&self, m: &Isometry3<f32>) -> AABB<f32> {
+        bounding_volume::aabb(&self.0, m)
+    }
+
+    fn tangent_cone_contains_dir(&self, _feature: FeatureId, _m: &Isometry3<f32>, _deformations: Option<&[f32]>, _dir: &Unit<Vector3<f32>>) -> bool {
+        false
+    }
+
+    fn as_support_map(&self) -> Option<&dyn SupportMap<f32>> {
+        Some(&self.0)
+    }
+
+    fn is_support_map(&self) -> bool {
+        true
+    }
+}
+
+/// ncollide shape of a Shape. Capsules and cylinders are built along y, so they are turned onto z when posed
+#[derive(Clone)]
+enum Collider {
+    Cuboid(Cuboid<f32>),
+    Ball(Ball<f32>),
+    Capsule(Capsule<f32>),
+    Cylinder(SolidCylinder),
+    ConvexHull(ConvexHull<f32>),
+    TriMesh(TriMesh<f32>),
+    Compound(Vec<Collider>),
+}
+
+impl Collider {
+
+    /// ncollide shape of the collider, compounds are split into their parts instead
+    fn shape(&self) -> Option<&dyn ncollide_shape::Shape<f32>> {
+        match self {
+            Collider::Cuboid(cuboid) => Some(cuboid),
+            Collider::Ball(ball) => Some(ball),
+            Collider::Capsule(capsule) => Some(capsule),
+            Collider::Cylinder(cylinder) => Some(cylinder),
+            Collider::ConvexHull(hull) => Some(hull),
+            Collider::TriMesh(mesh) => Some(mesh),
+            Collider::Compound(_) => None,
+        }
+    }
+
+    /// Pose of the ncollide shape for a shape posed at the isometry
+    fn pose(&self, isometry: &Isometry3<f32>) -> Isometry3<f32> {
+        match self {
+            Collider::Capsule(_) | Collider::Cylinder(_) => isometry * UnitQuaternion::from_axis_angle(&Vector3::x_axis(), std::f32::consts::FRAC_PI_2),
+            _ => *isometry,
+        }
+    }
+
+    fn bounding_sphere(&self, isometry: &Isometry3<f32>) -> BoundingSphere<f32> {
+        let pose: Isometry3<f32> = self.pose(isometry);
+        match self {
+            Collider::Cuboid(cuboid) => bounding_volume::bounding_sphere(cuboid, &pose),
+            Collider::Ball(ball) => bounding_volume::bounding_sphere(ball, &pose),
+            Collider::Capsule(capsule) => bounding_volume::bounding_sphere(capsule, &pose),
+            Collider::Cylinder(cylinder) => bounding_volume::bounding_sphere(&cylinder.0, &pose),
+            Collider::ConvexHull(hull) => bounding_volume::bounding_sphere(hull, &pose),
+            Collider::TriMesh(mesh) => bounding_volume::bounding_sphere(mesh, &pose),
+            Collider::Compound(parts) => parts.iter().map(|part| part.bounding_sphere(isometry)).reduce(|a, b| a.merged(&b))
+            .unwrap_or_else(|| BoundingSphere::new(pose.translation.vector.into(), 0.0)),
+        }
+    }
+
+    /// Distance to another collider, zero if they intersect.
+    /// Meshes walk their bounding volume tree, so only triangles near the other collider are tested
+    fn distance(&self, isometry: &Isometry3<f32>, other: &Collider, other_isometry: &Isometry3<f32>) -> f32 {
+        match (self, other) {
+            (Collider::Compound(parts), _) => parts.iter().map(|part| part.distance(isometry, other, other_isometry)).fold(f32::INFINITY, f32::min),
+            (_, Collider::Compound(parts)) => parts.iter().map(|part| self.distance(isometry, part, other_isometry)).fold(f32::INFINITY, f32::min),
+            _ => match (self.shape(), other.shape()) {
+                (Some(shape), Some(other_shape)) => query::distance(&self.pose(isometry), shape, &other.pose(other_isometry), other_shape),
+                _ => f32::INFINITY,
+            },
+        }
+    }
+
+    /// Signed distance to another collider and the closest points, on this collider first
+    fn closest(&self, isometry: &Isometry3<f32>, other: &Collider, other_isometry: &Isometry3<f32>) -> Approach {
+        match (self, other) {
+            (Collider::Compound(parts), _) => parts.iter().map(|part| part.closest(isometry, other, other_isometry)).reduce(nearer).unwrap_or_else(far),
+            (_, Collider::Compound(parts)) => parts.iter().map(|part| self.closest(isometry, part, other_isometry)).reduce(nearer).unwrap_or_else(far),
+            _ => match (self.shape(), other.shape()) {
+                (Some(shape), Some(other_shape)) => contact(&self.pose(isometry), shape, &other.pose(other_isometry), other_shape),
+                _ => far(),
+            },
+        }
+    }
+}
+
+/// Signed distance and closest points of two shapes, GJK when apart and EPA when overlapping.
+/// Meshes only test the triangles whose bounding boxes come within the prediction, so it is kept just past the gap
+fn contact(isometry: &Isometry3<f32>, shape: &dyn ncollide_shape::Shape<f32>, other_isometry: &Isometry3<f32>, other: &dyn ncollide_shape::Shape<f32>) -> Approach {
+
+    let prediction: f32 = if shape.as_composite_shape().is_some() || other.as_composite_shape().is_some() {
+        query::distance(isometry, shape, other_isometry, other) * (1.0 + PREDICTION_SLACK) + PREDICTION_SLACK
+    } else {
+        f32::MAX
+    };
+
+    match query::contact(isometry, shape, other_isometry, other, prediction) {
+        Some(contact) => (-contact.depth, contact.world1, contact.world2),
+        // EPA only gives up deep inside the other shape
+        None => (0.0, isometry.translation.vector.into(), other_isometry.translation.vector.into()),
+    }
+}
+
+fn nearer(a: Approach, b: Approach) -> Approach {
+    if b.0 < a.0 { b } else { a }
+}
+
+fn far() -> Approach {
+    (f32::INFINITY, Point::origin(), Point::origin())
+}
 
 pub struct CollisionHandler {
 
     arm_offsets: Vec<Matrix4<f32>>,
-    arm_colliders: Vec<Cuboid<f32>>,
+    arm_shapes: Vec<Shape>,
+    arm_colliders: Vec<Collider>,
     arm_spheres: Vec<BoundingSphere<f32>>,
+    arm_telescoping: Vec<Option<f32>>,
+    arm_offsets_explicit: bool,
+    arm_parents: Vec<Option<usize>>,
 
     world_offsets: Vec<Matrix4<f32>>,
-    world_colliders: Vec<Cuboid<f32>>,
+    world_colliders: Vec<Collider>,
     world_spheres: Vec<BoundingSphere<f32>>,
 
+    safety_margin: f32,
+
 }
 
 impl CollisionHandler{
 
-    // TODO: make this less ugly
-    pub fn new(arm: &Vec<Vector3<f32>>, obstacles: &Vec<Vector3<f32>>, obstacle_offsets: &Vec<Vector3<f32>>) -> CollisionHandler {
+    /// Boxes for the arm links and obstacles from their half extents, with the obstacles translated but not rotated.
+    /// Use with_poses to turn obstacles as well
+    pub fn new(arm: &[Vector3<f32>], obstacles: &[Vector3<f32>], obstacle_offsets: &[Vector3<f32>]) -> CollisionHandler {
+
+        let arm: Vec<Shape> = arm.iter().map(|v| Shape::Box { half_extents: *v }).collect();
+        let obstacles: Vec<Shape> = obstacles.iter().map(|v| Shape::Box { half_extents: *v }).collect();
+
+        CollisionHandler::with_shapes(&arm, &obstacles, obstacle_offsets)
+    }
+
+    /// Any mix of boxes, spheres, capsules, cylinders and meshes for the arm links and obstacles,
+    /// with the obstacles translated like new. Use with_poses to turn obstacles as well
+    pub fn with_shapes(arm: &[Shape], obstacles: &[Shape], obstacle_offsets: &[Vector3<f32>]) -> CollisionHandler {
+
+        let obstacle_poses: Vec<ColliderPose> = obstacle_offsets.iter().map(|offset| ColliderPose::from(*offset)).collect();
+
+        CollisionHandler::with_poses(arm, obstacles, &obstacle_poses)
+    }
+
+    /// with_shapes for obstacles placed by a translation, a translation and rotation or a 4x4 matrix each
+    pub fn with_poses(arm: &[Shape], obstacles: &[Shape], obstacle_offsets: &[ColliderPose]) -> CollisionHandler {
+
+        if let Some(err) = arm.iter().chain(obstacles.iter()).find_map(|shape| shape.validate().err()) {
+            panic!("Invalid shape! {}", err);
+        }
+
+        assert!(obstacles.len() == obstacle_offsets.len(),
+        "Vector lengths unequal! obstacles: {}, obstacle offsets: {}", obstacles.len(), obstacle_offsets.len());
 
         let identity = na::one::<Isometry3<f32>>();
 
-        let arm_colliders: Vec<Cuboid<f32>> = arm.iter().map(|v| Cuboid::new(vector_convert(v))).collect();
-        let arm_spheres: Vec<BoundingSphere<f32>> = arm_colliders.iter().map(|cube| bounding_volume::bounding_sphere(cube, &identity)).collect();
+        let arm_colliders: Vec<Collider> = arm.iter().map(Shape::collider).collect();
+        let arm_spheres: Vec<BoundingSphere<f32>> = arm_colliders.iter().map(|collider| collider.bounding_sphere(&identity)).collect();
         
-        let world_colliders: Vec<Cuboid<f32>> = obstacles.iter().map(|v| Cuboid::new(vector_convert(v))).collect();
-        let world_offsets: Vec<Matrix4<f32>> =  obstacle_offsets.iter().map(|v| transform_matrix(0.0, &Vector3::z_axis(), &v)).collect();
+        let world_colliders: Vec<Collider> = obstacles.iter().map(Shape::collider).collect();
+        let world_offsets: Vec<Matrix4<f32>> = obstacle_offsets.iter().map(|pose| match pose.to_isometry() {
+            Some(isometry) => isometry.to_homogeneous(),
+            None => panic!("Invalid obstacle pose! {:?} is not a rigid transform", pose),
+        }).collect();
 
         CollisionHandler {
-            arm_offsets: arm.iter().map(|v| transform_matrix(0.0, &Vector3::z_axis(), &Vector3::new(0.0, 0.0, v.z / 2.0))).collect(),
-            arm_colliders: arm_colliders,
-            arm_spheres: arm_spheres,
+            arm_offsets: arm.iter().map(|shape| transform_matrix(0.0, &Vector3::z_axis(), &Vector3::new(0.0, 0.0, shape.half_length() / 2.0))).collect(),
+            arm_shapes: arm.to_vec(),
+            arm_colliders,
+            arm_spheres,
+            arm_telescoping: vec![None; arm.len()],
+            arm_offsets_explicit: false,
+            arm_parents: chain_parents(arm.len()),
 
             world_spheres: get_bounding_spheres_world(&world_offsets, &world_colliders),
-            world_offsets: world_offsets,
-            world_colliders: world_colliders,
+            world_offsets,
+            world_colliders,
+
+            safety_margin: 0.0,
         }
     }
 
-    pub fn is_arm_colliding_self(&self, index: usize, matrices: &Vec<Matrix4<f32>>) -> bool {
+    /// Check the arm colliders fit a chain of joints, collider i is posed on the frame before joint i
+    /// so there is at most one for each joint and one for the last link
+    pub fn validate_arm(&self, joints: usize) -> Result<(), String> {
+        if self.arm_colliders.len() > joints + 1 {
+            return Err(format!("{} colliders for {} joints, at most one per joint and one for the last link", self.arm_colliders.len(), joints));
+        }
+        Ok(())
+    }
 
-        let isometries: Vec<Isometry3<f32>> = self.get_arm_isometries(matrices);
-        let mut spheres: Vec<BoundingSphere<f32>> = vec![];
-        self.arm_spheres.iter().enumerate().for_each(|(i, sphere)| spheres.push(sphere.transform_by(&isometries[i])));
+    /// Pose each link collider in the frame of its link, e.g. from URDF collision origins.
+    /// Colliders placed this way are rigid and are no longer moved by set_link_offsets or stretched by set_joint_types
+    pub fn set_arm_offsets(&mut self, offsets: &[Isometry3<f32>]) {
+
+        assert!(offsets.len() == self.arm_colliders.len(),
+        "Vector lengths unequal! arm offsets: {}, arm colliders: {}", offsets.len(), self.arm_colliders.len());
+
+        self.arm_offsets = offsets.iter().map(|offset| offset.to_homogeneous()).collect();
+        self.arm_telescoping = vec![None; self.arm_colliders.len()];
+        self.arm_offsets_explicit = true;
+    }
+
+    /// Place each link collider halfway along its link offset, with its z axis pointing along the link.
+    /// By default the colliders sit along the z axis of the link frame
+    pub fn set_link_offsets(&mut self, offsets: &[Isometry3<f32>]) {
+
+        if self.arm_offsets_explicit {
+            return;
+        }
+
+        for (arm_offset, offset) in self.arm_offsets.iter_mut().zip(offsets.iter()) {
+
+            let link: Vector3<f32> = offset.translation.vector;
+
+            let rotation: UnitQuaternion<f32> = match link.try_normalize(f32::EPSILON) {
+                Some(direction) if direction.z < -1.0 + f32::EPSILON => UnitQuaternion::from_axis_angle(&Vector3::x_axis(), std::f32::consts::PI),
+                Some(direction) => UnitQuaternion::rotation_between(&Vector3::z(), &direction).unwrap_or_else(UnitQuaternion::identity),
+                None => UnitQuaternion::identity(),
+            };
+
+            *arm_offset = Isometry3::from_parts(Translation3::from(link / 2.0), rotation).to_homogeneous();
+        }
+    }
+
+    /// Let the link colliders before prismatic joints stretch with the joint, radii are the unextended link lengths.
+    /// Mesh colliders keep their shape
+    pub fn set_joint_types(&mut self, joint_types: &[JointType], radii: &[f32]) {
+
+        assert!(joint_types.len() == radii.len(),
+        "Vector lengths unequal! joint types: {}, radii: {}", joint_types.len(), radii.len());
+
+        if self.arm_offsets_explicit {
+            return;
+        }
+
+        self.arm_telescoping = (0..self.arm_colliders.len()).map(|i| match joint_types.get(i) {
+            Some(JointType::Prismatic) if self.arm_shapes[i].stretches() => Some(radii[i]),
+            _ => None,
+        }).collect();
+    }
+
+    /// Arrange the links as a tree instead of a chain, parents[i] is the link that link i hangs off, None for the origin.
+    /// Each collider is then posed on the frame of its parent joint and is adjacent to its parent, children and siblings
+    pub fn set_arm_parents(&mut self, parents: &[Option<usize>]) {
+
+        assert!(parents.iter().enumerate().all(|(i, parent)| parent.is_none_or(|parent| parent < i)),
+        "Links must come after their parents!");
+
+        for (arm_parent, parent) in self.arm_parents.iter_mut().zip(parents.iter()) {
+            *arm_parent = *parent;
+        }
+    }
+
+    /// Count colliders closer than the margin as colliding, e.g. 0.02 to keep 2 cm from everything
+    pub fn set_safety_margin(&mut self, margin: f32) {
+
+        assert!(margin >= 0.0 && margin.is_finite(), "Invalid safety margin! {} must be finite and not negative", margin);
+
+        self.safety_margin = margin;
+    }
+
+    /// Add an obstacle placed by a full pose, e.g. a mesh of a fixture in the work cell
+    pub fn add_obstacle(&mut self, shape: &Shape, pose: &Isometry3<f32>) {
+
+        if let Err(err) = shape.validate() {
+            panic!("Invalid shape! {}", err);
+        }
+
+        let collider: Collider = shape.collider();
+        self.world_spheres.push(collider.bounding_sphere(pose));
+        self.world_colliders.push(collider);
+        self.world_offsets.push(pose.to_homogeneous());
+    }
+
+    pub fn is_arm_colliding_self(&self, index: usize, matrices: &[Matrix4<f32>]) -> bool {
+
+        let (isometries, colliders, spheres) = self.get_arm_shapes(matrices);
+        let moved: Vec<bool> = self.get_arm_moved(index);
 
-        // Checks collisions of the colliders before the index against the
-        // colliders after the index. Since colliders in their own slice are guaranteed not to be colliding
+        // Checks collisions of the colliders moved by the joint at the index against the
+        // colliders that stay put. Since colliders in their own group are guaranteed not to be colliding
         // [] [] [] index [] [] []
-        for i in 0..index {
-            for j in index..self.arm_colliders.len() {
-                if j - i > 1 {
-                    if spheres[i].intersects(&spheres[j]) {
-                        let iso_i: Isometry3<f32> = isometries[i];
-                        let iso_j: Isometry3<f32> = isometries[j];
-                        let dist = query::distance(&iso_i, &self.arm_colliders[i], &iso_j, &self.arm_colliders[j]);
-                        if dist <= 0.0
-                        {
-                            return true
-                        }
+        for i in 0..self.arm_colliders.len() {
+            for j in i..self.arm_colliders.len() {
+                if moved[i] != moved[j] && !self.is_arm_adjacent(i, j) && spheres[i].loosened(self.safety_margin).intersects(&spheres[j]) {
+                    let iso_i: Isometry3<f32> = isometries[i];
+                    let iso_j: Isometry3<f32> = isometries[j];
+                    let dist = colliders[i].distance(&iso_i, &colliders[j], &iso_j);
+                    if dist <= self.safety_margin
+                    {
+                        return true
                     }
                 }
             }
@@ -72,23 +508,19 @@ impl CollisionHandler{
         false
     }
 
-    pub fn is_arm_colliding_self_naive(&self, matrices: &Vec<Matrix4<f32>>) -> bool {
+    pub fn is_arm_colliding_self_naive(&self, matrices: &[Matrix4<f32>]) -> bool {
 
-        let isometries: Vec<Isometry3<f32>> = self.get_arm_isometries(matrices);
-        let mut spheres: Vec<BoundingSphere<f32>> = vec![];
-        self.arm_spheres.iter().enumerate().for_each(|(i, sphere)| spheres.push(sphere.transform_by(&isometries[i])));
+        let (isometries, colliders, spheres) = self.get_arm_shapes(matrices);
 
         for i in 0..self.arm_colliders.len() {
             for j in i..self.arm_colliders.len() {
-                if j - i > 1 {
-                    if spheres[i].intersects(&spheres[j]) {
-                        let iso_i: Isometry3<f32> = isometries[i];
-                        let iso_j: Isometry3<f32> = isometries[j];
-                        let dist = query::distance(&iso_i, &self.arm_colliders[i], &iso_j, &self.arm_colliders[j]);
-                        if dist <= 0.0
-                        {
-                            return true
-                        }
+                if !self.is_arm_adjacent(i, j) && spheres[i].loosened(self.safety_margin).intersects(&spheres[j]) {
+                    let iso_i: Isometry3<f32> = isometries[i];
+                    let iso_j: Isometry3<f32> = isometries[j];
+                    let dist = colliders[i].distance(&iso_i, &colliders[j], &iso_j);
+                    if dist <= self.safety_margin
+                    {
+                        return true
                     }
                 }
             }
@@ -97,27 +529,22 @@ impl CollisionHandler{
         false
     }
 
-    pub fn find_arm_collisions_self(&self, matrices: &Vec<Matrix4<f32>>) -> Vec<bool> {
-
-        let isometries: Vec<Isometry3<f32>> = self.get_arm_isometries(matrices);
+    pub fn find_arm_collisions_self(&self, matrices: &[Matrix4<f32>]) -> Vec<bool> {
 
-        let mut spheres: Vec<BoundingSphere<f32>> = vec![];
-        self.arm_spheres.iter().enumerate().for_each(|(i, sphere)| spheres.push(sphere.transform_by(&isometries[i])));
+        let (isometries, colliders, spheres) = self.get_arm_shapes(matrices);
         
         let mut collisions: Vec<bool>  = vec![false; self.arm_colliders.len()];
 
         for i in 0..self.arm_colliders.len() {
             for j in i..self.arm_colliders.len() {
-                if j - i > 1 {
-                    if spheres[i].intersects(&spheres[j]) {
-                        let iso_i: Isometry3<f32> = isometries[i];
-                        let iso_j: Isometry3<f32> = isometries[j];
-                        let dist = query::distance(&iso_i, &self.arm_colliders[i], &iso_j, &self.arm_colliders[j]);
-                        if dist <= 0.0
-                        {
-                            collisions[i] = true;
-                            collisions[j] = true;
-                        }
+                if !self.is_arm_adjacent(i, j) && spheres[i].loosened(self.safety_margin).intersects(&spheres[j]) {
+                    let iso_i: Isometry3<f32> = isometries[i];
+                    let iso_j: Isometry3<f32> = isometries[j];
+                    let dist = colliders[i].distance(&iso_i, &colliders[j], &iso_j);
+                    if dist <= self.safety_margin
+                    {
+                        collisions[i] = true;
+                        collisions[j] = true;
                     }
                 }
             }
@@ -126,21 +553,19 @@ impl CollisionHandler{
         collisions
     }
 
-    pub fn is_arm_colliding_world(&self, index: usize, matrices: &Vec<Matrix4<f32>>) -> bool {
+    pub fn is_arm_colliding_world(&self, index: usize, matrices: &[Matrix4<f32>]) -> bool {
 
-        let arm_isometries: Vec<Isometry3<f32>> = self.get_arm_isometries(matrices);
+        let (arm_isometries, arm_colliders, arm_spheres) = self.get_arm_shapes(matrices);
         let world_isometries: Vec<Isometry3<f32>> = self.get_world_isometries();
+        let moved: Vec<bool> = self.get_arm_moved(index);
 
-        let mut arm_spheres: Vec<BoundingSphere<f32>> = vec![];
-        self.arm_spheres.iter().enumerate().for_each(|(i, sphere)| arm_spheres.push(sphere.transform_by(&arm_isometries[i])));
-
-        for i in index..self.arm_colliders.len() {
-            for j in 0..self.world_colliders.len() {
-                if arm_spheres[i].intersects(&self.world_spheres[j]) {
+        for i in (0..self.arm_colliders.len()).filter(|i| moved[*i]) {
+            for (j, world_sphere) in self.world_spheres.iter().enumerate() {
+                if arm_spheres[i].loosened(self.safety_margin).intersects(world_sphere) {
                     let iso_i: Isometry3<f32> = arm_isometries[i];
                     let iso_j: Isometry3<f32> = world_isometries[j];
-                    let dist = query::distance(&iso_i, &self.arm_colliders[i], &iso_j, &self.world_colliders[j]);
-                    if dist <= 0.0
+                    let dist = arm_colliders[i].distance(&iso_i, &self.world_colliders[j], &iso_j);
+                    if dist <= self.safety_margin
                     {
                         return true
                     }
@@ -151,21 +576,18 @@ impl CollisionHandler{
         false
     }
 
-    pub fn is_arm_colliding_world_naive(&self, matrices: &Vec<Matrix4<f32>>) -> bool {
+    pub fn is_arm_colliding_world_naive(&self, matrices: &[Matrix4<f32>]) -> bool {
 
-        let arm_isometries: Vec<Isometry3<f32>> = self.get_arm_isometries(matrices);
+        let (arm_isometries, arm_colliders, arm_spheres) = self.get_arm_shapes(matrices);
         let world_isometries: Vec<Isometry3<f32>> = self.get_world_isometries();
 
-        let mut arm_spheres: Vec<BoundingSphere<f32>> = vec![];
-        self.arm_spheres.iter().enumerate().for_each(|(i, sphere)| arm_spheres.push(sphere.transform_by(&arm_isometries[i])));
-
         for i in 0..self.arm_colliders.len() {
-            for j in 0..self.world_colliders.len() {
-                if arm_spheres[i].intersects(&self.world_spheres[j]) {
+            for (j, world_sphere) in self.world_spheres.iter().enumerate() {
+                if arm_spheres[i].loosened(self.safety_margin).intersects(world_sphere) {
                     let iso_i: Isometry3<f32> = arm_isometries[i];
                     let iso_j: Isometry3<f32> = world_isometries[j];
-                    let dist = query::distance(&iso_i, &self.arm_colliders[i], &iso_j, &self.world_colliders[j]);
-                    if dist <= 0.0
+                    let dist = arm_colliders[i].distance(&iso_i, &self.world_colliders[j], &iso_j);
+                    if dist <= self.safety_margin
                     {
                         return true
                     }
@@ -176,24 +598,21 @@ impl CollisionHandler{
         false
     }
 
-    pub fn find_arm_collisions_world(&self, matrices: &Vec<Matrix4<f32>>) -> Vec<bool> {
+    pub fn find_arm_collisions_world(&self, matrices: &[Matrix4<f32>]) -> Vec<bool> {
 
-        let arm_isometries: Vec<Isometry3<f32>> = self.get_arm_isometries(matrices);
+        let (arm_isometries, arm_colliders, arm_spheres) = self.get_arm_shapes(matrices);
         let world_isometries: Vec<Isometry3<f32>> = self.get_world_isometries();
 
-        let mut arm_spheres: Vec<BoundingSphere<f32>> = vec![];
-        self.arm_spheres.iter().enumerate().for_each(|(i, sphere)| arm_spheres.push(sphere.transform_by(&arm_isometries[i])));
-
         
         let mut collisions: Vec<bool>  = vec![false; self.arm_colliders.len()];
 
         for i in 0..self.arm_colliders.len() {
-            for j in 0..self.world_colliders.len() {
-                if arm_spheres[i].intersects(&self.world_spheres[j]) {
+            for (j, world_sphere) in self.world_spheres.iter().enumerate() {
+                if arm_spheres[i].loosened(self.safety_margin).intersects(world_sphere) {
                     let iso_i: Isometry3<f32> = arm_isometries[i];
                     let iso_j: Isometry3<f32> = world_isometries[j];
-                    let dist = query::distance(&iso_i, &self.arm_colliders[i], &iso_j, &self.world_colliders[j]);
-                    if dist <= 0.0
+                    let dist = arm_colliders[i].distance(&iso_i, &self.world_colliders[j], &iso_j);
+                    if dist <= self.safety_margin
                     {
                         collisions[i] = true;
                     }
@@ -204,19 +623,117 @@ impl CollisionHandler{
         collisions
     }
 
-    fn get_arm_isometries(&self, matrices: &Vec<Matrix4<f32>>) -> Vec<Isometry3<f32>> {
+    /// Smallest signed distance between any link collider and any obstacle, infinite without obstacles
+    pub fn world_clearance(&self, matrices: &[Matrix4<f32>]) -> f32 {
+        self.closest_world(matrices).map_or(f32::INFINITY, |proximity| proximity.distance)
+    }
+
+    /// Closest approach between any link collider and any obstacle, None without obstacles
+    pub fn closest_world(&self, matrices: &[Matrix4<f32>]) -> Option<Proximity> {
+
+        let (arm_isometries, arm_colliders, arm_spheres) = self.get_arm_shapes(matrices);
+        let world_isometries: Vec<Isometry3<f32>> = self.get_world_isometries();
+
+        let mut closest: Option<Proximity> = None;
+
+        for i in 0..self.arm_colliders.len() {
+            for (j, world_sphere) in self.world_spheres.iter().enumerate() {
+                // skip pairs whose bounding spheres are already further apart than the closest pair
+                if closest.is_some_and(|closest| sphere_gap(&arm_spheres[i], world_sphere) > closest.distance) {
+                    continue;
+                }
+                let (distance, link_point, other_point) = arm_colliders[i].closest(&arm_isometries[i], &self.world_colliders[j], &world_isometries[j]);
+                if closest.is_none_or(|closest| distance < closest.distance) {
+                    closest = Some(Proximity { distance, link: i, other: j, link_point: link_point.coords, other_point: other_point.coords });
+                }
+            }
+        }
+
+        closest
+    }
+
+    /// Closest approach between any two link colliders that don't share a joint, None if there are no such pairs
+    pub fn closest_self(&self, matrices: &[Matrix4<f32>]) -> Option<Proximity> {
+
+        let (isometries, colliders, spheres) = self.get_arm_shapes(matrices);
+
+        let mut closest: Option<Proximity> = None;
+
+        for i in 0..self.arm_colliders.len() {
+            for j in i..self.arm_colliders.len() {
+                if self.is_arm_adjacent(i, j) || closest.is_some_and(|closest| sphere_gap(&spheres[i], &spheres[j]) > closest.distance) {
+                    continue;
+                }
+                let (distance, link_point, other_point) = colliders[i].closest(&isometries[i], &colliders[j], &isometries[j]);
+                if closest.is_none_or(|closest| distance < closest.distance) {
+                    closest = Some(Proximity { distance, link: i, other: j, link_point: link_point.coords, other_point: other_point.coords });
+                }
+            }
+        }
+
+        closest
+    }
+
+    /// Posed isometries, colliders and bounding spheres of the arm links
+    fn get_arm_shapes(&self, matrices: &[Matrix4<f32>]) -> ArmShapes<'_> {
 
         let mut isometries: Vec<Isometry3<f32>> = vec![];
+        let mut colliders: Vec<Cow<Collider>> = vec![];
+        let mut spheres: Vec<BoundingSphere<f32>> = vec![];
 
-        self.arm_offsets
-        .iter()
-        .enumerate()
-        .for_each(
-            |(i, offset)| 
-            isometries.push(nalgebra::try_convert(matrices[i] * offset).expect("Matrix was not an isometry"))
-        );
+        for (i, offset) in self.arm_offsets.iter().enumerate() {
+
+            let stretch: f32 = self.get_arm_stretch(i, matrices);
+
+            if stretch == 0.0 {
+                let isometry: Isometry3<f32> = nalgebra::try_convert(matrices[self.get_arm_frame(i)] * offset).expect("Matrix was not an isometry");
+                colliders.push(Cow::Borrowed(&self.arm_colliders[i]));
+                spheres.push(self.arm_spheres[i].transform_by(&isometry));
+                isometries.push(isometry);
+            } else {
+                // grow the collider along the link, keeping its base end in place
+                let offset: Matrix4<f32> = offset * Matrix4::new_translation(&Vector3::new(0.0, 0.0, stretch / 2.0));
+                let isometry: Isometry3<f32> = nalgebra::try_convert(matrices[self.get_arm_frame(i)] * offset).expect("Matrix was not an isometry");
+                let collider: Collider = self.arm_shapes[i].stretched(stretch).collider();
+                spheres.push(collider.bounding_sphere(&isometry));
+                colliders.push(Cow::Owned(collider));
+                isometries.push(isometry);
+            }
+        }
 
-        isometries
+        (isometries, colliders, spheres)
+    }
+
+    /// How far a telescoping link is extended past its length, from the offset to the next joint along the collider
+    fn get_arm_stretch(&self, index: usize, matrices: &[Matrix4<f32>]) -> f32 {
+        match (self.arm_telescoping[index], matrices.get(index + 1)) {
+            (Some(radius), Some(next)) => {
+                let frame: Matrix4<f32> = matrices[self.get_arm_frame(index)];
+                let local: Vector3<f32> = frame.fixed_slice::<3, 3>(0, 0).transpose() * (next.fixed_slice::<3, 1>(0, 3) - frame.fixed_slice::<3, 1>(0, 3));
+                let direction: Vector3<f32> = self.arm_offsets[index].fixed_slice::<3, 1>(0, 2).into_owned();
+                f32::max(local.dot(&direction) - radius, -2.0 * self.arm_shapes[index].half_length())
+            },
+            _ => 0.0,
+        }
+    }
+
+    /// Index of the forward matrix a link collider is posed on, the frame of its parent joint
+    fn get_arm_frame(&self, index: usize) -> usize {
+        self.arm_parents[index].map_or(0, |parent| parent + 1)
+    }
+
+    /// Links that share a joint always touch, so parents, children and siblings are never checked against each other
+    fn is_arm_adjacent(&self, i: usize, j: usize) -> bool {
+        i == j || self.arm_parents[i] == Some(j) || self.arm_parents[j] == Some(i) || self.arm_parents[i] == self.arm_parents[j]
+    }
+
+    /// Which links move with the joint at the index, the link into it and everything below it
+    fn get_arm_moved(&self, index: usize) -> Vec<bool> {
+        let mut moved: Vec<bool> = vec![false; self.arm_colliders.len()];
+        for i in 0..self.arm_colliders.len() {
+            moved[i] = i == index || self.arm_parents[i].is_some_and(|parent| moved[parent]);
+        }
+        moved
     }
 
     fn get_world_isometries(&self) -> Vec<Isometry3<f32>> {
@@ -225,7 +742,7 @@ impl CollisionHandler{
         .iter()
         .map(
             |offset| 
-            nalgebra::try_convert(offset.clone()).expect("Matrix was not an isometry")
+            nalgebra::try_convert(*offset).expect("Matrix was not an isometry")
         ).collect()
 
     }
@@ -237,11 +754,16 @@ impl Clone for CollisionHandler {
         CollisionHandler 
         { 
             arm_offsets: self.arm_offsets.to_vec(), 
+            arm_shapes: self.arm_shapes.to_vec(), 
             arm_colliders: self.arm_colliders.to_vec(), 
             arm_spheres: self.arm_spheres.to_vec(), 
+            arm_telescoping: self.arm_telescoping.to_vec(), 
+            arm_offsets_explicit: self.arm_offsets_explicit,
+            arm_parents: self.arm_parents.to_vec(),
             world_offsets: self.world_offsets.to_vec(), 
             world_colliders: self.world_colliders.to_vec(), 
             world_spheres: self.world_spheres.to_vec(), 
+            safety_margin: self.safety_margin,
         }
     }
 } 
@@ -250,13 +772,18 @@ fn vector_convert(v: &Vector3<f32>) -> Vector<f32> {
     Vector::new(v.x, v.y, v.z)
 }
 
+/// Lower bound on the distance between the shapes inside two bounding spheres
+fn sphere_gap(a: &BoundingSphere<f32>, b: &BoundingSphere<f32>) -> f32 {
+    na::distance(a.center(), b.center()) - a.radius() - b.radius()
+}
+
 // Since obstacles are static we bake their Isometries into their bounding spheres
-fn get_bounding_spheres_world(world_offsets: &Vec<Matrix4<f32>>, world_colliders: &Vec<Cuboid<f32>>) -> Vec<BoundingSphere<f32>> {
+fn get_bounding_spheres_world(world_offsets: &[Matrix4<f32>], world_colliders: &[Collider]) -> Vec<BoundingSphere<f32>> {
 
-    let world_isometries: Vec<Isometry3<f32>> = world_offsets.iter().map(|offset| nalgebra::try_convert(offset.clone()).expect("Matrix was not an isometry")).collect();
+    let world_isometries: Vec<Isometry3<f32>> = world_offsets.iter().map(|offset| nalgebra::try_convert(*offset).expect("Matrix was not an isometry")).collect();
     
     let mut world_spheres: Vec<BoundingSphere<f32>> = vec![];
-    world_colliders.iter().enumerate().for_each(|(i, cube)| world_spheres.push(bounding_volume::bounding_sphere(cube, &world_isometries[i])));
+    world_colliders.iter().enumerate().for_each(|(i, collider)| world_spheres.push(collider.bounding_sphere(&world_isometries[i])));
 
     world_spheres
 }
\ No newline at end of file
@@ -1,5 +1,16 @@
 pub mod matrices;
+pub mod chain;
+pub mod solver;
 pub mod solver_gd;
+pub mod solver_dls;
+pub mod solver_ga;
+pub mod solver_ccd;
+pub mod solver_fabrik;
+pub mod solver_tree;
+pub mod target;
+pub mod objectives;
 
 pub mod collision_handler;
+pub mod mesh;
+pub mod urdf;
 pub mod webassembly;
\ No newline at end of file
@@ -1,6 +1,7 @@
 extern crate nalgebra as na;
-use na::{Vector3, Matrix4};
-use std::{ops::Mul, vec};
+use na::{Vector3, Matrix3, Matrix4, DMatrix, Isometry3, Translation3, UnitQuaternion, Rotation3};
+use serde::{Serialize, Deserialize};
+use std::{ops::Mul, vec, f32::consts::PI};
 
 pub const IDENTITY: Matrix4<f32> = Matrix4::new(  
     1.0,0.0,0.0,0.0,
@@ -9,23 +10,58 @@ pub const IDENTITY: Matrix4<f32> = Matrix4::new(
     0.0,0.0,0.0,1.0  
 );
 
+/// How a joint moves about/along its axis
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum JointType {
+    /// Rotates by an angle in radians about the axis
+    #[default]
+    Revolute,
+    /// Slides by a distance along the axis
+    Prismatic,
+    /// Does not move, the joint value is ignored
+    Fixed,
+    /// Rotates like a revolute joint without limits, its angle is kept in (-pi, pi]
+    Continuous,
+}
+
+/// Same angle in (-pi, pi]
+pub fn wrap_angle(angle: f32) -> f32 {
+    let wrapped: f32 = (angle + PI).rem_euclid(2.0 * PI) - PI;
+    if wrapped <= -PI { wrapped + 2.0 * PI } else { wrapped }
+}
+
+/// Shortest signed rotation in radians from one angle to another
+pub fn angle_difference(from: f32, to: f32) -> f32 {
+    wrap_angle(to - from)
+}
+
+/// Wrap the angles of continuous joints into (-pi, pi], leaving other joints alone
+pub fn wrap_continuous(values: &mut [f32], joint_types: &[JointType]) {
+    for (value, joint_type) in values.iter_mut().zip(joint_types.iter()) {
+        if *joint_type == JointType::Continuous {
+            *value = wrap_angle(*value);
+        }
+    }
+}
+
 /// Create a homogeneous transformation matrix with an angle, axis and position
 pub fn transform_matrix(angle: f32, axis: &Vector3<f32>, position: &Vector3<f32>) -> Matrix4<f32> {
 
     let r_mat  = Matrix4::new_rotation(axis.mul(angle));
-    let t_mat = Matrix4::new_translation(&position);
+    let t_mat = Matrix4::new_translation(position);
 
-    return t_mat * r_mat;
+    t_mat * r_mat
 
 }
 
 /// Create a Vec of homogeneous transform matrices from minimal parameters
-pub fn generate_matrices(origin: Matrix4<f32>, angles: &Vec<f32>, axes: &Vec<Vector3<f32>>, radii: &Vec<f32>) -> Vec<Matrix4<f32>> {
+pub fn generate_matrices(origin: Matrix4<f32>, angles: &[f32], axes: &[Vector3<f32>], radii: &[f32]) -> Vec<Matrix4<f32>> {
 
     assert!(angles.len() == axes.len() && angles.len() == radii.len(), 
     "Vector lengths unequal! angles: {}, axes: {}, radii: {}", angles.len(), axes.len(), radii.len());
 
-    let radii:Vec<Vector3<f32>> = radii.iter().map(|radius|(Vector3::new(0.0,0.0,*radius))).collect();
+    let radii:Vec<Vector3<f32>> = radii.iter().map(|radius| Vector3::new(0.0,0.0,*radius)).collect();
 
     let mut matrices: Vec<Matrix4<f32>> = vec![origin * IDENTITY];
 
@@ -34,15 +70,125 @@ pub fn generate_matrices(origin: Matrix4<f32>, angles: &Vec<f32>, axes: &Vec<Vec
     .zip(radii.iter())
     .for_each(
         |((angle, axis), radius)|
-        (matrices.push(transform_matrix(*angle,axis,radius))));
+        matrices.push(transform_matrix(*angle,axis,radius)));
+
+    matrices
+
+}
+
+/// Fixed transform from a joint's parent frame to the joint, for a straight link of length radius along z
+pub fn radius_offset(radius: f32) -> Isometry3<f32> {
+    Isometry3::translation(0.0, 0.0, radius)
+}
+
+/// Link offsets for a chain of straight links
+pub fn radius_offsets(radii: &[f32]) -> Vec<Isometry3<f32>> {
+    radii.iter().map(|radius| radius_offset(*radius)).collect()
+}
+
+/// Length of the link described by an offset, the distance from the parent joint to the joint
+pub fn offset_length(offset: &Isometry3<f32>) -> f32 {
+    offset.translation.vector.norm()
+}
+
+/// Transform of a fixed link offset followed by a joint of the given type moved by value
+pub fn joint_matrix(joint_type: JointType, value: f32, axis: &Vector3<f32>, offset: &Isometry3<f32>) -> Matrix4<f32> {
+
+    let offset: Matrix4<f32> = offset.to_homogeneous();
+
+    match joint_type {
+        JointType::Revolute | JointType::Continuous => offset * Matrix4::new_rotation(axis.mul(value)),
+        JointType::Prismatic => offset * Matrix4::new_translation(&axis.mul(value)),
+        JointType::Fixed => offset,
+    }
+
+}
+
+/// generate_matrices for revolute joints with an arbitrary fixed offset before each joint
+pub fn generate_matrices_with_offsets(origin: Matrix4<f32>, angles: &[f32], axes: &[Vector3<f32>], offsets: &[Isometry3<f32>]) -> Vec<Matrix4<f32>> {
+    generate_matrices_with_joints(origin, angles, axes, offsets, &vec![JointType::Revolute; angles.len()])
+}
+
+/// generate_matrices for a chain mixing joint types, values are radians for revolute joints and distances for prismatic joints
+pub fn generate_matrices_with_joints(origin: Matrix4<f32>, values: &[f32], axes: &[Vector3<f32>], offsets: &[Isometry3<f32>], joint_types: &[JointType]) -> Vec<Matrix4<f32>> {
+
+    assert!(values.len() == axes.len() && values.len() == offsets.len() && values.len() == joint_types.len(),
+    "Vector lengths unequal! values: {}, axes: {}, offsets: {}, joint types: {}", values.len(), axes.len(), offsets.len(), joint_types.len());
+
+    let mut matrices: Vec<Matrix4<f32>> = vec![origin * IDENTITY];
+
+    for i in 0..values.len() {
+        matrices.push(joint_matrix(joint_types[i], values[i], &axes[i], &offsets[i]));
+    }
 
     matrices
 
 }
 
+/// One row of a Denavit-Hartenberg table
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct DHParameters {
+    /// Link length along x
+    pub a: f32,
+    /// Link twist about x
+    pub alpha: f32,
+    /// Link offset along z
+    pub d: f32,
+    /// Constant added to the joint angle
+    #[serde(default)]
+    pub theta_offset: f32,
+}
+
+/// Which Denavit-Hartenberg convention a table is written in
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DHConvention {
+    /// Rz(theta) Tz(d) Tx(a) Rx(alpha)
+    #[default]
+    Classic,
+    /// Craig's convention, Rx(alpha) Tx(a) Rz(theta) Tz(d)
+    Modified,
+}
+
+/// Axes, link offsets and tool for a chain of revolute joints described by a DH table, one joint per row turning about its local z.
+/// The classic convention leaves a fixed transform after the last joint, which becomes the tool between the last joint and the end effector.
+/// The tool of the modified convention is the identity
+pub fn dh_chain(parameters: &[DHParameters], convention: DHConvention) -> (Vec<Vector3<f32>>, Vec<Isometry3<f32>>, Isometry3<f32>) {
+
+    let rotation_x = |angle: f32| UnitQuaternion::from_axis_angle(&Vector3::x_axis(), angle);
+    let rotation_z = |angle: f32| UnitQuaternion::from_axis_angle(&Vector3::z_axis(), angle);
+
+    let mut offsets: Vec<Isometry3<f32>> = vec![];
+    let mut tool: Isometry3<f32> = Isometry3::identity();
+
+    match convention {
+        DHConvention::Classic => {
+            // Tz(d) Tx(a) Rx(alpha) comes after the joint, so it becomes part of the next offset
+            let mut previous: Isometry3<f32> = Isometry3::identity();
+            for row in parameters {
+                offsets.push(previous * Isometry3::from_parts(Translation3::identity(), rotation_z(row.theta_offset)));
+                previous = Isometry3::from_parts(Translation3::new(0.0, 0.0, row.d), UnitQuaternion::identity())
+                    * Isometry3::from_parts(Translation3::new(row.a, 0.0, 0.0), rotation_x(row.alpha));
+            }
+            tool = previous;
+        },
+        DHConvention::Modified => {
+            // Rz(theta) commutes with Tz(d), so the whole row except the joint is the offset
+            for row in parameters {
+                offsets.push(Isometry3::from_parts(Translation3::identity(), rotation_x(row.alpha))
+                    * Isometry3::from_parts(Translation3::new(row.a, 0.0, 0.0), rotation_z(row.theta_offset))
+                    * Isometry3::from_parts(Translation3::new(0.0, 0.0, row.d), UnitQuaternion::identity()));
+            }
+        },
+    }
+
+    (vec![Vector3::z(); offsets.len()], offsets, tool)
+
+}
+
 /// generate all the forward partial matrix products
 /// [ O, O x A, O x A x B, O x A x B x C]
-pub fn generate_forward_matrices(matrices: &Vec<Matrix4<f32>>) -> Vec<Matrix4<f32>> {
+pub fn generate_forward_matrices(matrices: &[Matrix4<f32>]) -> Vec<Matrix4<f32>> {
 
     let mut forward: Vec<Matrix4<f32>> = vec![matrices[0]];
 
@@ -54,9 +200,51 @@ pub fn generate_forward_matrices(matrices: &Vec<Matrix4<f32>>) -> Vec<Matrix4<f3
 
 }
 
+/// Parents of each joint in a serial chain, [None, Some(0), Some(1), ...]
+pub fn chain_parents(len: usize) -> Vec<Option<usize>> {
+    (0..len).map(|i| i.checked_sub(1)).collect()
+}
+
+/// generate all the forward partial matrix products for a tree of joints.
+/// matrices are [origin, joint 0, joint 1, ...] as for a chain, parents[i] is the joint that joint i hangs off, None for the origin.
+/// Parents must come before their children, the result for chain_parents matches generate_forward_matrices
+pub fn generate_forward_matrices_tree(matrices: &[Matrix4<f32>], parents: &[Option<usize>]) -> Vec<Matrix4<f32>> {
+
+    assert!(matrices.len() == parents.len() + 1,
+    "Vector lengths unequal! matrices: {}, parents: {}", matrices.len(), parents.len());
+
+    let mut forward: Vec<Matrix4<f32>> = vec![matrices[0]];
+
+    for (i, parent) in parents.iter().enumerate() {
+        let parent_frame: usize = match parent {
+            Some(parent) => {
+                assert!(*parent < i, "Joint {} comes before its parent {}!", i, parent);
+                parent + 1
+            },
+            None => 0,
+        };
+        forward.push(forward[parent_frame] * matrices[i + 1]);
+    }
+
+    forward
+
+}
+
+/// The joint and every joint above it in the tree, from the joint up to the root
+pub fn tree_ancestors(parents: &[Option<usize>], joint: usize) -> Vec<usize> {
+
+    let mut ancestors: Vec<usize> = vec![joint];
+
+    while let Some(parent) = parents[ancestors[ancestors.len() - 1]] {
+        ancestors.push(parent);
+    }
+
+    ancestors
+}
+
 /// generate all the backwards partial matrix products
 /// [E, D x E, C x D x E] -> [C x D x E, D x E, E] + [ I ]
-pub fn generate_backward_matrices(matrices: &Vec<Matrix4<f32>>) -> Vec<Matrix4<f32>> {
+pub fn generate_backward_matrices(matrices: &[Matrix4<f32>]) -> Vec<Matrix4<f32>> {
 
     let mut backward: Vec<Matrix4<f32>> = vec![matrices[matrices.len() - 1]];
 
@@ -101,6 +289,100 @@ pub fn rotation_loss(actual: &Matrix4<f32>, expected: &Matrix4<f32>, rot_correct
 
 }
 
+/// Distance between the positions of two homogeneous transforms
+pub fn position_error(actual: &Matrix4<f32>, expected: &Matrix4<f32>) -> f32 {
+    (expected.fixed_slice::<3, 1>(0, 3) - actual.fixed_slice::<3, 1>(0, 3)).norm()
+}
+
+/// Angle in radians of the rotation between the orientations of two homogeneous transforms
+pub fn orientation_error(actual: &Matrix4<f32>, expected: &Matrix4<f32>) -> f32 {
+
+    let rotation: Matrix3<f32> = actual.fixed_slice::<3, 3>(0, 0).transpose() * expected.fixed_slice::<3, 3>(0, 0);
+
+    // atan2 stays accurate for small angles where acos of the trace does not in f32
+    let sin: f32 = Vector3::new(rotation[(2, 1)] - rotation[(1, 2)], rotation[(0, 2)] - rotation[(2, 0)], rotation[(1, 0)] - rotation[(0, 1)]).norm() / 2.0;
+    let cos: f32 = (rotation.trace() - 1.0) / 2.0;
+
+    sin.atan2(cos)
+}
+
+/// Rotation vector (axis times angle in radians) of a rotation matrix
+pub fn rotation_vector(rotation: &Matrix3<f32>) -> Vector3<f32> {
+
+    let skew: Vector3<f32> = Vector3::new(rotation[(2, 1)] - rotation[(1, 2)], rotation[(0, 2)] - rotation[(2, 0)], rotation[(1, 0)] - rotation[(0, 1)]) / 2.0;
+    let sin: f32 = skew.norm();
+    let cos: f32 = (rotation.trace() - 1.0) / 2.0;
+
+    if cos < -0.99 {
+        // near half a turn the skew part vanishes and no longer gives the axis
+        Rotation3::from_matrix_unchecked(*rotation).scaled_axis()
+    } else if sin > f32::EPSILON {
+        skew * (sin.atan2(cos) / sin)
+    } else {
+        skew
+    }
+}
+
+/// Geodesic orientation error, the rotation vector in the world frame turning the actual orientation onto the expected one.
+/// Its norm is orientation_error
+pub fn rotation_error(actual: &Matrix4<f32>, expected: &Matrix4<f32>) -> Vector3<f32> {
+    rotation_vector(&(expected.fixed_slice::<3, 3>(0, 0) * actual.fixed_slice::<3, 3>(0, 0).transpose()))
+}
+
+/// Weights of a pose loss in physical units, position errors are in the length unit of the chain (metres) and orientation errors in radians
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct PoseWeights {
+    /// Weight of the squared position error, per length unit squared
+    pub position: f32,
+    /// Weight of the squared orientation error, per radian squared
+    pub orientation: f32,
+}
+
+impl PoseWeights {
+
+    pub fn new(position: f32, orientation: f32) -> PoseWeights {
+        PoseWeights { position, orientation }
+    }
+
+    /// Weights scaling each error by a tolerance, so a loss under 1 means both errors are within their tolerance
+    pub fn from_tolerances(position: f32, orientation: f32) -> PoseWeights {
+        PoseWeights { position: 1.0 / (position * position), orientation: 1.0 / (orientation * orientation) }
+    }
+
+    /// Check the weights give a usable loss
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.position >= 0.0 && self.position.is_finite()) {
+            return Err(format!("position weight must not be negative, got {}", self.position));
+        }
+        if !(self.orientation >= 0.0 && self.orientation.is_finite()) {
+            return Err(format!("orientation weight must not be negative, got {}", self.orientation));
+        }
+        if self.position == 0.0 && self.orientation == 0.0 {
+            return Err("position and orientation weights can't both be zero".to_string());
+        }
+        Ok(())
+    }
+
+}
+
+/// Weighted sum of the squared distance and the squared geodesic angle between two homogeneous transforms
+pub fn weighted_pose_loss(actual: &Matrix4<f32>, expected: &Matrix4<f32>, weights: &PoseWeights) -> f32 {
+    weights.position * position_error(actual, expected).powi(2) + weights.orientation * rotation_error(actual, expected).norm_squared()
+}
+
+/// Gradient of weighted_pose_loss with respect to each joint, using the geometric jacobian of the chain
+pub fn weighted_pose_loss_gradient(actual: &Matrix4<f32>, expected: &Matrix4<f32>, jacobian: &DMatrix<f32>, weights: &PoseWeights) -> Vec<f32> {
+
+    let position_err: Vector3<f32> = expected.fixed_slice::<3, 1>(0, 3) - actual.fixed_slice::<3, 1>(0, 3);
+    let rotation_err: Vector3<f32> = rotation_error(actual, expected);
+
+    // turning the actual orientation by w changes the squared angle by -2 r.w, exactly, as r is an eigenvector of the log map jacobian
+    jacobian.column_iter().map(|column| {
+        -2.0 * (weights.position * position_err.dot(&column.fixed_rows::<3>(0)) + weights.orientation * rotation_err.dot(&column.fixed_rows::<3>(3)))
+    }).collect()
+
+}
+
 /// Loss/Err function of homogeneous transform matrix, with normalization parameters
 pub fn transform_loss(actual: &Matrix4<f32>, expected: &Matrix4<f32>, dist_correction: f32, rot_correction: f32) -> f32 {
 
@@ -115,3 +397,110 @@ pub fn transform_loss(actual: &Matrix4<f32>, expected: &Matrix4<f32>, dist_corre
     err
 
 }
+
+/// Gradient of transform_loss with respect to each joint angle, using the geometric jacobian of the chain
+pub fn transform_loss_gradient(actual: &Matrix4<f32>, expected: &Matrix4<f32>, jacobian: &DMatrix<f32>, dist_correction: f32, rot_correction: f32) -> Vec<f32> {
+
+    let position_err: Vector3<f32> = (expected.fixed_slice::<3, 1>(0, 3) - actual.fixed_slice::<3, 1>(0, 3)) / (dist_correction * dist_correction);
+    let rotation_err: Matrix3<f32> = (expected.fixed_slice::<3, 3>(0, 0) - actual.fixed_slice::<3, 3>(0, 0)) / (rot_correction * rot_correction * rot_correction);
+    let rotation: Matrix3<f32> = actual.fixed_slice::<3, 3>(0, 0).into_owned();
+
+    jacobian.column_iter().map(|column| {
+
+        let linear: Vector3<f32> = column.fixed_rows::<3>(0).into_owned();
+        let angular: Vector3<f32> = column.fixed_rows::<3>(3).into_owned();
+
+        // dR/dtheta = [w]x R
+        let d_rotation: Matrix3<f32> = angular.cross_matrix() * rotation;
+
+        -2.0 * (position_err.dot(&linear) + rotation_err.dot(&d_rotation))
+
+    }).collect()
+
+}
+
+/// 6xN geometric jacobian of the end effector for the chain in forward_mats, linear rows then angular rows.
+/// Joint i rotates about axes[i] at the origin of forward_mats[i + 1]
+pub fn jacobian(forward_mats: &[Matrix4<f32>], axes: &[Vector3<f32>]) -> DMatrix<f32> {
+    jacobian_of_point(forward_mats, axes, &forward_mats[forward_mats.len() - 1].fixed_slice::<3, 1>(0, 3).into_owned())
+}
+
+/// 6xN geometric jacobian of a point moved rigidly by the last joint of the chain in forward_mats
+fn jacobian_of_point(forward_mats: &[Matrix4<f32>], axes: &[Vector3<f32>], end_effector: &Vector3<f32>) -> DMatrix<f32> {
+
+    assert!(forward_mats.len() == axes.len() + 1,
+    "Vector lengths unequal! forward mats: {}, axes: {}", forward_mats.len(), axes.len());
+
+    let mut jacobian: DMatrix<f32> = DMatrix::zeros(6, axes.len());
+
+    for (i, axis) in axes.iter().enumerate() {
+
+        let joint: Matrix4<f32> = forward_mats[i + 1];
+        let axis: Vector3<f32> = joint.fixed_slice::<3, 3>(0, 0) * axis;
+        let position: Vector3<f32> = joint.fixed_slice::<3, 1>(0, 3).into_owned();
+
+        jacobian.fixed_slice_mut::<3, 1>(0, i).copy_from(&axis.cross(&(end_effector - position)));
+        jacobian.fixed_slice_mut::<3, 1>(3, i).copy_from(&axis);
+    }
+
+    jacobian
+}
+
+/// 6xN jacobian of the frame of joint `effector` in a tree from generate_forward_matrices_tree.
+/// Only the effector and its ancestors move it, the columns of every other joint are zero
+pub fn jacobian_tree(forward_mats: &[Matrix4<f32>], axes: &[Vector3<f32>], joint_types: &[JointType], parents: &[Option<usize>], effector: usize) -> DMatrix<f32> {
+
+    assert!(forward_mats.len() == axes.len() + 1 && axes.len() == joint_types.len() && axes.len() == parents.len(),
+    "Vector lengths unequal! forward mats: {}, axes: {}, joint types: {}, parents: {}", forward_mats.len(), axes.len(), joint_types.len(), parents.len());
+
+    let end_effector: Vector3<f32> = forward_mats[effector + 1].fixed_slice::<3, 1>(0, 3).into_owned();
+
+    let mut jacobian: DMatrix<f32> = DMatrix::zeros(6, axes.len());
+
+    for i in tree_ancestors(parents, effector) {
+
+        let joint: Matrix4<f32> = forward_mats[i + 1];
+        let axis: Vector3<f32> = joint.fixed_slice::<3, 3>(0, 0) * axes[i];
+        let position: Vector3<f32> = joint.fixed_slice::<3, 1>(0, 3).into_owned();
+
+        match joint_types[i] {
+            JointType::Revolute | JointType::Continuous => {
+                jacobian.fixed_slice_mut::<3, 1>(0, i).copy_from(&axis.cross(&(end_effector - position)));
+                jacobian.fixed_slice_mut::<3, 1>(3, i).copy_from(&axis);
+            },
+            JointType::Prismatic => jacobian.fixed_slice_mut::<3, 1>(0, i).copy_from(&axis),
+            JointType::Fixed => {},
+        }
+    }
+
+    jacobian
+}
+
+/// Prismatic joints only move the end effector along their axis, fixed joints don't move it at all
+pub fn jacobian_with_joints(forward_mats: &[Matrix4<f32>], axes: &[Vector3<f32>], joint_types: &[JointType]) -> DMatrix<f32> {
+    jacobian_with_tool(forward_mats, axes, joint_types, &Isometry3::identity())
+}
+
+/// jacobian_with_joints for an end effector held by a fixed tool transform after the last joint
+pub fn jacobian_with_tool(forward_mats: &[Matrix4<f32>], axes: &[Vector3<f32>], joint_types: &[JointType], tool: &Isometry3<f32>) -> DMatrix<f32> {
+
+    assert!(axes.len() == joint_types.len(),
+    "Vector lengths unequal! axes: {}, joint types: {}", axes.len(), joint_types.len());
+
+    let end_effector: Vector3<f32> = (forward_mats[forward_mats.len() - 1] * tool.to_homogeneous()).fixed_slice::<3, 1>(0, 3).into_owned();
+    let mut jacobian: DMatrix<f32> = jacobian_of_point(forward_mats, axes, &end_effector);
+
+    for (i, joint_type) in joint_types.iter().enumerate() {
+        match joint_type {
+            JointType::Revolute | JointType::Continuous => {},
+            JointType::Prismatic => {
+                let axis: Vector3<f32> = forward_mats[i + 1].fixed_slice::<3, 3>(0, 0) * axes[i];
+                jacobian.fixed_slice_mut::<3, 1>(0, i).copy_from(&axis);
+                jacobian.fixed_slice_mut::<3, 1>(3, i).fill(0.0);
+            },
+            JointType::Fixed => jacobian.column_mut(i).fill(0.0),
+        }
+    }
+
+    jacobian
+}
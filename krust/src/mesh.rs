@@ -0,0 +1,269 @@
+extern crate nalgebra as na;
+use na::{Vector3, Point3};
+use ncollide3d::procedural::{self, IndexBuffer};
+use ncollide3d::transformation;
+use std::{fmt, path::Path, collections::HashMap};
+use crate::collision_handler::Shape;
+
+/// Why a mesh file could not be loaded
+#[derive(Debug, Clone, PartialEq)]
+pub enum MeshError {
+    /// The file could not be read
+    Io(String),
+    /// The file extension is neither .stl nor .obj
+    UnknownFormat(String),
+    /// A binary STL is shorter or longer than its triangle count says
+    Truncated { expected: usize, actual: usize },
+    /// A line of an ASCII STL or OBJ could not be parsed, lines count from 1
+    Invalid { line: usize, message: String },
+    /// The file has no triangles
+    Empty,
+}
+
+impl fmt::Display for MeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MeshError::Io(err) => write!(f, "Could not read mesh: {err}"),
+            MeshError::UnknownFormat(extension) => write!(f, "Unknown mesh format \"{extension}\", expected stl or obj"),
+            MeshError::Truncated { expected, actual } => write!(f, "Binary STL should be {expected} bytes but is {actual}"),
+            MeshError::Invalid { line, message } => write!(f, "Line {line}: {message}"),
+            MeshError::Empty => write!(f, "Mesh has no triangles"),
+        }
+    }
+}
+
+impl std::error::Error for MeshError {}
+
+/// Indexed triangle mesh, e.g. a CAD model of a fixture or link. Vertices shared by triangles are welded
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mesh {
+    pub vertices: Vec<Vector3<f32>>,
+    pub indices: Vec<[usize; 3]>,
+}
+
+impl Mesh {
+
+    /// Load an .stl or .obj file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Mesh, MeshError> {
+
+        let path: &Path = path.as_ref();
+        let extension: String = path.extension().map(|extension| extension.to_string_lossy().to_lowercase()).unwrap_or_default();
+
+        match extension.as_str() {
+            "stl" => Mesh::from_stl(&std::fs::read(path).map_err(|err| MeshError::Io(err.to_string()))?),
+            "obj" => Mesh::from_obj(&std::fs::read_to_string(path).map_err(|err| MeshError::Io(err.to_string()))?),
+            _ => Err(MeshError::UnknownFormat(extension)),
+        }
+    }
+
+    /// Parse a binary or ASCII STL
+    pub fn from_stl(bytes: &[u8]) -> Result<Mesh, MeshError> {
+
+        // ASCII files start with "solid", but so do the headers of some binary files, so trust the size first
+        let count: Option<usize> = bytes.get(80..84).map(|count| u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as usize);
+        // a corrupt count can overflow the size on 32 bit targets like wasm32
+        let expected: Option<usize> = count.map(|count| count.checked_mul(50).and_then(|size| size.checked_add(84)).unwrap_or(usize::MAX));
+
+        match (count, expected) {
+            (Some(count), Some(expected)) if bytes.len() == expected => Mesh::from_binary_stl(&bytes[84..], count),
+            _ if bytes.starts_with(b"solid") => {
+                let text: &str = std::str::from_utf8(bytes).map_err(|err| MeshError::Invalid { line: 1, message: err.to_string() })?;
+                Mesh::from_ascii_stl(text)
+            },
+            (_, Some(expected)) => Err(MeshError::Truncated { expected, actual: bytes.len() }),
+            (_, None) => Err(MeshError::Truncated { expected: 84, actual: bytes.len() }),
+        }
+    }
+
+    /// Parse the vertices and faces of an OBJ, polygons are split into triangle fans and everything else is ignored
+    pub fn from_obj(text: &str) -> Result<Mesh, MeshError> {
+
+        let mut vertices: Vec<Vector3<f32>> = vec![];
+        let mut indices: Vec<[usize; 3]> = vec![];
+
+        for (number, line) in text.lines().enumerate() {
+
+            let invalid = |message: String| MeshError::Invalid { line: number + 1, message };
+            let mut tokens = line.split_whitespace();
+
+            match tokens.next() {
+                Some("v") => vertices.push(parse_vector(&mut tokens).map_err(invalid)?),
+                Some("f") => {
+                    // references are v, v/vt, v//vn or v/vt/vn, negative ones count back from the last vertex
+                    let face: Vec<usize> = tokens.map(|token| {
+                        let index: i64 = token.split('/').next().unwrap_or_default().parse().map_err(|_| format!("Invalid vertex reference \"{token}\""))?;
+                        let resolved: i64 = if index < 0 { vertices.len() as i64 + index } else { index - 1 };
+                        if resolved < 0 || resolved >= vertices.len() as i64 {
+                            return Err(format!("Vertex {index} is out of range"));
+                        }
+                        Ok(resolved as usize)
+                    }).collect::<Result<Vec<usize>, String>>().map_err(invalid)?;
+
+                    if face.len() < 3 {
+                        return Err(invalid(format!("Face has {} vertices", face.len())));
+                    }
+                    indices.extend((1..face.len() - 1).map(|i| [face[0], face[i], face[i + 1]]));
+                },
+                _ => {},
+            }
+        }
+
+        if indices.is_empty() {
+            return Err(MeshError::Empty);
+        }
+
+        Ok(Mesh { vertices, indices })
+    }
+
+    fn from_binary_stl(bytes: &[u8], count: usize) -> Result<Mesh, MeshError> {
+
+        let float = |offset: usize| f32::from_le_bytes([bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]]);
+
+        // each triangle is a normal, three vertices and a two byte attribute
+        let triangles: Vec<[Vector3<f32>; 3]> = (0..count).map(|i| {
+            let vertex = |v: usize| {
+                let offset: usize = 50 * i + 12 + 12 * v;
+                Vector3::new(float(offset), float(offset + 4), float(offset + 8))
+            };
+            [vertex(0), vertex(1), vertex(2)]
+        }).collect();
+
+        weld(&triangles)
+    }
+
+    fn from_ascii_stl(text: &str) -> Result<Mesh, MeshError> {
+
+        let mut triangles: Vec<[Vector3<f32>; 3]> = vec![];
+        let mut corners: Vec<Vector3<f32>> = vec![];
+
+        for (number, line) in text.lines().enumerate() {
+
+            let invalid = |message: String| MeshError::Invalid { line: number + 1, message };
+            let mut tokens = line.split_whitespace();
+
+            match tokens.next() {
+                Some("vertex") => corners.push(parse_vector(&mut tokens).map_err(invalid)?),
+                Some("endloop") => match corners.as_slice() {
+                    [a, b, c] => {
+                        triangles.push([*a, *b, *c]);
+                        corners.clear();
+                    },
+                    _ => return Err(invalid(format!("Loop has {} vertices", corners.len()))),
+                },
+                _ => {},
+            }
+        }
+
+        weld(&triangles)
+    }
+
+    /// The mesh scaled along each axis, e.g. from millimetres to metres or by a URDF scale
+    pub fn scaled(mut self, scale: &Vector3<f32>) -> Mesh {
+        self.vertices.iter_mut().for_each(|vertex| *vertex = vertex.component_mul(scale));
+        self
+    }
+
+    /// Solid convex hull of the mesh, the cheapest collider
+    pub fn convex_hull(&self) -> Shape {
+        Shape::ConvexHull { points: self.vertices.clone() }
+    }
+
+    /// The triangles themselves, for concave meshes. Only the surface collides, so a link fully inside the mesh is not caught
+    pub fn tri_mesh(&self) -> Shape {
+        Shape::TriMesh { vertices: self.vertices.clone(), indices: self.indices.clone() }
+    }
+
+    /// Split a concave mesh into convex hulls. concavity is relative to the size of the mesh, e.g. 0.03,
+    /// and the mesh is not split into fewer than min_parts hulls
+    pub fn convex_decomposition(&self, concavity: f32, min_parts: usize) -> Shape {
+
+        // concavity is measured along the vertex normals, so each corner gets the normal of its own face.
+        // Averaged normals point along the flat caps at the inner edges of coarse CAD meshes and hide the concavity there
+        let normals: Vec<Vector3<f32>> = self.indices.iter().map(|[a, b, c]| {
+            (self.vertices[*b] - self.vertices[*a]).cross(&(self.vertices[*c] - self.vertices[*a])).try_normalize(f32::EPSILON).unwrap_or_else(Vector3::zeros)
+        }).collect();
+        let corners: Vec<Point3<Point3<u32>>> = self.indices.iter().enumerate().map(|(face, triangle)| {
+            let corner = |index: usize| Point3::new(index as u32, face as u32, face as u32);
+            Point3::new(corner(triangle[0]), corner(triangle[1]), corner(triangle[2]))
+        }).collect();
+
+        let mesh: procedural::TriMesh<f32> = procedural::TriMesh::new(
+            self.vertices.iter().map(|vertex| Point3::from(*vertex)).collect(),
+            Some(normals),
+            None,
+            Some(IndexBuffer::Split(corners)),
+        );
+
+        let (hulls, _) = transformation::hacd(mesh, concavity, min_parts);
+
+        // parts that stay a single triangle are flat, so they can only be kept as a surface
+        let parts: Vec<Shape> = hulls.into_iter().filter_map(|hull| {
+            let points: Vec<Vector3<f32>> = hull.coords.iter().map(|point| point.coords).collect();
+            match points.len() {
+                _ if is_solid(&points) => Some(Shape::ConvexHull { points }),
+                3 => Some(Shape::TriMesh { vertices: points, indices: vec![[0, 1, 2]] }),
+                _ => None,
+            }
+        }).collect();
+
+        Shape::Compound { parts }
+    }
+}
+
+/// Whether the points span a volume, rather than lying on a plane, line or point
+pub fn is_solid(points: &[Vector3<f32>]) -> bool {
+
+    let Some(first) = points.first() else {
+        return false;
+    };
+
+    let size: f32 = points.iter().map(|point| (point - first).norm()).fold(0.0, f32::max);
+    let tolerance: f32 = 1e-4 * size;
+
+    // grow a tetrahedron from the first point, each next corner as far as possible from the span of the last ones
+    let Some(edge) = points.iter().map(|point| point - first).max_by(|a, b| a.norm().total_cmp(&b.norm())) else {
+        return false;
+    };
+    let Some(direction) = edge.try_normalize(tolerance) else {
+        return false;
+    };
+
+    let off_line = |point: &Vector3<f32>| {
+        let relative: Vector3<f32> = point - first;
+        relative - direction * direction.dot(&relative)
+    };
+    let Some(side) = points.iter().map(off_line).max_by(|a, b| a.norm().total_cmp(&b.norm())).and_then(|side| side.try_normalize(tolerance)) else {
+        return false;
+    };
+
+    let normal: Vector3<f32> = direction.cross(&side);
+    points.iter().any(|point| normal.dot(&(point - first)).abs() > tolerance)
+}
+
+fn parse_vector<'a>(tokens: &mut impl Iterator<Item = &'a str>) -> Result<Vector3<f32>, String> {
+    let mut coordinate = || -> Result<f32, String> {
+        let token: &str = tokens.next().ok_or("Expected three coordinates")?;
+        token.parse().map_err(|_| format!("Invalid coordinate \"{token}\""))
+    };
+    Ok(Vector3::new(coordinate()?, coordinate()?, coordinate()?))
+}
+
+/// Merge bit-identical corners, so triangles that share an edge share its vertices
+fn weld(triangles: &[[Vector3<f32>; 3]]) -> Result<Mesh, MeshError> {
+
+    if triangles.is_empty() {
+        return Err(MeshError::Empty);
+    }
+
+    let mut vertices: Vec<Vector3<f32>> = vec![];
+    let mut lookup: HashMap<[u32; 3], usize> = HashMap::new();
+
+    let indices: Vec<[usize; 3]> = triangles.iter().map(|triangle| triangle.map(|corner| {
+        *lookup.entry([corner.x.to_bits(), corner.y.to_bits(), corner.z.to_bits()]).or_insert_with(|| {
+            vertices.push(corner);
+            vertices.len() - 1
+        })
+    })).collect();
+
+    Ok(Mesh { vertices, indices })
+}
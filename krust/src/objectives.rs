@@ -0,0 +1,89 @@
+extern crate nalgebra as na;
+use na::{Matrix4, DMatrix};
+use serde::{Serialize, Deserialize};
+use crate::collision_handler::CollisionHandler;
+
+/// Secondary goal for a redundant arm, pursued in the null space of the target so it never pulls the end effector away
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum Objective {
+    /// Stay near a preferred posture
+    RestPosture { thetas: Vec<f32>, weight: f32 },
+    /// Keep each joint near the middle of its limits, joints without finite limits are ignored
+    JointLimits { weight: f32 },
+    /// Stay away from singularities, maximizing sqrt(det(J J^T)) of the rows the target constrains
+    Manipulability { weight: f32 },
+    /// Keep at least distance between the arm and the obstacles of the collision handler
+    Clearance { distance: f32, weight: f32 },
+}
+
+/// The arm in one configuration, as seen by the objectives
+pub struct Posture<'a> {
+    pub thetas: &'a [f32],
+    pub min_angles: &'a [f32],
+    pub max_angles: &'a [f32],
+    pub forward_mats: &'a [Matrix4<f32>],
+    /// Rows of the geometric jacobian the target constrains
+    pub jacobian: &'a DMatrix<f32>,
+    pub collision_handler: &'a CollisionHandler,
+}
+
+impl Objective {
+
+    /// Weighted cost of the posture, lower is better
+    pub fn cost(&self, posture: &Posture) -> f32 {
+        match self {
+            Objective::RestPosture { thetas, weight } => {
+                weight * 0.5 * posture.thetas.iter().zip(thetas.iter()).map(|(theta, rest)| (theta - rest).powi(2)).sum::<f32>()
+            },
+            Objective::JointLimits { weight } => {
+                // 0 in the middle of the range and 0.5 at either limit
+                weight * 0.5 * posture.thetas.iter().zip(posture.min_angles.iter().zip(posture.max_angles.iter()))
+                .filter(|(_, (min, max))| min.is_finite() && max.is_finite() && max > min)
+                .map(|(theta, (min, max))| ((2.0 * theta - max - min) / (max - min)).powi(2))
+                .sum::<f32>()
+            },
+            Objective::Manipulability { weight } => {
+                -weight * (posture.jacobian * posture.jacobian.transpose()).determinant().max(0.0).sqrt()
+            },
+            Objective::Clearance { distance, weight } => {
+                let clearance: f32 = posture.collision_handler.world_clearance(posture.forward_mats);
+                weight * 0.5 * (distance - clearance).max(0.0).powi(2)
+            },
+        }
+    }
+
+    /// Analytic gradient of the weighted cost over the joint angles,
+    /// None for objectives that have to be differentiated numerically
+    pub fn gradient(&self, posture: &Posture) -> Option<Vec<f32>> {
+        match self {
+            Objective::RestPosture { thetas, weight } => {
+                Some(posture.thetas.iter().zip(thetas.iter()).map(|(theta, rest)| weight * (theta - rest)).collect())
+            },
+            Objective::JointLimits { weight } => {
+                Some(posture.thetas.iter().zip(posture.min_angles.iter().zip(posture.max_angles.iter()))
+                .map(|(theta, (min, max))| {
+                    if min.is_finite() && max.is_finite() && max > min {
+                        weight * 2.0 * (2.0 * theta - max - min) / ((max - min) * (max - min))
+                    } else {
+                        0.0
+                    }
+                })
+                .collect())
+            },
+            Objective::Manipulability { .. } | Objective::Clearance { .. } => None,
+        }
+    }
+
+    /// Check the objective fits an arm of the given number of joints
+    pub fn validate(&self, joints: usize) -> Result<(), String> {
+        match self {
+            Objective::RestPosture { thetas, .. } if thetas.len() != joints => Err(format!("rest posture has {} angles for {} joints", thetas.len(), joints)),
+            Objective::Clearance { distance, .. } if !(*distance >= 0.0 && distance.is_finite()) => Err(format!("clearance distance must not be negative, got {}", distance)),
+            Objective::RestPosture { weight, .. } | Objective::JointLimits { weight } | Objective::Manipulability { weight } | Objective::Clearance { weight, .. }
+            if !(*weight >= 0.0 && weight.is_finite()) => Err(format!("objective weight must not be negative, got {}", weight)),
+            _ => Ok(()),
+        }
+    }
+
+}
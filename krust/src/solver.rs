@@ -0,0 +1,308 @@
+extern crate nalgebra as na;
+use na::Matrix4;
+use serde::{Serialize, Deserialize};
+use crate::{chain::Chain, target::Target};
+
+#[cfg(target_arch = "wasm32")]
+use wasm_bindgen::prelude::*;
+
+// how close to a limit a joint counts as at it
+const LIMIT_TOLERANCE: f32 = 1e-5;
+
+/// Which solver implementation to build from a JSON config
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SolverType {
+    #[default]
+    GD,
+    DLS,
+    GA,
+    CCD,
+    FABRIK,
+}
+
+/// Why a solve stopped iterating
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TerminationReason {
+    /// The loss fell below the threshold
+    Converged,
+    /// Ran out of iterations
+    MaxIterations,
+    /// The loss stopped improving
+    Stalled,
+    /// The loss stopped improving because steps were rejected by collision checks
+    CollisionBlocked,
+    /// Ran out of time
+    TimeBudget,
+    /// The target orientation has a tolerance but the solver only reaches for positions
+    OrientationUnsupported,
+}
+
+/// Outcome of a solve, reported for the final joint angles
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SolveResult {
+    pub success: bool,
+    pub reason: TerminationReason,
+    pub loss: f32,
+    pub iterations: i32,
+    /// Distance from the end effector to the target
+    pub position_error: f32,
+    /// Angle in radians between the end effector and target orientations
+    pub orientation_error: f32,
+    pub thetas: Vec<f32>,
+    /// Whether each joint ended at one of its limits
+    #[serde(default)]
+    pub at_limits: Vec<bool>,
+}
+
+/// Largest position and orientation errors of the end effector that count as reaching the target
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// Distance in the length unit of the chain
+    pub position: f32,
+    /// Angle in radians
+    pub orientation: f32,
+}
+
+impl Tolerance {
+
+    pub fn new(position: f32, orientation: f32) -> Tolerance {
+        Tolerance { position, orientation }
+    }
+
+    /// Tolerance for a chain measured in metres
+    pub fn from_millimetres_degrees(millimetres: f32, degrees: f32) -> Tolerance {
+        Tolerance { position: millimetres / 1000.0, orientation: degrees.to_radians() }
+    }
+
+    /// Whether position and orientation errors are both within the tolerance
+    pub fn contains(&self, (position_error, orientation_error): (f32, f32)) -> bool {
+        position_error <= self.position && orientation_error <= self.orientation
+    }
+
+}
+
+/// When a solve counts as converged
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Convergence {
+    /// The loss of the solver falls to the threshold, which depends on its normalization
+    Loss(f32),
+    /// The errors of the actual end effector are within the tolerance
+    Tolerance(Tolerance),
+}
+
+/// Stopping rules for a solve besides convergence
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolveLimits {
+    pub max_iterations: i32,
+    /// Wall clock budget in milliseconds
+    pub time_budget: Option<f64>,
+    /// Number of iterations without improvement before the solve counts as stalled
+    pub stall_iterations: i32,
+    /// Smallest drop in loss that counts as an improvement
+    pub stall_tolerance: f32,
+}
+
+impl SolveLimits {
+    pub fn new(max_iterations: i32) -> SolveLimits {
+        SolveLimits {
+            max_iterations,
+            time_budget: None,
+            stall_iterations: 10,
+            stall_tolerance: 1e-9,
+        }
+    }
+}
+
+/// Common interface for all IK solvers so they can be swapped at runtime
+pub trait IKSolver {
+
+    /// Iterate towards the target pose until the loss falls below thresh or one of the limits is hit
+    fn solve(&mut self, target: Matrix4<f32>, thresh: f32) -> SolveResult {
+        self.solve_target(Target::from(target), thresh)
+    }
+
+    /// Iterate towards a full or partial target until the loss falls below thresh or one of the limits is hit.
+    /// The errors in the result only count the constrained parts of the target
+    fn solve_target(&mut self, target: Target, thresh: f32) -> SolveResult {
+        self.set_target(target);
+        self.solve_until(Convergence::Loss(thresh))
+    }
+
+    /// Iterate towards a full or partial target until the end effector is within tolerance or one of the limits is hit
+    fn solve_within(&mut self, target: Target, tolerance: Tolerance) -> SolveResult {
+        self.set_target(target);
+        self.solve_until(Convergence::Tolerance(tolerance))
+    }
+
+    /// Iterate towards the current target until it converges or one of the limits is hit
+    fn solve_until(&mut self, convergence: Convergence) -> SolveResult {
+
+        let reason: TerminationReason = self.iterate(convergence);
+        let (position_error, orientation_error): (f32, f32) = self.errors();
+
+        SolveResult {
+            success: reason == TerminationReason::Converged,
+            reason,
+            loss: self.loss(),
+            iterations: self.iterations(),
+            position_error,
+            orientation_error,
+            thetas: self.thetas().to_vec(),
+            at_limits: at_limits(self.thetas(), self.min_angles(), self.max_angles()),
+        }
+
+    }
+
+    /// Iterate towards the current target from a reset, returning why it stopped
+    fn iterate(&mut self, convergence: Convergence) -> TerminationReason {
+
+        self.reset_params();
+        self.set_convergence(convergence);
+        self.update_matrices();
+
+        let limits: SolveLimits = self.limits().clone();
+        let start: f64 = now();
+
+        let mut best_loss: f32 = self.loss();
+        let mut stalled: i32 = 0;
+
+        // a position-only solver could spin until the limits without ever turning the end effector into the tolerance
+        if let Convergence::Tolerance(tolerance) = convergence {
+            if !self.solves_orientation() && tolerance.orientation.is_finite() && self.target().is_some_and(|target| target.constrains_orientation()) {
+                return TerminationReason::OrientationUnsupported;
+            }
+        }
+
+        loop {
+
+            let converged: bool = match convergence {
+                Convergence::Loss(thresh) => self.loss() <= thresh,
+                Convergence::Tolerance(tolerance) => tolerance.contains(self.errors()),
+            };
+
+            // once converged, solvers with secondary objectives keep refining them until they settle
+            if converged && self.settled() {
+                break TerminationReason::Converged;
+            }
+
+            let limit: Option<TerminationReason> = if stalled >= limits.stall_iterations {
+                Some(if self.collision_blocked() { TerminationReason::CollisionBlocked } else { TerminationReason::Stalled })
+            } else if self.iterations() >= limits.max_iterations {
+                Some(TerminationReason::MaxIterations)
+            } else if limits.time_budget.is_some_and(|budget| now() - start >= budget) {
+                Some(TerminationReason::TimeBudget)
+            } else {
+                None
+            };
+
+            if let Some(reason) = limit {
+                break if converged { TerminationReason::Converged } else { reason };
+            }
+
+            self.update();
+            self.update_matrices();
+
+            // refining at the target does not have to lower the loss any further
+            if self.loss() < best_loss - limits.stall_tolerance {
+                best_loss = self.loss();
+                stalled = 0;
+            } else if !converged {
+                stalled += 1;
+            }
+
+        }
+
+    }
+
+    /// Perform a single optimization step towards the current target
+    fn update(&mut self);
+
+    /// Generate mats and update end-effector position/loss for the current configuration
+    fn update_matrices(&mut self);
+
+    /// Reset parameters between runs
+    fn reset_params(&mut self);
+
+    /// Set the target without solving, for stepping the solver manually with update()
+    fn set_target(&mut self, target: Target);
+
+    /// Current target, if one has been set
+    fn target(&self) -> Option<Target>;
+
+    /// Position and orientation error of the end effector, only counting the constrained parts of the target
+    fn errors(&self) -> (f32, f32) {
+        self.target().map_or((0.0, 0.0), |target| target.errors(&self.end_effector()))
+    }
+
+    /// The arm being solved
+    fn chain(&self) -> &Chain;
+
+    /// Current joint angles
+    fn thetas(&self) -> &[f32] {
+        &self.chain().thetas
+    }
+
+    /// Lower limit of each joint
+    fn min_angles(&self) -> &[f32] {
+        &self.chain().min_angles
+    }
+
+    /// Upper limit of each joint
+    fn max_angles(&self) -> &[f32] {
+        &self.chain().max_angles
+    }
+
+    /// Current end-effector pose
+    fn end_effector(&self) -> Matrix4<f32> {
+        self.chain().end_effector
+    }
+
+    /// Loss of the current end-effector pose against the target
+    fn loss(&self) -> f32;
+
+    /// Number of updates since the last reset
+    fn iterations(&self) -> i32;
+
+    /// Whether a step in the last update was rejected by the collision checks
+    fn collision_blocked(&self) -> bool;
+
+    /// Whether the solver turns the end effector towards the target orientation, rather than only reaching for its position
+    fn solves_orientation(&self) -> bool {
+        true
+    }
+
+    /// Tell the solver when the solve counts as converged, for solvers that keep refining once it is
+    fn set_convergence(&mut self, _convergence: Convergence) {}
+
+    /// Whether there is nothing left to refine once converged, e.g. secondary objectives that stopped improving
+    fn settled(&self) -> bool {
+        true
+    }
+
+    /// Stopping rules used by solve()
+    fn limits(&self) -> &SolveLimits;
+
+    fn limits_mut(&mut self) -> &mut SolveLimits;
+
+}
+
+/// Whether each joint is at one of its limits, allowing for rounding
+pub fn at_limits(thetas: &[f32], min_angles: &[f32], max_angles: &[f32]) -> Vec<bool> {
+    thetas.iter().zip(min_angles.iter().zip(max_angles.iter())).map(|(theta, (min, max))| *theta <= min + LIMIT_TOLERANCE || *theta >= max - LIMIT_TOLERANCE).collect()
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+extern {
+    #[wasm_bindgen(js_namespace = Date)]
+    fn now() -> f64;
+}
+
+/// Current time in milliseconds. std::time is not available in the browser
+#[cfg(not(target_arch = "wasm32"))]
+fn now() -> f64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map_or(0.0, |time| time.as_secs_f64() * 1000.0)
+}
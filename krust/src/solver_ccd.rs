@@ -0,0 +1,232 @@
+extern crate nalgebra as na;
+use na::{Vector3, Matrix4, Isometry3, DMatrix, DVector};
+use std::{fmt, f32::consts::PI};
+use crate::{chain::Chain, matrices::generate_forward_matrices, collision_handler::CollisionHandler, solver::{IKSolver, SolveLimits}, target::Target};
+
+const ROT_CORRECTION: f32 = PI;
+const MAX_STEPS: i32 = 100;
+
+pub struct IKSolverCCD {
+
+    pub chain: Chain,
+    pub target: Option<Target>,
+
+    pub loss: f32,
+    pub iterations: i32,
+
+    pub limits: SolveLimits,
+    collision_blocked: bool,
+
+    pub collision_handler: CollisionHandler,
+
+}
+
+/// Cyclic Coordinate Descent solver.
+/// Sweeps from the end effector back to the base, rotating each joint about its axis
+/// to the angle that best aligns the end effector with the target
+impl IKSolverCCD {
+
+    pub fn new(origin: Matrix4<f32>, thetas: &[f32], axes: &[Vector3<f32>], radii: &[f32], min_angles: &[f32], max_angles: &[f32], col_handler: CollisionHandler) -> IKSolverCCD {
+
+        if let Err(err) = col_handler.validate_arm(thetas.len()) {
+            panic!("Invalid arm colliders! {}", err);
+        }
+
+        IKSolverCCD {
+            chain: Chain::new(origin, thetas, axes, radii, min_angles, max_angles),
+            target: None,
+
+            loss: 100.0,
+            iterations: 0,
+
+            limits: SolveLimits::new(MAX_STEPS),
+            collision_blocked: false,
+
+            collision_handler: col_handler,
+        }
+    }
+
+    /// Replace the straight links from radii with an arbitrary fixed offset before each joint,
+    /// for offset elbows and shoulders. radii become the lengths of the offsets
+    pub fn with_offsets(mut self, offsets: &[Isometry3<f32>]) -> IKSolverCCD {
+
+        self.chain.set_offsets(offsets);
+        self.collision_handler.set_link_offsets(offsets);
+
+        self
+    }
+
+    /// Hold the end effector at a fixed transform from the last joint, e.g. a gripper
+    pub fn with_tool(mut self, tool: &Isometry3<f32>) -> IKSolverCCD {
+
+        self.chain.set_tool(tool);
+
+        self
+    }
+
+    /// Sweep every joint once, from the end effector back to the base
+    fn update_thetas(&mut self) {
+
+        let target: Target = self.target.unwrap();
+
+        // weights matching transform_loss, so each joint lands on the minimum of the loss about its axis
+        let position_weight: f32 = 1.0 / (self.chain.arm_length * self.chain.arm_length);
+        let rotation_weight: f32 = 1.0 / (ROT_CORRECTION * ROT_CORRECTION * ROT_CORRECTION);
+
+        self.collision_blocked = false;
+
+        for i in (0..self.chain.len()).rev() {
+
+            let axis_length: f32 = self.chain.axes[i].norm();
+            if axis_length == 0.0 {
+                continue;
+            }
+
+            let joint: Matrix4<f32> = self.chain.forward_mats[i + 1];
+            let end_effector: Matrix4<f32> = joint * self.chain.backward_mats[i + 2] * self.chain.tool.to_homogeneous();
+
+            let axis: Vector3<f32> = joint.fixed_slice::<3, 3>(0, 0) * self.chain.axes[i] / axis_length;
+            let pivot: Vector3<f32> = joint.fixed_slice::<3, 1>(0, 3).into_owned();
+
+            // best rotation about the axis for a weighted set of vector pairs
+            let mut sin: f32 = 0.0;
+            let mut cos: f32 = 0.0;
+            let mut align = |weight: f32, from: Vector3<f32>, to: Vector3<f32>| {
+                sin += weight * axis.dot(&from.cross(&to));
+                cos += weight * (from.dot(&to) - axis.dot(&from) * axis.dot(&to));
+            };
+
+            let end_position: Vector3<f32> = end_effector.fixed_slice::<3, 1>(0, 3).into_owned();
+
+            let angle: f32 = match target {
+                Target::Pose { pose } => {
+                    align(position_weight, end_position - pivot, pose.fixed_slice::<3, 1>(0, 3) - pivot);
+                    for c in 0..3 {
+                        align(rotation_weight, end_effector.fixed_slice::<3, 1>(0, c).into_owned(), pose.fixed_slice::<3, 1>(0, c).into_owned());
+                    }
+                    sin.atan2(cos)
+                },
+                Target::Position { position } => {
+                    align(position_weight, end_position - pivot, position - pivot);
+                    sin.atan2(cos)
+                },
+                Target::Axis { position, tool_axis, direction } => {
+                    align(position_weight, end_position - pivot, position - pivot);
+                    align(rotation_weight, end_effector.fixed_slice::<3, 3>(0, 0) * tool_axis.normalize(), direction.normalize());
+                    sin.atan2(cos)
+                },
+                Target::Masked { .. } => {
+                    // masked axes can't be written as vector pairs, so take a weighted least-squares step on the joint instead
+                    let column: DMatrix<f32> = DMatrix::from_iterator(6, 1, axis.cross(&(end_position - pivot)).iter().chain(axis.iter()).copied());
+                    let (error, column): (DVector<f32>, DMatrix<f32>) = target.task(&end_effector, &column);
+                    let weights: DVector<f32> = DVector::from_fn(error.len(), |r, _| if r < target.position_rows() { position_weight } else { 2.0 * rotation_weight });
+
+                    let step: f32 = column.column(0).component_mul(&weights).dot(&error);
+                    let curvature: f32 = column.column(0).component_mul(&weights).dot(&column.column(0));
+                    let mut angle: f32 = if curvature > 0.0 { na::clamp(step / curvature, -PI, PI) } else { 0.0 };
+
+                    // the step is only linear, so back off until it no longer raises the loss
+                    let loss: f32 = self.calculate_loss(&end_effector);
+                    let turned = |angle: f32| {
+                        let rotation: Matrix4<f32> = Matrix4::new_rotation(axis * angle);
+                        Matrix4::new_translation(&pivot) * rotation * Matrix4::new_translation(&-pivot) * end_effector
+                    };
+                    for _ in 0..10 {
+                        if self.calculate_loss(&turned(angle)) <= loss {
+                            break;
+                        }
+                        angle *= 0.5;
+                    }
+                    angle
+                },
+            };
+
+            // check angle constraints
+            let new_theta: f32 = na::clamp(self.chain.thetas[i] + angle / axis_length, self.chain.min_angles[i], self.chain.max_angles[i]);
+
+            let mut mats: Vec<Matrix4<f32>> = self.chain.mats.clone();
+            mats[i + 1] = self.chain.joint_matrix(i, new_theta);
+            let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&mats);
+
+            // check collision constraints
+            if !self.collision_handler.is_arm_colliding_self(i, &forward_mats) && !self.collision_handler.is_arm_colliding_world(i, &forward_mats) {
+                self.chain.thetas[i] = new_theta;
+                self.update_matrices();
+            } else {
+                self.collision_blocked = true;
+            }
+
+        }
+
+    }
+
+    /// Update iteration count
+    fn update_params(&mut self) {
+        self.iterations += 1;
+    }
+
+    /// Calculate loss for an end effector position
+    fn calculate_loss(&self, end_effector: &Matrix4<f32>) -> f32 {
+        self.target.unwrap().loss(end_effector, self.chain.arm_length, ROT_CORRECTION)
+    }
+
+}
+
+impl IKSolver for IKSolverCCD {
+
+    fn update_matrices(&mut self) {
+        self.chain.update();
+        self.loss = self.calculate_loss(&self.chain.end_effector);
+    }
+
+    fn update(&mut self) {
+        self.update_matrices();
+        self.update_thetas();
+        self.update_params();
+    }
+
+    // Reset parameters between runs
+    fn reset_params(&mut self) {
+        self.iterations = 0;
+        self.loss = 100.0;
+    }
+
+    fn set_target(&mut self, target: Target) {
+        self.target = Some(target);
+    }
+
+    fn target(&self) -> Option<Target> {
+        self.target
+    }
+
+    fn chain(&self) -> &Chain {
+        &self.chain
+    }
+
+    fn loss(&self) -> f32 {
+        self.loss
+    }
+
+    fn iterations(&self) -> i32 {
+        self.iterations
+    }
+
+    fn collision_blocked(&self) -> bool {
+        self.collision_blocked
+    }
+
+    fn limits(&self) -> &SolveLimits {
+        &self.limits
+    }
+
+    fn limits_mut(&mut self) -> &mut SolveLimits {
+        &mut self.limits
+    }
+
+}
+
+impl fmt::Display for IKSolverCCD {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.chain)
+    }
+}
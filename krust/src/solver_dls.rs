@@ -0,0 +1,400 @@
+extern crate nalgebra as na;
+use serde::{Serialize, Deserialize};
+use na::{Vector3, Matrix4, Isometry3, DMatrix, DVector};
+use std::{fmt, f32::consts::PI};
+use crate::{chain::Chain, collision_handler::CollisionHandler, solver::{IKSolver, SolveLimits, Convergence}, target::Target, objectives::{Objective, Posture}};
+
+const ROT_CORRECTION: f32 = PI;
+const MAX_STEPS: i32 = 50;
+
+const INITIAL_DAMPING: f32 = 0.1;
+const MIN_DAMPING: f32 = 0.0001;
+const MAX_DAMPING: f32 = 1000.0;
+const DAMPING_INCREASE: f32 = 4.0;
+const DAMPING_DECREASE: f32 = 0.5;
+
+// step for the central differences of the objective costs
+const OBJECTIVE_DELTA: f32 = 0.001;
+// how much worse than the plain step a null space step may leave the target
+const NULL_SPACE_SLACK: f32 = 1e-9;
+// times the secondary step is halved before giving up on it
+const NULL_SPACE_HALVINGS: usize = 6;
+// smallest drop in the objective costs that keeps refining them at the target
+const OBJECTIVE_TOLERANCE: f32 = 1e-6;
+
+/// Tuning parameters for the damped least-squares solver
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct DLSConfig {
+    /// Scale of the step down the objective gradients, taken in the null space of the target
+    pub null_space_gain: f32,
+}
+
+impl Default for DLSConfig {
+    fn default() -> DLSConfig {
+        DLSConfig {
+            null_space_gain: 1.0,
+        }
+    }
+}
+
+impl DLSConfig {
+
+    /// Check the parameters are usable
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.null_space_gain >= 0.0 && self.null_space_gain.is_finite()) {
+            return Err(format!("null space gain must not be negative, got {}", self.null_space_gain));
+        }
+        Ok(())
+    }
+
+}
+
+/// A configuration the solver could step to
+struct Trial {
+    thetas: Vec<f32>,
+    forward_mats: Vec<Matrix4<f32>>,
+    loss: f32,
+    colliding: bool,
+}
+
+pub struct IKSolverDLS {
+
+    pub chain: Chain,
+    pub target: Option<Target>,
+
+    pub loss: f32,
+    pub iterations: i32,
+
+    /// Secondary objectives for redundant arms, followed in the null space of the target
+    pub objectives: Vec<Objective>,
+    objectives_settled: bool,
+    convergence: Option<Convergence>,
+
+    pub config: DLSConfig,
+    damping: f32,
+
+    pub limits: SolveLimits,
+    collision_blocked: bool,
+
+    pub collision_handler: CollisionHandler,
+
+}
+
+/// Damped least-squares (Levenberg-Marquardt) solver.
+/// Steps all joints at once along the pseudo-inverse of the geometric Jacobian,
+/// raising the damping when a step fails and lowering it when a step succeeds
+impl IKSolverDLS {
+
+    pub fn new(origin: Matrix4<f32>, thetas: &[f32], axes: &[Vector3<f32>], radii: &[f32], min_angles: &[f32], max_angles: &[f32], col_handler: CollisionHandler) -> IKSolverDLS {
+
+        if let Err(err) = col_handler.validate_arm(thetas.len()) {
+            panic!("Invalid arm colliders! {}", err);
+        }
+
+        IKSolverDLS {
+            chain: Chain::new(origin, thetas, axes, radii, min_angles, max_angles),
+            target: None,
+
+            loss: 100.0,
+            iterations: 0,
+
+            objectives: Vec::new(),
+            objectives_settled: true,
+            convergence: None,
+
+            config: DLSConfig::default(),
+            damping: INITIAL_DAMPING,
+
+            limits: SolveLimits::new(MAX_STEPS),
+            collision_blocked: false,
+
+            collision_handler: col_handler,
+        }
+    }
+
+    /// Replace the straight links from radii with an arbitrary fixed offset before each joint,
+    /// for offset elbows and shoulders. radii become the lengths of the offsets
+    pub fn with_offsets(mut self, offsets: &[Isometry3<f32>]) -> IKSolverDLS {
+
+        self.chain.set_offsets(offsets);
+        self.collision_handler.set_link_offsets(offsets);
+
+        self
+    }
+
+    /// Hold the end effector at a fixed transform from the last joint, e.g. a gripper
+    pub fn with_tool(mut self, tool: &Isometry3<f32>) -> IKSolverDLS {
+
+        self.chain.set_tool(tool);
+
+        self
+    }
+
+    /// Add secondary objectives. Each step also descends their weighted costs,
+    /// projected into the null space of the target jacobian so the end effector is not disturbed
+    pub fn with_objectives(mut self, objectives: &[Objective]) -> IKSolverDLS {
+
+        for objective in objectives {
+            if let Err(err) = objective.validate(self.chain.len()) {
+                panic!("Invalid objective! {}", err);
+            }
+        }
+
+        self.objectives = objectives.to_vec();
+
+        self
+    }
+
+    /// Replace the default tuning parameters
+    pub fn with_config(mut self, config: DLSConfig) -> IKSolverDLS {
+
+        if let Err(err) = config.validate() {
+            panic!("Invalid config! {}", err);
+        }
+
+        self.config = config;
+
+        self
+    }
+
+    /// Take a damped least-squares step, adapting the damping based on whether it succeeded
+    fn update_thetas(&mut self) {
+
+        // only the rows of the jacobian the target constrains
+        let (error, jacobian): (DVector<f32>, DMatrix<f32>) = self.target.unwrap().task(&self.chain.end_effector, &self.chain.jacobian(&self.chain.forward_mats));
+
+        // dtheta = J^T (J J^T + lambda^2 I)^-1 e
+        let damped: DMatrix<f32> = &jacobian * jacobian.transpose() + DMatrix::identity(error.len(), error.len()) * (self.damping * self.damping);
+
+        let pseudo_inverse: DMatrix<f32> = match damped.cholesky() {
+            Some(cholesky) => jacobian.transpose() * cholesky.inverse(),
+            None => {
+                self.damping = f32::min(self.damping * DAMPING_INCREASE, MAX_DAMPING);
+                return;
+            }
+        };
+
+        let step: DVector<f32> = &pseudo_inverse * error;
+        let mut trial: Trial = self.try_step(&step);
+
+        // descend the objectives in the null space, N = I - J# J. The combined step is kept if it holds the target
+        // as well as the plain step, or keeps it converged, so the objectives still act once the target is reached
+        let mut objectives_improvement: f32 = 0.0;
+
+        if !self.objectives.is_empty() {
+
+            let null_space: DMatrix<f32> = DMatrix::identity(step.len(), step.len()) - &pseudo_inverse * &jacobian;
+            let mut secondary: DVector<f32> = -(null_space * self.objectives_gradient()) * self.config.null_space_gain;
+
+            // the null space is only exact to first order, so halve the secondary step until it holds the target
+            for _ in 0..NULL_SPACE_HALVINGS {
+
+                let combined: Trial = self.try_step(&(&step + &secondary));
+                let holds_target: bool = combined.loss < self.loss || combined.loss <= trial.loss + NULL_SPACE_SLACK || self.converged(&combined);
+
+                if holds_target && !combined.colliding {
+                    objectives_improvement = self.cost(&self.chain.thetas, &self.chain.forward_mats) - self.cost(&combined.thetas, &combined.forward_mats);
+                    trial = combined;
+                    break;
+                }
+
+                secondary *= 0.5;
+            }
+        }
+
+        self.objectives_settled = objectives_improvement < OBJECTIVE_TOLERANCE;
+        self.collision_blocked = trial.colliding && trial.loss < self.loss;
+
+        // a step that only refines the objectives may not lose the target
+        let refines: bool = objectives_improvement > 0.0 && (trial.loss <= self.loss + NULL_SPACE_SLACK || self.converged(&trial));
+
+        if !trial.colliding && (trial.loss < self.loss || refines) {
+            self.chain.thetas = trial.thetas;
+            self.update_matrices();
+            self.damping = f32::max(self.damping * DAMPING_DECREASE, MIN_DAMPING);
+        } else {
+            self.damping = f32::min(self.damping * DAMPING_INCREASE, MAX_DAMPING);
+        }
+
+    }
+
+    /// Joint angles, loss and whether the arm collides after a step, clamped to the angle constraints
+    fn try_step(&self, step: &DVector<f32>) -> Trial {
+
+        let thetas: Vec<f32> = self.chain.thetas
+        .iter()
+        .enumerate()
+        .map(|(i, theta)| na::clamp(theta + step[i], self.chain.min_angles[i], self.chain.max_angles[i]))
+        .collect();
+
+        let forward_mats: Vec<Matrix4<f32>> = self.chain.forward_matrices(&thetas);
+        let loss: f32 = self.calculate_loss(&self.chain.tool_pose(&forward_mats));
+
+        // check collision constraints, every joint may have moved so check the whole arm
+        let colliding: bool = self.collision_handler.is_arm_colliding_self_naive(&forward_mats) || self.collision_handler.is_arm_colliding_world_naive(&forward_mats);
+
+        Trial { thetas, forward_mats, loss, colliding }
+    }
+
+    /// Whether the trial meets the convergence of the current solve
+    fn converged(&self, trial: &Trial) -> bool {
+        match self.convergence {
+            Some(Convergence::Loss(thresh)) => trial.loss <= thresh,
+            Some(Convergence::Tolerance(tolerance)) => tolerance.contains(self.target.unwrap().errors(&self.chain.tool_pose(&trial.forward_mats))),
+            None => false,
+        }
+    }
+
+    /// Summed cost of the objectives for a configuration
+    pub fn objectives_cost(&self, thetas: &[f32]) -> f32 {
+        self.cost(thetas, &self.chain.forward_matrices(thetas))
+    }
+
+    /// Summed cost of the objectives for a configuration and its forward matrices
+    fn cost(&self, thetas: &[f32], forward_mats: &[Matrix4<f32>]) -> f32 {
+        self.posture(thetas, forward_mats, |posture| self.objectives.iter().map(|objective| objective.cost(posture)).sum())
+    }
+
+    /// Evaluate f on the posture of a configuration and its forward matrices
+    fn posture<R>(&self, thetas: &[f32], forward_mats: &[Matrix4<f32>], f: impl FnOnce(&Posture) -> R) -> R {
+
+        let (_, jacobian): (DVector<f32>, DMatrix<f32>) = self.target.unwrap().task(&self.chain.tool_pose(forward_mats), &self.chain.jacobian(forward_mats));
+
+        f(&Posture {
+            thetas,
+            min_angles: &self.chain.min_angles,
+            max_angles: &self.chain.max_angles,
+            forward_mats,
+            jacobian: &jacobian,
+            collision_handler: &self.collision_handler,
+        })
+    }
+
+    /// Gradient of the objective costs, analytic where the objective has one and by central differences otherwise
+    fn objectives_gradient(&self) -> DVector<f32> {
+
+        let mut gradient: DVector<f32> = DVector::zeros(self.chain.len());
+        let mut numeric: Vec<&Objective> = Vec::new();
+
+        self.posture(&self.chain.thetas, &self.chain.forward_mats, |posture| {
+            for objective in &self.objectives {
+                match objective.gradient(posture) {
+                    Some(analytic) => gradient += DVector::from_vec(analytic),
+                    None => numeric.push(objective),
+                }
+            }
+        });
+
+        if numeric.is_empty() {
+            return gradient;
+        }
+
+        let numeric_cost = |thetas: &[f32], forward_mats: &[Matrix4<f32>]| -> f32 {
+            self.posture(thetas, forward_mats, |posture| numeric.iter().map(|objective| objective.cost(posture)).sum())
+        };
+
+        for i in 0..self.chain.len() {
+            let mut plus: Vec<f32> = self.chain.thetas.clone();
+            let mut minus: Vec<f32> = self.chain.thetas.clone();
+            plus[i] += OBJECTIVE_DELTA;
+            minus[i] -= OBJECTIVE_DELTA;
+            gradient[i] += (numeric_cost(&plus, &self.perturbed_forward(i, plus[i])) - numeric_cost(&minus, &self.perturbed_forward(i, minus[i]))) / (2.0 * OBJECTIVE_DELTA);
+        }
+
+        gradient
+    }
+
+    /// Forward matrices with only joint i moved to theta, reusing the cached frames above the joint and the link matrices below it
+    fn perturbed_forward(&self, i: usize, theta: f32) -> Vec<Matrix4<f32>> {
+
+        let mut forward: Vec<Matrix4<f32>> = self.chain.forward_mats[..=i].to_vec();
+        forward.push(forward[i] * self.chain.joint_matrix(i, theta));
+
+        for k in i + 2..self.chain.mats.len() {
+            forward.push(forward[k - 1] * self.chain.mats[k]);
+        }
+
+        forward
+    }
+
+    /// Update iteration count
+    fn update_params(&mut self) {
+        self.iterations += 1;
+    }
+
+    /// Calculate loss for the descent
+    fn calculate_loss(&self, end_effector: &Matrix4<f32>) -> f32 {
+        self.target.unwrap().loss(end_effector, self.chain.arm_length, ROT_CORRECTION)
+    }
+
+}
+
+impl IKSolver for IKSolverDLS {
+
+    fn update_matrices(&mut self) {
+        self.chain.update();
+        self.loss = self.calculate_loss(&self.chain.end_effector);
+    }
+
+    fn update(&mut self) {
+        self.update_matrices();
+        self.update_thetas();
+        self.update_params();
+    }
+
+    // Reset parameters between runs
+    fn reset_params(&mut self) {
+        self.iterations = 0;
+        self.loss = 100.0;
+        self.damping = INITIAL_DAMPING;
+        self.objectives_settled = self.objectives.is_empty();
+    }
+
+    fn set_target(&mut self, target: Target) {
+        self.target = Some(target);
+    }
+
+    fn target(&self) -> Option<Target> {
+        self.target
+    }
+
+    fn chain(&self) -> &Chain {
+        &self.chain
+    }
+
+    fn loss(&self) -> f32 {
+        self.loss
+    }
+
+    fn iterations(&self) -> i32 {
+        self.iterations
+    }
+
+    fn collision_blocked(&self) -> bool {
+        self.collision_blocked
+    }
+
+    fn set_convergence(&mut self, convergence: Convergence) {
+        self.convergence = Some(convergence);
+    }
+
+    fn settled(&self) -> bool {
+        self.objectives_settled
+    }
+
+    fn limits(&self) -> &SolveLimits {
+        &self.limits
+    }
+
+    fn limits_mut(&mut self) -> &mut SolveLimits {
+        &mut self.limits
+    }
+
+}
+
+impl fmt::Display for IKSolverDLS {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.chain)
+    }
+}
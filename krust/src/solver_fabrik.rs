@@ -0,0 +1,246 @@
+extern crate nalgebra as na;
+use na::{Vector3, Matrix4, Isometry3};
+use std::fmt;
+use crate::{chain::Chain, matrices::{generate_forward_matrices, offset_length}, collision_handler::CollisionHandler, solver::{IKSolver, SolveLimits}, target::Target};
+
+const MAX_STEPS: i32 = 100;
+
+pub struct IKSolverFABRIK {
+
+    pub chain: Chain,
+    pub target: Option<Target>,
+
+    pub loss: f32,
+    pub iterations: i32,
+
+    pub limits: SolveLimits,
+    collision_blocked: bool,
+
+    pub collision_handler: CollisionHandler,
+
+}
+
+/// Forward And Backward Reaching IK solver.
+/// Solves the joint positions for a position-only target, then projects them back
+/// onto the revolute joint angles. The loss ignores the orientation of the target, and any masked out coordinates of its position.
+/// solve_within stops straight away with OrientationUnsupported if the target orientation has a finite tolerance
+impl IKSolverFABRIK {
+
+    pub fn new(origin: Matrix4<f32>, thetas: &[f32], axes: &[Vector3<f32>], radii: &[f32], min_angles: &[f32], max_angles: &[f32], col_handler: CollisionHandler) -> IKSolverFABRIK {
+
+        if let Err(err) = col_handler.validate_arm(thetas.len()) {
+            panic!("Invalid arm colliders! {}", err);
+        }
+
+        IKSolverFABRIK {
+            chain: Chain::new(origin, thetas, axes, radii, min_angles, max_angles),
+            target: None,
+
+            loss: 100.0,
+            iterations: 0,
+
+            limits: SolveLimits::new(MAX_STEPS),
+            collision_blocked: false,
+
+            collision_handler: col_handler,
+        }
+    }
+
+    /// Replace the straight links from radii with an arbitrary fixed offset before each joint,
+    /// for offset elbows and shoulders. radii become the lengths of the offsets
+    pub fn with_offsets(mut self, offsets: &[Isometry3<f32>]) -> IKSolverFABRIK {
+
+        self.chain.set_offsets(offsets);
+        self.collision_handler.set_link_offsets(offsets);
+
+        self
+    }
+
+    /// Hold the end effector at a fixed transform from the last joint, e.g. a gripper
+    pub fn with_tool(mut self, tool: &Isometry3<f32>) -> IKSolverFABRIK {
+
+        self.chain.set_tool(tool);
+
+        self
+    }
+
+    /// One backward and forward reaching pass over the joint positions
+    fn reach(&self) -> Vec<Vector3<f32>> {
+
+        let target: Vector3<f32> = self.target.unwrap().position_goal(&self.chain.end_effector);
+
+        // positions[k] is the position of joint k, followed by the end effector if the tool moves it off the last joint.
+        // The first joint never moves since no joint comes before its link
+        let mut positions: Vec<Vector3<f32>> = self.positions(&self.chain.forward_mats);
+        let base: Vector3<f32> = positions[0];
+        let last: usize = positions.len() - 1;
+
+        // backward, pin the end effector to the target
+        positions[last] = target;
+        for k in (0..last).rev() {
+            positions[k] = reach_towards(&positions[k + 1], &positions[k], self.link_length(k + 1));
+        }
+
+        // forward, pin the base back in place
+        positions[0] = base;
+        for k in 0..last {
+            positions[k + 1] = reach_towards(&positions[k], &positions[k + 1], self.link_length(k + 1));
+        }
+
+        positions
+    }
+
+    /// World positions of each joint, followed by the end effector if the tool moves it off the last joint
+    fn positions(&self, forward_mats: &[Matrix4<f32>]) -> Vec<Vector3<f32>> {
+
+        let mut positions: Vec<Vector3<f32>> = joint_positions(forward_mats);
+
+        if offset_length(&self.chain.tool) > 0.0 {
+            positions.push(self.chain.tool_pose(forward_mats).fixed_slice::<3, 1>(0, 3).into_owned());
+        }
+
+        positions
+    }
+
+    /// Distance from position k - 1 to position k
+    fn link_length(&self, k: usize) -> f32 {
+        if k < self.chain.len() { self.chain.radii[k] } else { offset_length(&self.chain.tool) }
+    }
+
+    /// Rotate each joint about its axis so the links downstream of it line up with the solved positions
+    fn update_thetas(&mut self) {
+
+        self.collision_blocked = false;
+
+        let solved: Vec<Vector3<f32>> = self.reach();
+
+        // only joints with positions downstream of them, without a tool the last joint just changes orientation
+        for i in 0..solved.len() - 1 {
+
+            let axis_length: f32 = self.chain.axes[i].norm();
+            if axis_length == 0.0 {
+                continue;
+            }
+
+            let joint: Matrix4<f32> = self.chain.forward_mats[i + 1];
+            let axis: Vector3<f32> = joint.fixed_slice::<3, 3>(0, 0) * self.chain.axes[i] / axis_length;
+            let pivot: Vector3<f32> = joint.fixed_slice::<3, 1>(0, 3).into_owned();
+
+            // best rotation about the axis carrying every downstream joint towards its solved position
+            let current: Vec<Vector3<f32>> = self.positions(&self.chain.forward_mats);
+            let mut sin: f32 = 0.0;
+            let mut cos: f32 = 0.0;
+            for k in i + 1..current.len() {
+                let from: Vector3<f32> = current[k] - pivot;
+                let to: Vector3<f32> = solved[k] - pivot;
+                sin += axis.dot(&from.cross(&to));
+                cos += from.dot(&to) - axis.dot(&from) * axis.dot(&to);
+            }
+
+            // check angle constraints
+            let new_theta: f32 = na::clamp(self.chain.thetas[i] + sin.atan2(cos) / axis_length, self.chain.min_angles[i], self.chain.max_angles[i]);
+
+            let mut mats: Vec<Matrix4<f32>> = self.chain.mats.clone();
+            mats[i + 1] = self.chain.joint_matrix(i, new_theta);
+            let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&mats);
+
+            // check collision constraints
+            if !self.collision_handler.is_arm_colliding_self(i, &forward_mats) && !self.collision_handler.is_arm_colliding_world(i, &forward_mats) {
+                self.chain.thetas[i] = new_theta;
+                self.update_matrices();
+            } else {
+                self.collision_blocked = true;
+            }
+
+        }
+
+    }
+
+    /// Update iteration count
+    fn update_params(&mut self) {
+        self.iterations += 1;
+    }
+
+    /// Calculate loss for an end effector position
+    fn calculate_loss(&self, end_effector: &Matrix4<f32>) -> f32 {
+        self.target.unwrap().position_loss(end_effector, self.chain.arm_length)
+    }
+
+}
+
+impl IKSolver for IKSolverFABRIK {
+
+    fn update_matrices(&mut self) {
+        self.chain.update();
+        self.loss = self.calculate_loss(&self.chain.end_effector);
+    }
+
+    fn update(&mut self) {
+        self.update_matrices();
+        self.update_thetas();
+        self.update_params();
+    }
+
+    // Reset parameters between runs
+    fn reset_params(&mut self) {
+        self.iterations = 0;
+        self.loss = 100.0;
+    }
+
+    fn set_target(&mut self, target: Target) {
+        self.target = Some(target);
+    }
+
+    fn target(&self) -> Option<Target> {
+        self.target
+    }
+
+    fn chain(&self) -> &Chain {
+        &self.chain
+    }
+
+    fn loss(&self) -> f32 {
+        self.loss
+    }
+
+    fn iterations(&self) -> i32 {
+        self.iterations
+    }
+
+    fn collision_blocked(&self) -> bool {
+        self.collision_blocked
+    }
+
+    fn solves_orientation(&self) -> bool {
+        false
+    }
+
+    fn limits(&self) -> &SolveLimits {
+        &self.limits
+    }
+
+    fn limits_mut(&mut self) -> &mut SolveLimits {
+        &mut self.limits
+    }
+
+}
+
+impl fmt::Display for IKSolverFABRIK {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.chain)
+    }
+}
+
+/// World positions of each joint, the joint frames are forward_mats[1..]
+fn joint_positions(forward_mats: &[Matrix4<f32>]) -> Vec<Vector3<f32>> {
+    forward_mats[1..].iter().map(|mat| mat.fixed_slice::<3, 1>(0, 3).into_owned()).collect()
+}
+
+/// Place a point on the line from anchor towards point, at the link length from the anchor
+fn reach_towards(anchor: &Vector3<f32>, point: &Vector3<f32>, length: f32) -> Vector3<f32> {
+    let direction: Vector3<f32> = point - anchor;
+    match direction.try_normalize(f32::EPSILON) {
+        Some(direction) => anchor + direction * length,
+        None => *point,
+    }
+}
@@ -0,0 +1,309 @@
+extern crate nalgebra as na;
+use na::{Vector3, Matrix4, Isometry3};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use rayon::prelude::*;
+use serde::{Serialize, Deserialize};
+use std::{fmt, f32::consts::PI};
+use crate::{chain::Chain, collision_handler::CollisionHandler, solver::{IKSolver, SolveLimits}, target::Target};
+
+const ROT_CORRECTION: f32 = PI;
+const MAX_STEPS: i32 = 100;
+const TOURNAMENT_SIZE: usize = 3;
+
+/// Tuning parameters for the genetic algorithm
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct GAConfig {
+    /// Number of individuals in each generation
+    pub population_size: usize,
+    /// Number of best individuals copied unchanged into the next generation
+    pub elitism: usize,
+    /// Chance that a child is bred from two parents instead of copied from one
+    pub crossover_rate: f32,
+    /// Chance that each joint angle of a child is mutated
+    pub mutation_rate: f32,
+    /// Largest change in radians a single mutation can make
+    pub mutation_scale: f32,
+    /// Seed for reproducible runs, random if not set
+    pub seed: Option<u64>,
+}
+
+impl Default for GAConfig {
+    fn default() -> GAConfig {
+        GAConfig {
+            population_size: 100,
+            elitism: 4,
+            crossover_rate: 0.7,
+            mutation_rate: 0.2,
+            mutation_scale: 0.3,
+            seed: None,
+        }
+    }
+}
+
+impl GAConfig {
+
+    /// Check the rates and the mutation scale are usable
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.crossover_rate) {
+            return Err(format!("crossover rate must be in [0, 1], got {}", self.crossover_rate));
+        }
+        if !(0.0..=1.0).contains(&self.mutation_rate) {
+            return Err(format!("mutation rate must be in [0, 1], got {}", self.mutation_rate));
+        }
+        if !(self.mutation_scale >= 0.0 && self.mutation_scale.is_finite()) {
+            return Err(format!("mutation scale must not be negative, got {}", self.mutation_scale));
+        }
+        Ok(())
+    }
+
+}
+
+pub struct IKSolverGA {
+
+    pub chain: Chain,
+    pub target: Option<Target>,
+
+    pub loss: f32,
+    pub iterations: i32,
+
+    pub config: GAConfig,
+    pub population: Vec<Vec<f32>>,
+    rng: StdRng,
+
+    pub limits: SolveLimits,
+    collision_blocked: bool,
+
+    pub collision_handler: CollisionHandler,
+
+}
+
+/// Genetic algorithm solver.
+/// Evolves a population of arm configurations, evaluating fitness in parallel,
+/// which gives a global search for targets where gradient descent gets stuck
+impl IKSolverGA {
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(origin: Matrix4<f32>, thetas: &[f32], axes: &[Vector3<f32>], radii: &[f32], min_angles: &[f32], max_angles: &[f32], col_handler: CollisionHandler, config: GAConfig) -> IKSolverGA {
+
+        assert!(config.population_size > 0 && config.elitism <= config.population_size,
+        "Invalid population! population size: {}, elitism: {}", config.population_size, config.elitism);
+
+        if let Err(err) = config.validate() {
+            panic!("Invalid config! {}", err);
+        }
+
+        if let Err(err) = col_handler.validate_arm(thetas.len()) {
+            panic!("Invalid arm colliders! {}", err);
+        }
+
+        let rng: StdRng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        let mut solver = IKSolverGA {
+            chain: Chain::new(origin, thetas, axes, radii, min_angles, max_angles),
+            target: None,
+
+            loss: 100.0,
+            iterations: 0,
+
+            config,
+            population: vec![],
+            rng,
+
+            limits: SolveLimits::new(MAX_STEPS),
+            collision_blocked: false,
+
+            collision_handler: col_handler,
+        };
+
+        solver.population = solver.generate_population();
+        solver
+    }
+
+    /// Replace the straight links from radii with an arbitrary fixed offset before each joint,
+    /// for offset elbows and shoulders. radii become the lengths of the offsets
+    pub fn with_offsets(mut self, offsets: &[Isometry3<f32>]) -> IKSolverGA {
+
+        self.chain.set_offsets(offsets);
+        self.collision_handler.set_link_offsets(offsets);
+
+        self
+    }
+
+    /// Hold the end effector at a fixed transform from the last joint, e.g. a gripper
+    pub fn with_tool(mut self, tool: &Isometry3<f32>) -> IKSolverGA {
+
+        self.chain.set_tool(tool);
+
+        self
+    }
+
+    /// Random population spread around the current configuration, which is kept as the first individual
+    fn generate_population(&mut self) -> Vec<Vec<f32>> {
+
+        let mut population: Vec<Vec<f32>> = vec![self.chain.thetas.clone()];
+
+        while population.len() < self.config.population_size {
+            let individual: Vec<f32> = (0..self.chain.len()).map(|i| {
+                let low: f32 = f32::max(self.chain.min_angles[i], self.chain.thetas[i] - PI);
+                let high: f32 = f32::min(self.chain.max_angles[i], self.chain.thetas[i] + PI);
+                if low < high { self.rng.gen_range(low..high) } else { self.chain.thetas[i] }
+            }).collect();
+            population.push(individual);
+        }
+
+        population
+    }
+
+    /// Evolve one generation and keep the fittest individual as the current configuration
+    fn update_population(&mut self) {
+
+        // evaluate fitness in parallel
+        let evaluated: Vec<(f32, bool)> = self.population
+        .par_iter()
+        .map(|individual| self.evaluate(individual))
+        .collect();
+
+        // blocked if a colliding individual would have beaten every valid one
+        let best_valid: f32 = evaluated.iter().filter(|(_, colliding)| !colliding).map(|(loss, _)| *loss).fold(self.loss, f32::min);
+        self.collision_blocked = evaluated.iter().any(|(loss, colliding)| *colliding && *loss < best_valid);
+
+        let mut ranked: Vec<(f32, Vec<f32>)> = evaluated
+        .iter()
+        .zip(self.population.iter())
+        .map(|((loss, colliding), individual)| (if *colliding { f32::INFINITY } else { *loss }, individual.clone()))
+        .collect();
+
+        ranked.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        // the current configuration may be colliding, only replace it with a valid individual
+        if ranked[0].0 < self.loss {
+            self.chain.thetas = ranked[0].1.clone();
+        }
+
+        let mut next: Vec<Vec<f32>> = ranked.iter().take(self.config.elitism).map(|(_, individual)| individual.clone()).collect();
+
+        while next.len() < self.config.population_size {
+
+            let parent_a: &Vec<f32> = self.select(&ranked);
+
+            let mut child: Vec<f32> = if self.rng.gen::<f32>() < self.config.crossover_rate {
+                let parent_b: &Vec<f32> = self.select(&ranked);
+                self.crossover(parent_a, parent_b)
+            } else {
+                parent_a.clone()
+            };
+
+            self.mutate(&mut child);
+            next.push(child);
+        }
+
+        self.population = next;
+    }
+
+    /// Loss of an individual and whether it collides, colliding individuals are ranked last
+    fn evaluate(&self, individual: &[f32]) -> (f32, bool) {
+
+        let forward_mats: Vec<Matrix4<f32>> = self.chain.forward_matrices(individual);
+
+        let colliding: bool = self.collision_handler.is_arm_colliding_self_naive(&forward_mats) || self.collision_handler.is_arm_colliding_world_naive(&forward_mats);
+
+        (self.calculate_loss(&self.chain.tool_pose(&forward_mats)), colliding)
+    }
+
+    /// Tournament selection, the best of a few random individuals
+    fn select<'a>(&mut self, ranked: &'a [(f32, Vec<f32>)]) -> &'a Vec<f32> {
+        let best: usize = (0..TOURNAMENT_SIZE).map(|_| self.rng.gen_range(0..ranked.len())).min().unwrap();
+        &ranked[best].1
+    }
+
+    /// Uniform crossover, each joint angle comes from either parent
+    fn crossover(&mut self, parent_a: &[f32], parent_b: &[f32]) -> Vec<f32> {
+        parent_a.iter().zip(parent_b.iter()).map(|(a, b)| if self.rng.gen::<bool>() { *a } else { *b }).collect()
+    }
+
+    /// Nudge random joint angles, staying within the angle constraints
+    fn mutate(&mut self, individual: &mut [f32]) {
+        for (i, theta) in individual.iter_mut().enumerate() {
+            if self.rng.gen::<f32>() < self.config.mutation_rate {
+                let nudge: f32 = self.rng.gen_range(-1.0..=1.0) * self.config.mutation_scale;
+                *theta = na::clamp(*theta + nudge, self.chain.min_angles[i], self.chain.max_angles[i]);
+            }
+        }
+    }
+
+    /// Update iteration count
+    fn update_params(&mut self) {
+        self.iterations += 1;
+    }
+
+    /// Calculate loss for an end effector position
+    fn calculate_loss(&self, end_effector: &Matrix4<f32>) -> f32 {
+        self.target.unwrap().loss(end_effector, self.chain.arm_length, ROT_CORRECTION)
+    }
+
+}
+
+impl IKSolver for IKSolverGA {
+
+    fn update_matrices(&mut self) {
+        self.chain.update();
+        self.loss = self.calculate_loss(&self.chain.end_effector);
+    }
+
+    fn update(&mut self) {
+        self.update_matrices();
+        self.update_population();
+        self.update_matrices();
+        self.update_params();
+    }
+
+    // Reset parameters between runs
+    fn reset_params(&mut self) {
+        self.iterations = 0;
+        self.loss = 100.0;
+        self.population = self.generate_population();
+    }
+
+    fn set_target(&mut self, target: Target) {
+        self.target = Some(target);
+    }
+
+    fn target(&self) -> Option<Target> {
+        self.target
+    }
+
+    fn chain(&self) -> &Chain {
+        &self.chain
+    }
+
+    fn loss(&self) -> f32 {
+        self.loss
+    }
+
+    fn iterations(&self) -> i32 {
+        self.iterations
+    }
+
+    fn collision_blocked(&self) -> bool {
+        self.collision_blocked
+    }
+
+    fn limits(&self) -> &SolveLimits {
+        &self.limits
+    }
+
+    fn limits_mut(&mut self) -> &mut SolveLimits {
+        &mut self.limits
+    }
+
+}
+
+impl fmt::Display for IKSolverGA {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.chain)
+    }
+}
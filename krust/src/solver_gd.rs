@@ -1,47 +1,288 @@
 extern crate nalgebra as na;
-use na::{Vector3, Matrix4, clamp};
+use na::{Vector3, Matrix4, Isometry3, DMatrix, clamp};
+use serde::{Serialize, Deserialize};
 use std::{fmt, f32::consts::PI};
-use crate::{matrices::{generate_matrices, generate_forward_matrices, generate_backward_matrices, transform_matrix, transform_loss}, collision_handler::CollisionHandler};
+use crate::{chain::Chain, matrices::{generate_forward_matrices, wrap_continuous, JointType, PoseWeights}, collision_handler::CollisionHandler, solver::{IKSolver, SolveLimits}, target::Target};
+
+/// How the learn rate changes over the iterations of a solve
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum LearnRateSchedule {
+    /// Always the base learn rate
+    Constant,
+    /// rate / (1 + decay * t)
+    InverseTime { decay: f32 },
+    /// rate * gamma^t
+    Exponential { gamma: f32 },
+    /// rate * gamma^(t / step_size), dropping every step_size iterations
+    Step { step_size: i32, gamma: f32 },
+    /// Cosine anneal from rate down to min_rate over period iterations, then held at min_rate
+    Cosine { period: i32, min_rate: f32 },
+}
 
-const ROT_CORRECTION: f32 = PI;
-const MAX_D_LOSS: f32 = 0.5;
-const MAX_STEPS: i32 = 10;
+impl LearnRateSchedule {
+
+    /// Learn rate for the given iteration
+    pub fn rate(&self, learn_rate: f32, iteration: i32) -> f32 {
+        let t: f32 = iteration as f32;
+        match *self {
+            LearnRateSchedule::Constant => learn_rate,
+            LearnRateSchedule::InverseTime { decay } => learn_rate / (1.0 + decay * t),
+            LearnRateSchedule::Exponential { gamma } => learn_rate * gamma.powf(t),
+            LearnRateSchedule::Step { step_size, gamma } => learn_rate * gamma.powi(iteration / step_size),
+            LearnRateSchedule::Cosine { period, min_rate } => {
+                let progress: f32 = f32::min(t / period as f32, 1.0);
+                min_rate + 0.5 * (learn_rate - min_rate) * (1.0 + (PI * progress).cos())
+            }
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match *self {
+            LearnRateSchedule::Constant => Ok(()),
+            LearnRateSchedule::InverseTime { decay } if !(decay >= 0.0 && decay.is_finite()) => Err(format!("decay must not be negative, got {}", decay)),
+            LearnRateSchedule::Exponential { gamma } | LearnRateSchedule::Step { gamma, .. } if !(gamma > 0.0 && gamma <= 1.0) => Err(format!("gamma must be in (0, 1], got {}", gamma)),
+            LearnRateSchedule::Step { step_size, .. } if step_size <= 0 => Err(format!("step size must be positive, got {}", step_size)),
+            LearnRateSchedule::Cosine { period, .. } if period <= 0 => Err(format!("period must be positive, got {}", period)),
+            LearnRateSchedule::Cosine { min_rate, .. } if !(min_rate >= 0.0 && min_rate.is_finite()) => Err(format!("min rate must not be negative, got {}", min_rate)),
+            _ => Ok(()),
+        }
+    }
 
-#[derive(Eq, PartialEq, Hash, Clone)]
-struct Key{
-    index: usize,
-    angle: i32,
 }
 
-pub struct IKSolverGD {
+/// How the gradient of each joint is turned into a step
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum Optimizer {
+    /// Gradient step plus a fraction of the previous step, see GDConfig::momentum_retain
+    Momentum,
+    /// Gradient scaled by a running average of its magnitude
+    RMSProp { rho: f32, epsilon: f32 },
+    /// Running average of the gradient scaled by a running average of its magnitude, with bias correction
+    Adam { beta1: f32, beta2: f32, epsilon: f32 },
+}
+
+impl Optimizer {
+
+    fn validate(&self) -> Result<(), String> {
+        match *self {
+            Optimizer::Momentum => Ok(()),
+            Optimizer::RMSProp { rho, .. } | Optimizer::Adam { beta2: rho, .. } if !(0.0..1.0).contains(&rho) => Err(format!("decay rate must be in [0, 1), got {}", rho)),
+            Optimizer::Adam { beta1, .. } if !(0.0..1.0).contains(&beta1) => Err(format!("beta1 must be in [0, 1), got {}", beta1)),
+            Optimizer::RMSProp { epsilon, .. } | Optimizer::Adam { epsilon, .. } if epsilon.is_nan() || epsilon <= 0.0 => Err(format!("epsilon must be positive, got {}", epsilon)),
+            _ => Ok(()),
+        }
+    }
+
+}
+
+/// What happens to a step that would take a joint past one of its limits
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum LimitStrategy {
+    /// Drop the step, so a joint near its limit can freeze
+    Skip,
+    /// Stop the joint at the limit
+    Clamp,
+    /// Add weight * ((margin - d) / margin)^2 to the loss for joints within margin of a limit, and stop them at the limit
+    Penalty { weight: f32, margin: f32 },
+    /// Bounce back off the limit by the overshoot
+    Reflect,
+}
+
+impl LimitStrategy {
+
+    /// New angle for a joint stepping to theta, None if the step is dropped
+    pub fn apply(&self, theta: f32, min_angle: f32, max_angle: f32) -> Option<f32> {
+        match *self {
+            LimitStrategy::Skip => if theta > min_angle && theta < max_angle { Some(theta) } else { None },
+            LimitStrategy::Clamp | LimitStrategy::Penalty { .. } => Some(clamp(theta, min_angle, max_angle)),
+            LimitStrategy::Reflect => {
+                let reflected: f32 = if theta < min_angle { 2.0 * min_angle - theta } else if theta > max_angle { 2.0 * max_angle - theta } else { theta };
+                Some(clamp(reflected, min_angle, max_angle))
+            },
+        }
+    }
+
+    /// Penalty added to the loss for a joint and its derivative
+    pub fn penalty(&self, theta: f32, min_angle: f32, max_angle: f32) -> (f32, f32) {
+        match *self {
+            LimitStrategy::Penalty { weight, margin } => {
+                // distance into the margin, pushing away from the nearer limit
+                let (depth, direction): (f32, f32) = if theta - min_angle < max_angle - theta { (margin - (theta - min_angle), -1.0) } else { (margin - (max_angle - theta), 1.0) };
+                if depth > 0.0 && depth.is_finite() {
+                    (weight * (depth / margin).powi(2), direction * 2.0 * weight * depth / (margin * margin))
+                } else {
+                    (0.0, 0.0)
+                }
+            },
+            _ => (0.0, 0.0),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match *self {
+            LimitStrategy::Penalty { weight, .. } if !(weight >= 0.0 && weight.is_finite()) => Err(format!("limit penalty weight must not be negative, got {}", weight)),
+            LimitStrategy::Penalty { margin, .. } if !(margin > 0.0 && margin.is_finite()) => Err(format!("limit margin must be positive, got {}", margin)),
+            _ => Ok(()),
+        }
+    }
+
+}
+
+/// Tuning parameters for gradient descent.
+/// The loss gradient comes from the analytic jacobian, so unlike the old finite differences there is no step size to tune for it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct GDConfig {
+    /// Step size applied to the loss gradient
+    pub learn_rate: f32,
+    /// How the learn rate decays over a solve
+    pub schedule: LearnRateSchedule,
+    /// How gradients are turned into steps
+    pub optimizer: Optimizer,
+    /// Fraction of the previous step carried into the next one
+    pub momentum_retain: f32,
+    /// Iteration limit for solve()
+    pub max_steps: i32,
+    /// Largest gradient component used for a single step
+    pub max_d_loss: f32,
+    /// Normalization of the rotation part of the loss
+    pub rot_correction: f32,
+    /// Weights of a geodesic loss in physical units, replacing the loss normalized by arm length and rot_correction.
+    /// That loss is not normalized, so learn_rate and max_d_loss have to be scaled to the weights
+    pub weights: Option<PoseWeights>,
+    /// How steps past the joint limits are handled
+    pub limit_strategy: LimitStrategy,
+}
+
+impl Default for GDConfig {
+    fn default() -> GDConfig {
+        GDConfig {
+            learn_rate: 0.7,
+            schedule: LearnRateSchedule::InverseTime { decay: 0.000005 },
+            optimizer: Optimizer::Momentum,
+            momentum_retain: 0.25,
+            max_steps: 10,
+            max_d_loss: 0.5,
+            rot_correction: PI,
+            weights: None,
+            limit_strategy: LimitStrategy::Skip,
+        }
+    }
+}
+
+impl GDConfig {
+
+    pub fn builder() -> GDConfigBuilder {
+        GDConfigBuilder { config: GDConfig::default() }
+    }
+
+    /// Check the parameters can produce a working solver
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.learn_rate > 0.0 && self.learn_rate.is_finite()) {
+            return Err(format!("learn rate must be positive, got {}", self.learn_rate));
+        }
+        self.schedule.validate()?;
+        self.optimizer.validate()?;
+        if !(0.0..1.0).contains(&self.momentum_retain) {
+            return Err(format!("momentum retain must be in [0, 1), got {}", self.momentum_retain));
+        }
+        if self.max_steps <= 0 {
+            return Err(format!("max steps must be positive, got {}", self.max_steps));
+        }
+        if self.max_d_loss.is_nan() || self.max_d_loss <= 0.0 {
+            return Err(format!("max d_loss must be positive, got {}", self.max_d_loss));
+        }
+        if !(self.rot_correction > 0.0 && self.rot_correction.is_finite()) {
+            return Err(format!("rotation correction must be positive, got {}", self.rot_correction));
+        }
+        if let Some(weights) = &self.weights {
+            weights.validate()?;
+        }
+        self.limit_strategy.validate()?;
+        Ok(())
+    }
+
+}
 
-    pub axes: Vec<Vector3<f32>>,
-    pub radii: Vec<f32>,
-    pub thetas: Vec<f32>,
-    pub origin: Matrix4<f32>, 
+/// Builds a GDConfig from the defaults, validating it at the end
+pub struct GDConfigBuilder {
+    config: GDConfig,
+}
 
-    pub min_angles: Vec<f32>,
-    pub max_angles: Vec<f32>,
+impl GDConfigBuilder {
 
-    pub arm_length: f32,
-    pub end_effector: Matrix4<f32>,
-    pub target: Option<Matrix4<f32>>,
+    pub fn learn_rate(mut self, learn_rate: f32) -> GDConfigBuilder {
+        self.config.learn_rate = learn_rate;
+        self
+    }
 
-    pub mats: Vec<Matrix4<f32>>,
-    pub forward_mats: Vec<Matrix4<f32>>,
-    pub backward_mats: Vec<Matrix4<f32>>,
+    pub fn schedule(mut self, schedule: LearnRateSchedule) -> GDConfigBuilder {
+        self.config.schedule = schedule;
+        self
+    }
+
+    pub fn optimizer(mut self, optimizer: Optimizer) -> GDConfigBuilder {
+        self.config.optimizer = optimizer;
+        self
+    }
+
+    pub fn momentum_retain(mut self, momentum_retain: f32) -> GDConfigBuilder {
+        self.config.momentum_retain = momentum_retain;
+        self
+    }
+
+    pub fn max_steps(mut self, max_steps: i32) -> GDConfigBuilder {
+        self.config.max_steps = max_steps;
+        self
+    }
+
+    pub fn max_d_loss(mut self, max_d_loss: f32) -> GDConfigBuilder {
+        self.config.max_d_loss = max_d_loss;
+        self
+    }
+
+    pub fn rot_correction(mut self, rot_correction: f32) -> GDConfigBuilder {
+        self.config.rot_correction = rot_correction;
+        self
+    }
+
+    pub fn weights(mut self, weights: PoseWeights) -> GDConfigBuilder {
+        self.config.weights = Some(weights);
+        self
+    }
+
+    pub fn limit_strategy(mut self, limit_strategy: LimitStrategy) -> GDConfigBuilder {
+        self.config.limit_strategy = limit_strategy;
+        self
+    }
+
+    pub fn build(self) -> Result<GDConfig, String> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+
+}
+
+pub struct IKSolverGD {
+
+    pub chain: Chain,
+    pub target: Option<Target>,
 
     pub loss: f32,
     pub iterations: i32,
 
-    learn_rate: f32,
+    pub config: GDConfig,
     current_learn_rate: f32,
-    decay: f32,
     momentums: Vec<f32>,
-    momentum_retain: f32,
+    velocities: Vec<f32>,
+
+    pub limits: SolveLimits,
+    collision_blocked: bool,
 
     pub collision_handler: CollisionHandler,
-    // mat_cache: FxHashMap<Key, Matrix4<f32>>,
 
 }
 
@@ -49,156 +290,235 @@ pub struct IKSolverGD {
 /// Uses gradient descent/optimization to solve IK for a given target position
 impl IKSolverGD {
 
-    pub fn new(origin: Matrix4<f32>, thetas: &Vec<f32>, axes: &Vec<Vector3<f32>>, radii: &Vec<f32>, min_angles: &Vec<f32>, max_angles: &Vec<f32>, col_handler: CollisionHandler) -> IKSolverGD {
-
-        // Make sure arm properties have the same length
-        assert!(thetas.len() == axes.len() && thetas.len() == radii.len() && thetas.len() == min_angles.len() && thetas.len() == max_angles.len(), 
-        "Vector lengths unequal! angles: {}, axes: {}, radii: {}, min angles: {}, max angles: {}", thetas.len(), axes.len(), radii.len(), min_angles.len(), max_angles.len());
+    pub fn new(origin: Matrix4<f32>, thetas: &[f32], axes: &[Vector3<f32>], radii: &[f32], min_angles: &[f32], max_angles: &[f32], col_handler: CollisionHandler) -> IKSolverGD {
+        IKSolverGD::with_config(origin, thetas, axes, radii, min_angles, max_angles, col_handler, GDConfig::default())
+    }
 
-        // Generate the matrices to avoid Option<> for matrix types
-        let matrices: Vec<Matrix4<f32>> = generate_matrices(origin, &thetas, &axes, &radii);
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_config(origin: Matrix4<f32>, thetas: &[f32], axes: &[Vector3<f32>], radii: &[f32], min_angles: &[f32], max_angles: &[f32], col_handler: CollisionHandler, config: GDConfig) -> IKSolverGD {
 
-        IKSolverGD {
-            origin: origin,
-            thetas: thetas.to_vec(),
-            axes: axes.to_vec(),
-            radii: radii.to_vec(),
+        if let Err(err) = col_handler.validate_arm(thetas.len()) {
+            panic!("Invalid arm colliders! {}", err);
+        }
 
-            min_angles: min_angles.to_vec(),
-            max_angles: max_angles.to_vec(),
+        if let Err(err) = config.validate() {
+            panic!("Invalid config! {}", err);
+        }
 
-            arm_length: radii.iter().sum(),
-            end_effector: matrices[matrices.len() - 1],
+        IKSolverGD {
+            chain: Chain::new(origin, thetas, axes, radii, min_angles, max_angles),
             target: None,
 
-            forward_mats: generate_forward_matrices(&matrices),
-            backward_mats: generate_backward_matrices(&matrices),
-            mats: matrices,
-
             loss: 100.0,
             iterations: 0,
 
-            learn_rate: 0.7,
-            current_learn_rate: 0.7,
-            decay: 0.000005,
+            current_learn_rate: config.learn_rate,
             momentums: vec![0.0; thetas.len()],
-            momentum_retain: 0.25,
+            velocities: vec![0.0; thetas.len()],
             
+            limits: SolveLimits::new(config.max_steps),
+            config,
+            collision_blocked: false,
+
             collision_handler: col_handler,
-            // mat_cache: FxHashMap::default()
         }
     }
 
-    /// Generate mats and update end-effector position/loss for the given configuration
-    pub fn update_matrices(&mut self) {
-        self.mats = generate_matrices(self.origin, &self.thetas, &self.axes, &self.radii);
-        self.forward_mats = generate_forward_matrices(&self.mats);
-        self.backward_mats = generate_backward_matrices(&self.mats);
+    /// Replace the straight links from radii with an arbitrary fixed offset before each joint,
+    /// for offset elbows and shoulders. radii become the lengths of the offsets
+    pub fn with_offsets(mut self, offsets: &[Isometry3<f32>]) -> IKSolverGD {
+
+        self.chain.set_offsets(offsets);
+
+        self.collision_handler.set_link_offsets(offsets);
+        self.collision_handler.set_joint_types(&self.chain.joint_types, &self.chain.radii);
 
-        self.end_effector = self.forward_mats[self.forward_mats.len() - 1];
-        self.loss = self.calculate_loss(&self.end_effector);
+        self
+    }
+
+    /// Hold the end effector at a fixed transform from the last joint, e.g. a gripper
+    pub fn with_tool(mut self, tool: &Isometry3<f32>) -> IKSolverGD {
+
+        self.chain.set_tool(tool);
+
+        self
+    }
+
+    /// Set the type of each joint, all joints are revolute by default.
+    /// The values and limits of prismatic joints are distances along their axis.
+    /// Continuous joints lose their limits and have their angles wrapped into (-pi, pi] after every step,
+    /// so each moves at most half a turn from the seed to any equivalent angle
+    pub fn with_joint_types(mut self, joint_types: &[JointType]) -> IKSolverGD {
+
+        self.chain.set_joint_types(joint_types);
+        self.collision_handler.set_joint_types(joint_types, &self.chain.radii);
+
+        self
     }
 
     /// Perform a gradient descent step to update arm angles
     fn update_thetas(&mut self) {
 
-        let d: f32 = 0.00001;
-
-        // store matrices for optimization
-        let mut mats: Vec<Matrix4<f32>> = self.mats.clone();
+        // exact loss gradient from the geometric jacobian
+        let jacobian: DMatrix<f32> = self.chain.jacobian(&self.chain.forward_mats);
+        let target: Target = self.target.unwrap();
+        let gradient: Vec<f32> = match &self.config.weights {
+            Some(weights) => target.weighted_loss_gradient(&self.chain.end_effector, &jacobian, weights),
+            None => target.loss_gradient(&self.chain.end_effector, &jacobian, self.chain.arm_length, self.config.rot_correction),
+        };
 
-        for i in 0..self.thetas.len() {
+        self.collision_blocked = false;
 
-            let d_theta: f32 = self.thetas[i] + d;
-            let radius: f32 = self.radii[i];
-            let axis: Vector3<f32> = self.axes[i];
-            let d_mat: Matrix4<f32> = transform_matrix(d_theta, &axis, &Vector3::new(0.0,0.0,radius));
+        // store matrices for optimization
+        let mut mats: Vec<Matrix4<f32>> = self.chain.mats.clone();
 
-            let delta_end_effector: Matrix4<f32> = (self.forward_mats[i] * d_mat) * self.backward_mats[i + 2];
+        for i in 0..self.chain.len() {
 
-            let d_loss: f32 = (self.calculate_loss(&delta_end_effector) - self.loss) / d;
+            if self.chain.joint_types[i] == JointType::Fixed {
+                continue;
+            }
 
-            // clamp d_loss
-            let d_loss: f32 = clamp(d_loss, -MAX_D_LOSS, MAX_D_LOSS);
+            // clamp d_loss, including the push away from the limits
+            let (_, d_penalty): (f32, f32) = self.config.limit_strategy.penalty(self.chain.thetas[i], self.chain.min_angles[i], self.chain.max_angles[i]);
+            let d_loss: f32 = clamp(gradient[i] + d_penalty, -self.config.max_d_loss, self.config.max_d_loss);
 
-            // momentum
-            let nudge: f32 = (self.momentums[i] * self.momentum_retain) + (d_loss * self.learn_rate);
+            let step: f32 = self.step(i, d_loss);
 
-            let mut new_thetas: Vec<f32> = self.thetas.to_vec();
-            new_thetas[i] -= nudge;
+            // check angle constraints, the nudge actually taken may be shortened or reversed by the limits
+            if let Some(new_theta) = self.config.limit_strategy.apply(self.chain.thetas[i] - step, self.chain.min_angles[i], self.chain.max_angles[i]) {
 
-            // check angle constraints
-            if new_thetas[i] > self.min_angles[i] && new_thetas[i] < self.max_angles[i]
-            {
+                let nudge: f32 = self.chain.thetas[i] - new_theta;
 
-                mats[i + 1] = transform_matrix(new_thetas[i], &axis, &Vector3::new(0.0,0.0,radius));
+                mats[i + 1] = self.chain.joint_matrix(i, new_theta);
                 let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&mats);
-                mats[i + 1] = self.mats[i + 1];
+                mats[i + 1] = self.chain.mats[i + 1];
                 
                 // check collision constraints
                 if !self.collision_handler.is_arm_colliding_self(i, &forward_mats) && !self.collision_handler.is_arm_colliding_world(i, &forward_mats) {
-                    self.thetas[i] -= nudge;
-                    self.momentums[i] = nudge;
+                    self.chain.thetas[i] -= nudge;
+                    if self.config.optimizer == Optimizer::Momentum {
+                        self.momentums[i] = nudge;
+                    }
                 } else {
-                    self.thetas[i] += nudge;
-                    self.momentums[i] = -nudge;
+                    // bounce back off the obstacle, adaptive optimizers drop their running average
+                    self.chain.thetas[i] = clamp(self.chain.thetas[i] + nudge, self.chain.min_angles[i], self.chain.max_angles[i]);
+                    self.momentums[i] = if self.config.optimizer == Optimizer::Momentum { -nudge } else { 0.0 };
+                    self.collision_blocked = true;
                 }
             }
 
         }
 
+        wrap_continuous(&mut self.chain.thetas, &self.chain.joint_types);
+
+    }
+
+    /// Step for joint i from its clamped gradient, updating the optimizer state
+    fn step(&mut self, i: usize, d_loss: f32) -> f32 {
+        match self.config.optimizer {
+            Optimizer::Momentum => (self.momentums[i] * self.config.momentum_retain) + (d_loss * self.current_learn_rate),
+            Optimizer::RMSProp { rho, epsilon } => {
+                self.velocities[i] = rho * self.velocities[i] + (1.0 - rho) * d_loss * d_loss;
+                self.current_learn_rate * d_loss / (self.velocities[i].sqrt() + epsilon)
+            },
+            Optimizer::Adam { beta1, beta2, epsilon } => {
+                self.momentums[i] = beta1 * self.momentums[i] + (1.0 - beta1) * d_loss;
+                self.velocities[i] = beta2 * self.velocities[i] + (1.0 - beta2) * d_loss * d_loss;
+
+                // correct the bias towards zero from starting the averages at zero
+                let t: i32 = self.iterations + 1;
+                let momentum: f32 = self.momentums[i] / (1.0 - beta1.powi(t));
+                let velocity: f32 = self.velocities[i] / (1.0 - beta2.powi(t));
+
+                self.current_learn_rate * momentum / (velocity.sqrt() + epsilon)
+            },
+        }
     }
 
     /// Update learning parameters
     fn update_params(&mut self) {
         self.iterations += 1;
-        self.current_learn_rate = self.learn_rate * (1.0 / (1.0 + self.decay * self.iterations as f32));
+        self.current_learn_rate = self.config.schedule.rate(self.config.learn_rate, self.iterations);
     }
 
-    pub fn update(&mut self) {
+    /// Learn rate for the next step
+    pub fn current_learn_rate(&self) -> f32 {
+        self.current_learn_rate
+    }
+
+    /// Calculate loss for the descent, with any limit penalty of the current angles
+    fn calculate_loss(&self, end_effector: &Matrix4<f32>) -> f32 {
+
+        let loss: f32 = match &self.config.weights {
+            Some(weights) => self.target.unwrap().weighted_loss(end_effector, weights),
+            None => self.target.unwrap().loss(end_effector, self.chain.arm_length, self.config.rot_correction),
+        };
+
+        let penalty: f32 = (0..self.chain.len()).map(|i| self.config.limit_strategy.penalty(self.chain.thetas[i], self.chain.min_angles[i], self.chain.max_angles[i]).0).sum();
+
+        loss + penalty
+    }
+
+}
+
+impl IKSolver for IKSolverGD {
+
+    fn update_matrices(&mut self) {
+        self.chain.update();
+        self.loss = self.calculate_loss(&self.chain.end_effector);
+    }
+
+    fn update(&mut self) {
         self.update_matrices();
         self.update_thetas();
         self.update_params();
     }
 
-    pub fn solve(&mut self, target: Matrix4<f32>, thresh: f32) {
+    // Reset parameters between runs
+    fn reset_params(&mut self) {
+        self.iterations = 0;
+        self.loss = 100.0;
+        self.current_learn_rate = self.config.learn_rate;
+        self.momentums = vec![0.0; self.chain.len()];
+        self.velocities = vec![0.0; self.chain.len()];
+    }
 
+    fn set_target(&mut self, target: Target) {
         self.target = Some(target);
-        self.reset_params();
+    }
 
-        while self.loss > thresh {
-            
-            if self.iterations < MAX_STEPS {
-                self.update();
-            } else {
-                println!("Failed to solve in {} steps!", self.iterations);
-                return;
-            }
+    fn target(&self) -> Option<Target> {
+        self.target
+    }
 
-        }
+    fn chain(&self) -> &Chain {
+        &self.chain
+    }
 
-        println!("Solution found in {} steps!", self.iterations);
+    fn loss(&self) -> f32 {
+        self.loss
+    }
 
+    fn iterations(&self) -> i32 {
+        self.iterations
     }
 
-    /// Calculate loss for the descent
-    fn calculate_loss(&self, end_effector: &Matrix4<f32>) -> f32 {
-        // distance_loss(end_effector, &self.target.unwrap(), self.arm_length)
-        transform_loss(end_effector, &self.target.unwrap(), self.arm_length, ROT_CORRECTION)
+    fn collision_blocked(&self) -> bool {
+        self.collision_blocked
     }
 
-    // Reset parameters between runs
-    pub fn reset_params(&mut self) {
-        self.iterations = 0;
-        self.loss = 100.0;
-        self.current_learn_rate = self.learn_rate;
-        self.momentums = vec![0.0; self.thetas.len()];
+    fn limits(&self) -> &SolveLimits {
+        &self.limits
+    }
+
+    fn limits_mut(&mut self) -> &mut SolveLimits {
+        &mut self.limits
     }
 
 }
 
 impl fmt::Display for IKSolverGD {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f,"Origin: {}, Axes: {:?}, Radii: {:?}, Thetas: {:?}", self.origin, self.axes, self.radii, self.thetas)
+        write!(f, "{}", self.chain)
     }
-}
\ No newline at end of file
+}
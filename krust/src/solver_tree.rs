@@ -0,0 +1,275 @@
+extern crate nalgebra as na;
+use na::{Vector3, Matrix4, Isometry3, DMatrix, DVector};
+use std::{fmt, f32::consts::PI};
+use crate::{chain::Chain, matrices::{tree_ancestors, jacobian_tree, wrap_continuous, JointType}, collision_handler::CollisionHandler, solver::{IKSolver, SolveLimits, SolveResult, Convergence, Tolerance}, target::Target};
+
+const ROT_CORRECTION: f32 = PI;
+const MAX_STEPS: i32 = 50;
+
+const INITIAL_DAMPING: f32 = 0.1;
+const MIN_DAMPING: f32 = 0.0001;
+const MAX_DAMPING: f32 = 1000.0;
+const DAMPING_INCREASE: f32 = 4.0;
+const DAMPING_DECREASE: f32 = 0.5;
+
+pub struct IKSolverTree {
+
+    pub chain: Chain,
+
+    /// Joints whose frames are end effectors
+    pub end_effectors: Vec<usize>,
+    /// Summed link lengths from the origin to each end effector
+    pub reaches: Vec<f32>,
+    /// Target of each end effector, end effectors without one are left free
+    pub targets: Vec<Option<Target>>,
+
+    pub loss: f32,
+    pub iterations: i32,
+
+    damping: f32,
+
+    pub limits: SolveLimits,
+    collision_blocked: bool,
+
+    pub collision_handler: CollisionHandler,
+
+}
+
+/// Damped least-squares solver for a tree of joints with several end effectors.
+/// Stacks the jacobians of every targeted end effector so shared ancestor joints serve all of them at once
+impl IKSolverTree {
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(origin: Matrix4<f32>, thetas: &[f32], axes: &[Vector3<f32>], radii: &[f32], parents: &[Option<usize>], end_effectors: &[usize], min_angles: &[f32], max_angles: &[f32], mut col_handler: CollisionHandler) -> IKSolverTree {
+
+        let mut chain: Chain = Chain::new(origin, thetas, axes, radii, min_angles, max_angles);
+        chain.set_parents(parents);
+
+        assert!(!end_effectors.is_empty() && end_effectors.iter().all(|joint| *joint < thetas.len()),
+        "End effectors must be joints of the tree! joints: {}, end effectors: {:?}", thetas.len(), end_effectors);
+
+        if let Err(err) = col_handler.validate_arm(thetas.len()) {
+            panic!("Invalid arm colliders! {}", err);
+        }
+
+        col_handler.set_arm_parents(parents);
+
+        IKSolverTree {
+            chain,
+
+            end_effectors: end_effectors.to_vec(),
+            reaches: get_reaches(parents, end_effectors, radii),
+            targets: vec![None; end_effectors.len()],
+
+            loss: 100.0,
+            iterations: 0,
+
+            damping: INITIAL_DAMPING,
+
+            limits: SolveLimits::new(MAX_STEPS),
+            collision_blocked: false,
+
+            collision_handler: col_handler,
+        }
+    }
+
+    /// Replace the straight links from radii with an arbitrary fixed offset before each joint,
+    /// so branches can leave their parent sideways. radii become the lengths of the offsets
+    pub fn with_offsets(mut self, offsets: &[Isometry3<f32>]) -> IKSolverTree {
+
+        self.chain.set_offsets(offsets);
+        self.reaches = get_reaches(&self.chain.parents, &self.end_effectors, &self.chain.radii);
+        self.collision_handler.set_link_offsets(offsets);
+
+        self
+    }
+
+    /// Set the type of each joint, values of prismatic joints are distances along their axis.
+    /// Continuous joints lose their limits and have their angles wrapped into (-pi, pi]
+    pub fn with_joint_types(mut self, joint_types: &[JointType]) -> IKSolverTree {
+
+        self.chain.set_joint_types(joint_types);
+        self.collision_handler.set_joint_types(joint_types, &self.chain.radii);
+
+        self
+    }
+
+    /// Set the target of each end effector without solving, None leaves an end effector free
+    pub fn set_targets(&mut self, targets: &[Option<Target>]) {
+
+        assert!(targets.len() == self.end_effectors.len(),
+        "Vector lengths unequal! end effectors: {}, targets: {}", self.end_effectors.len(), targets.len());
+
+        self.targets = targets.to_vec();
+    }
+
+    /// Iterate towards a target for each end effector. The errors in the result are the worst over the targeted end effectors
+    pub fn solve_targets(&mut self, targets: &[Option<Target>], thresh: f32) -> SolveResult {
+        self.set_targets(targets);
+        self.solve_until(Convergence::Loss(thresh))
+    }
+
+    /// Iterate towards a target for each end effector until every targeted end effector is within tolerance
+    pub fn solve_targets_within(&mut self, targets: &[Option<Target>], tolerance: Tolerance) -> SolveResult {
+        self.set_targets(targets);
+        self.solve_until(Convergence::Tolerance(tolerance))
+    }
+
+    /// Current pose of each end effector
+    pub fn end_effector_poses(&self) -> Vec<Matrix4<f32>> {
+        self.end_effectors.iter().map(|joint| self.chain.forward_mats[joint + 1]).collect()
+    }
+
+    /// Take a damped least-squares step for all targeted end effectors, adapting the damping based on whether it succeeded
+    fn update_thetas(&mut self) {
+
+        let targeted: Vec<(usize, Target)> = self.end_effectors.iter().zip(self.targets.iter()).filter_map(|(joint, target)| target.map(|target| (*joint, target))).collect();
+
+        if targeted.is_empty() {
+            return;
+        }
+
+        // stack the constrained rows of the jacobians and errors of the targeted end effectors
+        let tasks: Vec<(DVector<f32>, DMatrix<f32>)> = targeted.iter().map(|(joint, target)| target.task(&self.chain.forward_mats[joint + 1], &jacobian_tree(&self.chain.forward_mats, &self.chain.axes, &self.chain.joint_types, &self.chain.parents, *joint))).collect();
+        let rows: usize = tasks.iter().map(|(error, _)| error.len()).sum();
+
+        let mut jacobian: DMatrix<f32> = DMatrix::zeros(rows, self.chain.len());
+        let mut error: DVector<f32> = DVector::zeros(rows);
+
+        let mut row: usize = 0;
+        for (task_error, task_jacobian) in tasks.iter() {
+            jacobian.rows_mut(row, task_error.len()).copy_from(task_jacobian);
+            error.rows_mut(row, task_error.len()).copy_from(task_error);
+            row += task_error.len();
+        }
+
+        // dtheta = J^T (J J^T + lambda^2 I)^-1 e
+        let damped: DMatrix<f32> = &jacobian * jacobian.transpose() + DMatrix::identity(error.len(), error.len()) * (self.damping * self.damping);
+
+        let step: DVector<f32> = match damped.cholesky() {
+            Some(cholesky) => jacobian.transpose() * cholesky.solve(&error),
+            None => {
+                self.damping = f32::min(self.damping * DAMPING_INCREASE, MAX_DAMPING);
+                return;
+            }
+        };
+
+        // clamp to angle constraints
+        let mut new_thetas: Vec<f32> = self.chain.thetas
+        .iter()
+        .enumerate()
+        .map(|(i, theta)| na::clamp(theta + step[i], self.chain.min_angles[i], self.chain.max_angles[i]))
+        .collect();
+        wrap_continuous(&mut new_thetas, &self.chain.joint_types);
+
+        let forward_mats: Vec<Matrix4<f32>> = self.chain.forward_matrices(&new_thetas);
+        let new_loss: f32 = self.calculate_loss(&forward_mats);
+
+        // check collision constraints, every joint may have moved so check the whole tree
+        let colliding: bool = self.collision_handler.is_arm_colliding_self_naive(&forward_mats) || self.collision_handler.is_arm_colliding_world_naive(&forward_mats);
+        self.collision_blocked = colliding && new_loss < self.loss;
+
+        if new_loss < self.loss && !colliding {
+            self.chain.thetas = new_thetas;
+            self.update_matrices();
+            self.damping = f32::max(self.damping * DAMPING_DECREASE, MIN_DAMPING);
+        } else {
+            self.damping = f32::min(self.damping * DAMPING_INCREASE, MAX_DAMPING);
+        }
+
+    }
+
+    /// Update iteration count
+    fn update_params(&mut self) {
+        self.iterations += 1;
+    }
+
+    /// Sum of the losses of the targeted end effectors, each normalized by its own reach
+    fn calculate_loss(&self, forward_mats: &[Matrix4<f32>]) -> f32 {
+        self.end_effectors
+        .iter()
+        .zip(self.targets.iter())
+        .zip(self.reaches.iter())
+        .filter_map(|((joint, target), reach)| target.map(|target| target.loss(&forward_mats[joint + 1], *reach, ROT_CORRECTION)))
+        .sum()
+    }
+
+}
+
+/// Summed link lengths from the origin to each end effector
+fn get_reaches(parents: &[Option<usize>], end_effectors: &[usize], radii: &[f32]) -> Vec<f32> {
+    end_effectors.iter().map(|joint| tree_ancestors(parents, *joint).iter().map(|i| radii[*i]).sum()).collect()
+}
+
+/// Drives the first end effector, so the tree can stand in for a chain solver. The other targets are kept
+impl IKSolver for IKSolverTree {
+
+    fn update_matrices(&mut self) {
+        self.chain.update();
+        self.loss = self.calculate_loss(&self.chain.forward_mats);
+    }
+
+    fn update(&mut self) {
+        self.update_matrices();
+        self.update_thetas();
+        self.update_params();
+    }
+
+    // Reset parameters between runs
+    fn reset_params(&mut self) {
+        self.iterations = 0;
+        self.loss = 100.0;
+        self.damping = INITIAL_DAMPING;
+    }
+
+    fn set_target(&mut self, target: Target) {
+        self.targets[0] = Some(target);
+    }
+
+    fn target(&self) -> Option<Target> {
+        self.targets[0]
+    }
+
+    /// Worst errors over all targeted end effectors
+    fn errors(&self) -> (f32, f32) {
+
+        let errors: Vec<(f32, f32)> = self.end_effector_poses().iter().zip(self.targets.iter()).filter_map(|(pose, target)| target.map(|target| target.errors(pose))).collect();
+
+        (errors.iter().map(|(position, _)| *position).fold(0.0, f32::max), errors.iter().map(|(_, orientation)| *orientation).fold(0.0, f32::max))
+    }
+
+    fn chain(&self) -> &Chain {
+        &self.chain
+    }
+
+    fn end_effector(&self) -> Matrix4<f32> {
+        self.chain.forward_mats[self.end_effectors[0] + 1]
+    }
+
+    fn loss(&self) -> f32 {
+        self.loss
+    }
+
+    fn iterations(&self) -> i32 {
+        self.iterations
+    }
+
+    fn collision_blocked(&self) -> bool {
+        self.collision_blocked
+    }
+
+    fn limits(&self) -> &SolveLimits {
+        &self.limits
+    }
+
+    fn limits_mut(&mut self) -> &mut SolveLimits {
+        &mut self.limits
+    }
+
+}
+
+impl fmt::Display for IKSolverTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}, Parents: {:?}, End effectors: {:?}", self.chain, self.chain.parents, self.end_effectors)
+    }
+}
@@ -0,0 +1,266 @@
+extern crate nalgebra as na;
+use na::{Vector3, Matrix3, Matrix4, DMatrix, DVector};
+use serde::{Serialize, Deserialize};
+use std::f32::consts::PI;
+use crate::collision_handler::ColliderPose;
+use crate::matrices::{transform_loss, transform_loss_gradient, position_error, orientation_error, rotation_vector, rotation_error, weighted_pose_loss, weighted_pose_loss_gradient, PoseWeights};
+
+/// What the end effector has to reach
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case", tag = "type")]
+pub enum Target {
+    /// Full position and orientation
+    Pose { pose: Matrix4<f32> },
+    /// Position only, any orientation
+    Position { position: Vector3<f32> },
+    /// Position, with the tool axis of the end effector frame pointing along direction and free to spin about it
+    Axis { position: Vector3<f32>, tool_axis: Vector3<f32>, direction: Vector3<f32> },
+    /// Pose where only some axes count. position_mask selects world x, y and z,
+    /// rotation_mask selects rotations about the x, y and z axes of the target
+    Masked { pose: Matrix4<f32>, position_mask: [bool; 3], rotation_mask: [bool; 3] },
+}
+
+impl From<Matrix4<f32>> for Target {
+    fn from(pose: Matrix4<f32>) -> Target {
+        Target::Pose { pose }
+    }
+}
+
+impl Target {
+
+    /// Check the target can be reached for, rather than solving for NaN or for nothing
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Target::Pose { pose } | Target::Masked { pose, .. } if ColliderPose::Matrix(*pose).to_isometry().is_none() =>
+                Err(format!("{:?} is not a rigid transform", pose)),
+            Target::Axis { tool_axis, direction, .. } if tool_axis.norm() < f32::EPSILON || direction.norm() < f32::EPSILON =>
+                Err("tool axis and direction must not be zero".to_string()),
+            Target::Masked { position_mask, rotation_mask, .. } if !position_mask.iter().chain(rotation_mask).any(|constrained| *constrained) =>
+                Err("at least one position or rotation component must be unmasked".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Target position with any unconstrained coordinates taken from the actual end effector
+    pub fn position_goal(&self, actual: &Matrix4<f32>) -> Vector3<f32> {
+        match self {
+            Target::Pose { pose } => pose.fixed_slice::<3, 1>(0, 3).into_owned(),
+            Target::Position { position } | Target::Axis { position, .. } => *position,
+            Target::Masked { pose, position_mask, .. } => Vector3::from_fn(|i, _| if position_mask[i] { pose[(i, 3)] } else { actual[(i, 3)] }),
+        }
+    }
+
+    /// Loss against the actual end effector, with the normalization of transform_loss
+    pub fn loss(&self, actual: &Matrix4<f32>, dist_correction: f32, rot_correction: f32) -> f32 {
+        match self {
+            Target::Pose { pose } => transform_loss(actual, pose, dist_correction, rot_correction),
+            Target::Position { .. } => self.position_loss(actual, dist_correction),
+            Target::Axis { .. } => {
+                let (pointing, direction): (Vector3<f32>, Vector3<f32>) = self.tool_axes(actual);
+                self.position_loss(actual, dist_correction) + (pointing - direction).norm_squared() / rot_correction.powi(3)
+            },
+            Target::Masked { rotation_mask, .. } => {
+                // twice the squared angle matches rotation_loss for small rotations
+                let rotation: Vector3<f32> = self.masked_rotation(actual);
+                self.position_loss(actual, dist_correction) + 2.0 * mask(&rotation, rotation_mask).norm_squared() / rot_correction.powi(3)
+            },
+        }
+    }
+
+    /// Loss of the position alone, for solvers that only reach for positions
+    pub fn position_loss(&self, actual: &Matrix4<f32>, dist_correction: f32) -> f32 {
+        (self.position_goal(actual) - actual.fixed_slice::<3, 1>(0, 3)).norm_squared() / (dist_correction * dist_correction)
+    }
+
+    /// Gradient of loss with respect to each joint, from the geometric jacobian of the end effector
+    pub fn loss_gradient(&self, actual: &Matrix4<f32>, jacobian: &DMatrix<f32>, dist_correction: f32, rot_correction: f32) -> Vec<f32> {
+
+        if let Target::Pose { pose } = self {
+            return transform_loss_gradient(actual, pose, jacobian, dist_correction, rot_correction);
+        }
+
+        let position_err: Vector3<f32> = (self.position_goal(actual) - actual.fixed_slice::<3, 1>(0, 3)) / (dist_correction * dist_correction);
+
+        jacobian.column_iter().map(|column| {
+
+            let linear: Vector3<f32> = column.fixed_rows::<3>(0).into_owned();
+            let angular: Vector3<f32> = column.fixed_rows::<3>(3).into_owned();
+
+            let rotation: f32 = match self {
+                Target::Axis { .. } => {
+                    // the tool axis turns with the angular velocity of the joint
+                    let (pointing, direction): (Vector3<f32>, Vector3<f32>) = self.tool_axes(actual);
+                    2.0 * (pointing - direction).dot(&angular.cross(&pointing)) / rot_correction.powi(3)
+                },
+                Target::Masked { pose, rotation_mask, .. } => {
+                    // the joint turns the relative rotation from the left, in the target frame
+                    let rotation: Vector3<f32> = self.masked_rotation(actual);
+                    let d_rotation: Vector3<f32> = left_jacobian_inverse(&rotation) * (pose.fixed_slice::<3, 3>(0, 0).transpose() * angular);
+                    4.0 * mask(&rotation, rotation_mask).dot(&d_rotation) / rot_correction.powi(3)
+                },
+                _ => 0.0,
+            };
+
+            -2.0 * position_err.dot(&linear) + rotation
+
+        }).collect()
+    }
+
+    /// Weighted loss in physical units, the squared position error plus the squared geodesic angle of the constrained rotation
+    pub fn weighted_loss(&self, actual: &Matrix4<f32>, weights: &PoseWeights) -> f32 {
+        match self {
+            Target::Pose { pose } => weighted_pose_loss(actual, pose, weights),
+            _ => weights.position * (self.position_goal(actual) - actual.fixed_slice::<3, 1>(0, 3)).norm_squared() + weights.orientation * self.geodesic_rotation(actual).norm_squared(),
+        }
+    }
+
+    /// Gradient of weighted_loss with respect to each joint, from the geometric jacobian of the end effector
+    pub fn weighted_loss_gradient(&self, actual: &Matrix4<f32>, jacobian: &DMatrix<f32>, weights: &PoseWeights) -> Vec<f32> {
+
+        if let Target::Pose { pose } = self {
+            return weighted_pose_loss_gradient(actual, pose, jacobian, weights);
+        }
+
+        let position_err: Vector3<f32> = self.position_goal(actual) - actual.fixed_slice::<3, 1>(0, 3);
+        let rotation: Vector3<f32> = self.geodesic_rotation(actual);
+
+        jacobian.column_iter().map(|column| {
+
+            let angular: Vector3<f32> = column.fixed_rows::<3>(3).into_owned();
+
+            let d_rotation: f32 = match self {
+                // the world frame rotation to the goal shrinks along the angular velocity
+                Target::Axis { .. } => -2.0 * rotation.dot(&angular),
+                Target::Masked { pose, .. } => 2.0 * rotation.dot(&(left_jacobian_inverse(&self.masked_rotation(actual)) * (pose.fixed_slice::<3, 3>(0, 0).transpose() * angular))),
+                _ => 0.0,
+            };
+
+            -2.0 * weights.position * position_err.dot(&column.fixed_rows::<3>(0)) + weights.orientation * d_rotation
+
+        }).collect()
+    }
+
+    /// Error vector and the matching rows of the geometric jacobian, for least-squares solvers.
+    /// Position rows come first, see position_rows
+    pub fn task(&self, actual: &Matrix4<f32>, jacobian: &DMatrix<f32>) -> (DVector<f32>, DMatrix<f32>) {
+
+        let mut error: DVector<f32> = DVector::zeros(6);
+        let mut task_jacobian: DMatrix<f32> = jacobian.clone();
+
+        error.fixed_rows_mut::<3>(0).copy_from(&(self.position_goal(actual) - actual.fixed_slice::<3, 1>(0, 3)));
+
+        match self {
+            Target::Pose { pose } => error.fixed_rows_mut::<3>(3).copy_from(&rotation_error(actual, pose)),
+            Target::Position { .. } => {},
+            Target::Axis { .. } => {
+                // spinning about the tool axis does not change the error, so drop that part of the jacobian
+                let (pointing, direction): (Vector3<f32>, Vector3<f32>) = self.tool_axes(actual);
+                let projection: Matrix3<f32> = Matrix3::identity() - pointing * pointing.transpose();
+                error.fixed_rows_mut::<3>(3).copy_from(&rotation_between(&pointing, &direction));
+                task_jacobian.fixed_rows_mut::<3>(3).copy_from(&(projection * jacobian.fixed_rows::<3>(3)));
+            },
+            Target::Masked { pose, .. } => {
+                // rotations about the axes of the target frame
+                let frame: Matrix3<f32> = pose.fixed_slice::<3, 3>(0, 0).transpose();
+                error.fixed_rows_mut::<3>(3).copy_from(&(frame * rotation_error(actual, pose)));
+                task_jacobian.fixed_rows_mut::<3>(3).copy_from(&(frame * jacobian.fixed_rows::<3>(3)));
+            },
+        }
+
+        let (position_mask, rotation_mask): ([bool; 3], [bool; 3]) = self.masks();
+        let rows: Vec<usize> = (0..6).filter(|row| if *row < 3 { position_mask[*row] } else { rotation_mask[row - 3] }).collect();
+
+        (error.select_rows(&rows), task_jacobian.select_rows(&rows))
+    }
+
+    /// Number of position rows at the start of task
+    pub fn position_rows(&self) -> usize {
+        self.masks().0.iter().filter(|constrained| **constrained).count()
+    }
+
+    /// Position and orientation error of the actual end effector, only counting the constrained parts
+    pub fn errors(&self, actual: &Matrix4<f32>) -> (f32, f32) {
+        match self {
+            Target::Pose { pose } => (position_error(actual, pose), orientation_error(actual, pose)),
+            Target::Position { .. } => ((self.position_goal(actual) - actual.fixed_slice::<3, 1>(0, 3)).norm(), 0.0),
+            Target::Axis { .. } => {
+                let (pointing, direction): (Vector3<f32>, Vector3<f32>) = self.tool_axes(actual);
+                ((self.position_goal(actual) - actual.fixed_slice::<3, 1>(0, 3)).norm(), pointing.cross(&direction).norm().atan2(pointing.dot(&direction)))
+            },
+            Target::Masked { rotation_mask, .. } => ((self.position_goal(actual) - actual.fixed_slice::<3, 1>(0, 3)).norm(), mask(&self.masked_rotation(actual), rotation_mask).norm()),
+        }
+    }
+
+    /// Whether any part of the orientation is constrained
+    pub fn constrains_orientation(&self) -> bool {
+        self.masks().1.iter().any(|constrained| *constrained)
+    }
+
+    /// Which position and rotation components are constrained
+    fn masks(&self) -> ([bool; 3], [bool; 3]) {
+        match self {
+            Target::Pose { .. } | Target::Axis { .. } => ([true; 3], [true; 3]),
+            Target::Position { .. } => ([true; 3], [false; 3]),
+            Target::Masked { position_mask, rotation_mask, .. } => (*position_mask, *rotation_mask),
+        }
+    }
+
+    /// The tool axis as it currently points in the world, and where it should point
+    fn tool_axes(&self, actual: &Matrix4<f32>) -> (Vector3<f32>, Vector3<f32>) {
+        match self {
+            Target::Axis { tool_axis, direction, .. } => (actual.fixed_slice::<3, 3>(0, 0) * tool_axis.normalize(), direction.normalize()),
+            _ => (Vector3::zeros(), Vector3::zeros()),
+        }
+    }
+
+    /// Constrained part of the rotation error, with its norm the geodesic angle still to turn
+    fn geodesic_rotation(&self, actual: &Matrix4<f32>) -> Vector3<f32> {
+        match self {
+            Target::Pose { pose } => rotation_error(actual, pose),
+            Target::Position { .. } => Vector3::zeros(),
+            Target::Axis { .. } => {
+                let (pointing, direction): (Vector3<f32>, Vector3<f32>) = self.tool_axes(actual);
+                rotation_between(&pointing, &direction)
+            },
+            Target::Masked { rotation_mask, .. } => mask(&self.masked_rotation(actual), rotation_mask),
+        }
+    }
+
+    /// Rotation vector of the actual orientation relative to a masked target, in the target frame
+    fn masked_rotation(&self, actual: &Matrix4<f32>) -> Vector3<f32> {
+        match self {
+            Target::Masked { pose, .. } => rotation_vector(&(pose.fixed_slice::<3, 3>(0, 0).transpose() * actual.fixed_slice::<3, 3>(0, 0))),
+            _ => Vector3::zeros(),
+        }
+    }
+}
+
+/// Shortest rotation vector turning one unit vector onto another
+fn rotation_between(from: &Vector3<f32>, to: &Vector3<f32>) -> Vector3<f32> {
+
+    let cross: Vector3<f32> = from.cross(to);
+    let angle: f32 = cross.norm().atan2(from.dot(to));
+
+    match cross.try_normalize(f32::EPSILON) {
+        Some(axis) => axis * angle,
+        // opposite vectors, any perpendicular axis works
+        None if from.dot(to) < 0.0 => from.cross(&Vector3::x()).try_normalize(f32::EPSILON).unwrap_or_else(|| from.cross(&Vector3::y()).normalize()) * PI,
+        None => Vector3::zeros(),
+    }
+}
+
+/// Maps a small rotation applied from the left onto the change of a rotation vector
+fn left_jacobian_inverse(rotation: &Vector3<f32>) -> Matrix3<f32> {
+
+    let angle: f32 = rotation.norm();
+    let cross: Matrix3<f32> = rotation.cross_matrix();
+
+    // the series is exact enough for small angles where the closed form divides by zero
+    let coefficient: f32 = if angle < 1e-3 { 1.0 / 12.0 } else { 1.0 / (angle * angle) - (1.0 + angle.cos()) / (2.0 * angle * angle.sin()) };
+
+    Matrix3::identity() - cross * 0.5 + cross * cross * coefficient
+}
+
+fn mask(vector: &Vector3<f32>, mask: &[bool; 3]) -> Vector3<f32> {
+    Vector3::from_fn(|i, _| if mask[i] { vector[i] } else { 0.0 })
+}
@@ -0,0 +1,370 @@
+extern crate nalgebra as na;
+use na::{Vector3, Isometry3, Translation3, UnitQuaternion};
+use std::fmt;
+use crate::{matrices::{JointType, offset_length}, collision_handler::Shape};
+
+/// Shapes and link frame poses of the colliders of a chain
+pub type CollisionShapes = (Vec<Shape>, Vec<Isometry3<f32>>);
+
+/// Why a URDF could not be loaded or mapped onto the solver model
+#[derive(Debug, Clone, PartialEq)]
+pub enum UrdfError {
+    /// The document is not well formed XML
+    Xml(String),
+    /// A required element or attribute is absent
+    Missing { element: String, name: String },
+    /// An attribute could not be parsed
+    InvalidAttribute { element: String, attribute: String, value: String },
+    /// No link with this name exists
+    UnknownLink(String),
+    /// The tip link is not below the base link
+    NotAChain { base: String, tip: String },
+    /// A joint in the chain has a type the solvers cannot model, e.g. floating or planar
+    UnsupportedJoint { joint: String, joint_type: String },
+    /// A joint in the chain uses an element the solvers cannot model, e.g. mimic
+    UnsupportedElement { joint: String, element: String },
+    /// A link in the chain has collision geometry the colliders cannot model, e.g. a mesh or several elements
+    UnsupportedGeometry { link: String, geometry: String },
+}
+
+impl fmt::Display for UrdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UrdfError::Xml(err) => write!(f, "Invalid XML: {err}"),
+            UrdfError::Missing { element, name } => write!(f, "Missing {name} in <{element}>"),
+            UrdfError::InvalidAttribute { element, attribute, value } => write!(f, "Invalid {attribute}=\"{value}\" in <{element}>"),
+            UrdfError::UnknownLink(link) => write!(f, "Unknown link \"{link}\""),
+            UrdfError::NotAChain { base, tip } => write!(f, "Link \"{tip}\" is not below link \"{base}\""),
+            UrdfError::UnsupportedJoint { joint, joint_type } => write!(f, "Joint \"{joint}\" has unsupported type \"{joint_type}\""),
+            UrdfError::UnsupportedElement { joint, element } => write!(f, "Joint \"{joint}\" uses unsupported element <{element}>"),
+            UrdfError::UnsupportedGeometry { link, geometry } => write!(f, "Link \"{link}\" has unsupported collision geometry {geometry}"),
+        }
+    }
+}
+
+impl std::error::Error for UrdfError {}
+
+/// Collision geometry of a link, sizes are half extents and radii in metres
+#[derive(Debug, Clone, PartialEq)]
+pub enum UrdfGeometry {
+    Box { half_extents: Vector3<f32> },
+    Cylinder { radius: f32, length: f32 },
+    Sphere { radius: f32 },
+    Mesh { filename: String, scale: Vector3<f32> },
+}
+
+/// A `<collision>` element, its origin is relative to the link frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrdfCollision {
+    pub origin: Isometry3<f32>,
+    pub geometry: UrdfGeometry,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrdfLink {
+    pub name: String,
+    pub collisions: Vec<UrdfCollision>,
+}
+
+/// A `<joint>` element, `joint_type` is the raw URDF type
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrdfJoint {
+    pub name: String,
+    pub joint_type: String,
+    pub parent: String,
+    pub child: String,
+    pub origin: Isometry3<f32>,
+    pub axis: Vector3<f32>,
+    pub lower: f32,
+    pub upper: f32,
+    /// Name of the joint this one mimics
+    pub mimic: Option<String>,
+}
+
+/// A parsed robot description
+#[derive(Debug, Clone, PartialEq)]
+pub struct Urdf {
+    pub name: String,
+    pub links: Vec<UrdfLink>,
+    pub joints: Vec<UrdfJoint>,
+}
+
+/// A serial chain in the solver model. Joint i is preceded by offsets[i], fixed joints are kept so every link has its own frame.
+/// links[0] is the base, links[i + 1] is moved by joint i
+#[derive(Debug, Clone, PartialEq)]
+pub struct UrdfChain {
+    pub joint_names: Vec<String>,
+    pub links: Vec<String>,
+    pub axes: Vec<Vector3<f32>>,
+    pub offsets: Vec<Isometry3<f32>>,
+    pub joint_types: Vec<JointType>,
+    pub min_angles: Vec<f32>,
+    pub max_angles: Vec<f32>,
+}
+
+impl UrdfChain {
+
+    /// Link lengths for the solver constructors
+    pub fn radii(&self) -> Vec<f32> {
+        self.offsets.iter().map(offset_length).collect()
+    }
+
+    /// All joints at zero, or at their lower limit if zero is out of range
+    pub fn home_thetas(&self) -> Vec<f32> {
+        self.min_angles.iter().zip(self.max_angles.iter()).map(|(min, max)| na::clamp(0.0, *min, *max)).collect()
+    }
+}
+
+impl Urdf {
+
+    pub fn parse(xml: &str) -> Result<Urdf, UrdfError> {
+
+        let document = roxmltree::Document::parse(xml).map_err(|err| UrdfError::Xml(err.to_string()))?;
+        let robot = document.root_element();
+
+        if !robot.has_tag_name("robot") {
+            return Err(UrdfError::Missing { element: "urdf".to_string(), name: "<robot>".to_string() });
+        }
+
+        let links: Vec<UrdfLink> = robot.children().filter(|node| node.has_tag_name("link")).map(parse_link).collect::<Result<_, _>>()?;
+        let joints: Vec<UrdfJoint> = robot.children().filter(|node| node.has_tag_name("joint")).map(parse_joint).collect::<Result<_, _>>()?;
+
+        Ok(Urdf {
+            name: robot.attribute("name").unwrap_or_default().to_string(),
+            links,
+            joints,
+        })
+    }
+
+    pub fn link(&self, name: &str) -> Option<&UrdfLink> {
+        self.links.iter().find(|link| link.name == name)
+    }
+
+    /// Extract the serial chain from the base link to the tip link
+    pub fn chain(&self, base: &str, tip: &str) -> Result<UrdfChain, UrdfError> {
+
+        for name in [base, tip] {
+            if self.link(name).is_none() {
+                return Err(UrdfError::UnknownLink(name.to_string()));
+            }
+        }
+
+        // walk up from the tip through each link's parent joint
+        let mut joints: Vec<&UrdfJoint> = vec![];
+        let mut link: &str = tip;
+
+        while link != base {
+            let joint: &UrdfJoint = self.joints.iter().find(|joint| joint.child == link)
+            .ok_or_else(|| UrdfError::NotAChain { base: base.to_string(), tip: tip.to_string() })?;
+
+            if joints.len() > self.joints.len() {
+                return Err(UrdfError::NotAChain { base: base.to_string(), tip: tip.to_string() });
+            }
+
+            joints.push(joint);
+            link = &joint.parent;
+        }
+
+        joints.reverse();
+
+        let mut chain = UrdfChain {
+            joint_names: vec![],
+            links: vec![base.to_string()],
+            axes: vec![],
+            offsets: vec![],
+            joint_types: vec![],
+            min_angles: vec![],
+            max_angles: vec![],
+        };
+
+        for joint in joints {
+
+            if joint.mimic.is_some() {
+                return Err(UrdfError::UnsupportedElement { joint: joint.name.clone(), element: "mimic".to_string() });
+            }
+
+            let (joint_type, min, max): (JointType, f32, f32) = match joint.joint_type.as_str() {
+                "revolute" => (JointType::Revolute, joint.lower, joint.upper),
+                "continuous" => (JointType::Continuous, f32::NEG_INFINITY, f32::INFINITY),
+                "prismatic" => (JointType::Prismatic, joint.lower, joint.upper),
+                "fixed" => (JointType::Fixed, 0.0, 0.0),
+                other => return Err(UrdfError::UnsupportedJoint { joint: joint.name.clone(), joint_type: other.to_string() }),
+            };
+
+            chain.joint_names.push(joint.name.clone());
+            chain.links.push(joint.child.clone());
+            chain.axes.push(joint.axis);
+            chain.offsets.push(joint.origin);
+            chain.joint_types.push(joint_type);
+            chain.min_angles.push(min);
+            chain.max_angles.push(max);
+        }
+
+        Ok(chain)
+    }
+
+    /// Shapes and link frame poses of the collider of every link in the chain, for
+    /// CollisionHandler::with_shapes and CollisionHandler::set_arm_offsets. Boxes, cylinders and spheres are supported,
+    /// links without collision geometry get an empty box
+    pub fn collision_shapes(&self, chain: &UrdfChain) -> Result<CollisionShapes, UrdfError> {
+
+        let mut shapes: Vec<Shape> = vec![];
+        let mut offsets: Vec<Isometry3<f32>> = vec![];
+
+        for name in chain.links.iter() {
+
+            let link: &UrdfLink = self.link(name).ok_or_else(|| UrdfError::UnknownLink(name.clone()))?;
+
+            let (shape, offset): (Shape, Isometry3<f32>) = match link.collisions.as_slice() {
+                [] => (Shape::Box { half_extents: Vector3::zeros() }, Isometry3::identity()),
+                [UrdfCollision { origin, geometry }] => match geometry {
+                    UrdfGeometry::Box { half_extents } => (Shape::Box { half_extents: *half_extents }, *origin),
+                    UrdfGeometry::Cylinder { radius, length } => (Shape::Cylinder { half_height: length / 2.0, radius: *radius }, *origin),
+                    UrdfGeometry::Sphere { radius } => (Shape::Sphere { radius: *radius }, *origin),
+                    UrdfGeometry::Mesh { .. } => return Err(UrdfError::UnsupportedGeometry { link: name.clone(), geometry: geometry_name(geometry).to_string() }),
+                },
+                _ => return Err(UrdfError::UnsupportedGeometry { link: name.clone(), geometry: "multiple <collision> elements".to_string() }),
+            };
+
+            shapes.push(shape);
+            offsets.push(offset);
+        }
+
+        Ok((shapes, offsets))
+    }
+}
+
+fn geometry_name(geometry: &UrdfGeometry) -> &'static str {
+    match geometry {
+        UrdfGeometry::Box { .. } => "<box>",
+        UrdfGeometry::Cylinder { .. } => "<cylinder>",
+        UrdfGeometry::Sphere { .. } => "<sphere>",
+        UrdfGeometry::Mesh { .. } => "<mesh>",
+    }
+}
+
+fn parse_link(node: roxmltree::Node) -> Result<UrdfLink, UrdfError> {
+
+    let name: String = required_attribute(node, "name")?.to_string();
+
+    let collisions: Vec<UrdfCollision> = node.children().filter(|child| child.has_tag_name("collision")).map(|collision| {
+        let geometry = child_element(collision, "geometry").ok_or_else(|| missing("collision", "<geometry>"))?;
+        Ok(UrdfCollision {
+            origin: parse_origin(collision)?,
+            geometry: parse_geometry(geometry, &name)?,
+        })
+    }).collect::<Result<_, UrdfError>>()?;
+
+    Ok(UrdfLink { name, collisions })
+}
+
+fn parse_joint(node: roxmltree::Node) -> Result<UrdfJoint, UrdfError> {
+
+    let link_attribute = |tag: &str| -> Result<String, UrdfError> {
+        let element = child_element(node, tag).ok_or_else(|| missing("joint", &format!("<{tag}>")))?;
+        Ok(required_attribute(element, "link")?.to_string())
+    };
+
+    let axis: Vector3<f32> = match child_element(node, "axis") {
+        Some(axis) => parse_vector(axis, "xyz")?.unwrap_or_else(Vector3::x),
+        None => Vector3::x(),
+    };
+
+    let joint_type: &str = required_attribute(node, "type")?;
+
+    // fixed joints never move, so their axis does not matter
+    let axis: Vector3<f32> = match axis.try_normalize(f32::EPSILON) {
+        Some(axis) => axis,
+        None if joint_type == "fixed" => Vector3::x(),
+        None => return Err(missing("axis", "non-zero xyz")),
+    };
+
+    let (lower, upper): (f32, f32) = match child_element(node, "limit") {
+        Some(limit) => (parse_float(limit, "lower")?.unwrap_or(0.0), parse_float(limit, "upper")?.unwrap_or(0.0)),
+        None => (0.0, 0.0),
+    };
+
+    Ok(UrdfJoint {
+        name: required_attribute(node, "name")?.to_string(),
+        joint_type: joint_type.to_string(),
+        parent: link_attribute("parent")?,
+        child: link_attribute("child")?,
+        origin: parse_origin(node)?,
+        axis,
+        lower,
+        upper,
+        mimic: child_element(node, "mimic").map(|mimic| mimic.attribute("joint").unwrap_or_default().to_string()),
+    })
+}
+
+fn parse_geometry(node: roxmltree::Node, link: &str) -> Result<UrdfGeometry, UrdfError> {
+
+    let shape = node.children().find(|child| child.is_element()).ok_or_else(|| missing("geometry", "shape"))?;
+
+    match shape.tag_name().name() {
+        "box" => {
+            let size: Vector3<f32> = parse_vector(shape, "size")?.ok_or_else(|| missing("box", "size"))?;
+            Ok(UrdfGeometry::Box { half_extents: size / 2.0 })
+        },
+        "cylinder" => Ok(UrdfGeometry::Cylinder {
+            radius: parse_float(shape, "radius")?.ok_or_else(|| missing("cylinder", "radius"))?,
+            length: parse_float(shape, "length")?.ok_or_else(|| missing("cylinder", "length"))?,
+        }),
+        "sphere" => Ok(UrdfGeometry::Sphere {
+            radius: parse_float(shape, "radius")?.ok_or_else(|| missing("sphere", "radius"))?,
+        }),
+        "mesh" => Ok(UrdfGeometry::Mesh {
+            filename: required_attribute(shape, "filename")?.to_string(),
+            scale: parse_vector(shape, "scale")?.unwrap_or_else(|| Vector3::new(1.0, 1.0, 1.0)),
+        }),
+        other => Err(UrdfError::UnsupportedGeometry { link: link.to_string(), geometry: format!("<{other}>") }),
+    }
+}
+
+/// The pose from an `<origin xyz rpy>` child, rpy is roll about x, then pitch about y, then yaw about z in the fixed frame
+fn parse_origin(node: roxmltree::Node) -> Result<Isometry3<f32>, UrdfError> {
+
+    let Some(origin) = child_element(node, "origin") else {
+        return Ok(Isometry3::identity());
+    };
+
+    let xyz: Vector3<f32> = parse_vector(origin, "xyz")?.unwrap_or_else(Vector3::zeros);
+    let rpy: Vector3<f32> = parse_vector(origin, "rpy")?.unwrap_or_else(Vector3::zeros);
+
+    Ok(Isometry3::from_parts(Translation3::from(xyz), UnitQuaternion::from_euler_angles(rpy.x, rpy.y, rpy.z)))
+}
+
+fn child_element<'a, 'input>(node: roxmltree::Node<'a, 'input>, tag: &str) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|child| child.has_tag_name(tag))
+}
+
+fn required_attribute<'a>(node: roxmltree::Node<'a, '_>, attribute: &str) -> Result<&'a str, UrdfError> {
+    node.attribute(attribute).ok_or_else(|| missing(node.tag_name().name(), attribute))
+}
+
+fn parse_float(node: roxmltree::Node, attribute: &str) -> Result<Option<f32>, UrdfError> {
+    node.attribute(attribute).map(|value| value.trim().parse::<f32>().map_err(|_| invalid_attribute(node, attribute, value))).transpose()
+}
+
+fn parse_vector(node: roxmltree::Node, attribute: &str) -> Result<Option<Vector3<f32>>, UrdfError> {
+
+    let Some(value) = node.attribute(attribute) else {
+        return Ok(None);
+    };
+
+    let components: Vec<f32> = value.split_whitespace().map(|component| component.parse::<f32>()).collect::<Result<_, _>>()
+    .map_err(|_| invalid_attribute(node, attribute, value))?;
+
+    match components.as_slice() {
+        [x, y, z] => Ok(Some(Vector3::new(*x, *y, *z))),
+        _ => Err(invalid_attribute(node, attribute, value)),
+    }
+}
+
+fn missing(element: &str, name: &str) -> UrdfError {
+    UrdfError::Missing { element: element.to_string(), name: name.to_string() }
+}
+
+fn invalid_attribute(node: roxmltree::Node, attribute: &str, value: &str) -> UrdfError {
+    UrdfError::InvalidAttribute { element: node.tag_name().name().to_string(), attribute: attribute.to_string(), value: value.to_string() }
+}
@@ -2,23 +2,84 @@ use wasm_bindgen::prelude::*;
 use serde::{Serialize, Deserialize};
 
 extern crate nalgebra as na;
-use na::{Vector3, Matrix4};
-use crate::{solver_gd::IKSolverGD, collision_handler::CollisionHandler};
+use na::{Vector3, Matrix4, Isometry3};
+use std::f32::consts::PI;
+use crate::{matrices::{JointType, DHParameters, DHConvention, dh_chain, offset_length}, solver::{IKSolver, SolverType, SolveResult, Tolerance}, solver_gd::{IKSolverGD, GDConfig}, solver_dls::{IKSolverDLS, DLSConfig}, solver_ga::{IKSolverGA, GAConfig}, solver_ccd::IKSolverCCD, solver_fabrik::IKSolverFABRIK, collision_handler::{CollisionHandler, Shape, ColliderPose}, urdf::{Urdf, UrdfChain}, target::Target, objectives::Objective};
 
 #[derive(Serialize, Deserialize, Debug)]
 struct Fields {
     origin: Matrix4<f32>,
     thetas: Vec<f32>,
+    #[serde(default)]
     axes: Vec<Vector3<f32>>,
+    #[serde(default)]
     radii: Vec<f32>,
+    /// Revolute for every joint if empty, only the gd solver supports other joint types
+    #[serde(default)]
+    joint_types: Vec<JointType>,
+    /// Fixed transform before each joint, straight links from radii if empty
+    #[serde(default)]
+    link_offsets: Vec<Isometry3<f32>>,
+    /// Replaces axes, radii and link offsets if set, with one angle per row. The classic convention ends in a fixed tool transform
+    #[serde(default)]
+    dh: Option<DHTable>,
+    /// Replaces axes, radii, link offsets, joint types and limits if set, and the arm colliders if arm_half_extents and arm_shapes are empty
+    #[serde(default)]
+    urdf: Option<UrdfSource>,
 
+    #[serde(default)]
     min_angles: Vec<f32>,
+    #[serde(default)]
     max_angles: Vec<f32>,
 
+    #[serde(default)]
     arm_half_extents: Vec<Vector3<f32>>,
-    arm_offsets: Vec<Vector3<f32>>,
+    /// Replaces the boxes from arm_half_extents if set
+    #[serde(default)]
+    arm_shapes: Vec<Shape>,
+    /// Pose of each link collider in the frame of its link, e.g. off-axis for a motor housing.
+    /// Replaces the default placement along the link, and the URDF collision origins, if set
+    #[serde(default)]
+    arm_offsets: Vec<ColliderPose>,
+    #[serde(default)]
     world_half_extents: Vec<Vector3<f32>>,
-    world_offsets: Vec<Vector3<f32>>
+    /// Replaces the boxes from world_half_extents if set
+    #[serde(default)]
+    world_shapes: Vec<Shape>,
+    /// A translation, a translation and rotation, or a 4x4 matrix for each obstacle
+    #[serde(default)]
+    world_offsets: Vec<ColliderPose>,
+    /// Colliders closer than this count as colliding
+    #[serde(default)]
+    safety_margin: f32,
+
+    #[serde(default)]
+    solver: SolverType,
+    #[serde(default)]
+    gd_config: GDConfig,
+    #[serde(default)]
+    dls_config: DLSConfig,
+    #[serde(default)]
+    ga_config: GAConfig,
+    /// Null space objectives, only used by the dls solver
+    #[serde(default)]
+    objectives: Vec<Objective>,
+}
+
+/// Denavit-Hartenberg description of the arm
+#[derive(Serialize, Deserialize, Debug)]
+struct DHTable {
+    #[serde(default)]
+    convention: DHConvention,
+    parameters: Vec<DHParameters>,
+}
+
+/// URDF robot description and the links the chain runs between
+#[derive(Serialize, Deserialize, Debug)]
+struct UrdfSource {
+    xml: String,
+    base: String,
+    tip: String,
 }
 
 #[wasm_bindgen]
@@ -28,31 +89,210 @@ extern {
 
 #[wasm_bindgen]
 pub struct InverseKinematics {
-    ik_solver: IKSolverGD,
+    ik_solver: Box<dyn IKSolver>,
 }
 
 #[wasm_bindgen]
 impl InverseKinematics {
 
-    pub fn new(field_str: &str) -> InverseKinematics {
+    /// Build the solver from the JSON fields, bad fields throw an error with the reason in JS
+    pub fn new(field_str: &str) -> Result<InverseKinematics, JsValue> {
+        InverseKinematics::from_json(field_str).map_err(|err| JsValue::from_str(&err))
+    }
+
+    /// Solve for the target, returning the SolveResult as JSON. time_budget is in milliseconds.
+    /// The target is either a pose matrix or a tagged target, e.g. {"type": "position", "position": [1, 2, 3]}
+    pub fn solve(&mut self, target_str: &str, thresh: f32, time_budget: Option<f64>) -> Result<String, JsValue> {
+
+        let target: Target = parse_target(target_str).map_err(|err| JsValue::from_str(&err))?;
+
+        self.ik_solver.limits_mut().time_budget = time_budget;
+        let result: SolveResult = self.ik_solver.solve_target(target, thresh);
+    	serde_json::to_string(&result).map_err(|err| JsValue::from_str(&err.to_string()))
+    }
+
+    /// Solve until the end effector is within max_position_error, in the length unit of the chain,
+    /// and max_orientation_error in degrees of the target. Otherwise the same as solve
+    pub fn solve_within(&mut self, target_str: &str, max_position_error: f32, max_orientation_error: f32, time_budget: Option<f64>) -> Result<String, JsValue> {
+
+        let target: Target = parse_target(target_str).map_err(|err| JsValue::from_str(&err))?;
+
+        self.ik_solver.limits_mut().time_budget = time_budget;
+        let result: SolveResult = self.ik_solver.solve_within(target, Tolerance::new(max_position_error, max_orientation_error.to_radians()));
+    	serde_json::to_string(&result).map_err(|err| JsValue::from_str(&err.to_string()))
+    }
+}
+
+impl InverseKinematics {
+
+    /// Build the solver from the JSON fields, checking everything the solvers would otherwise panic on
+    pub fn from_json(field_str: &str) -> Result<InverseKinematics, String> {
+
+        let mut fields: Fields = serde_json::from_str(field_str).map_err(|err| format!("Invalid fields! {err}"))?;
+
+        let mut tool: Isometry3<f32> = Isometry3::identity();
+
+        // one joint per row, starting at zero if thetas is empty. Joints without limits turn all the way round
+        if let Some(dh) = fields.dh.take() {
+            let joints: usize = dh.parameters.len();
+            if !fields.thetas.is_empty() && fields.thetas.len() != joints {
+                return Err(format!("Vector lengths unequal! angles: {}, DH rows: {}", fields.thetas.len(), joints));
+            }
+
+            let (axes, offsets, dh_tool) = dh_chain(&dh.parameters, dh.convention);
+            if fields.thetas.is_empty() {
+                fields.thetas = vec![0.0; joints];
+            }
+            if fields.min_angles.is_empty() {
+                fields.min_angles = vec![-PI; joints];
+            }
+            if fields.max_angles.is_empty() {
+                fields.max_angles = vec![PI; joints];
+            }
+            fields.radii = offsets.iter().map(offset_length).collect();
+            fields.axes = axes;
+            fields.link_offsets = offsets;
+            tool = dh_tool;
+        }
+
+        let mut arm_collider_offsets: Vec<Isometry3<f32>> = vec![];
+
+        if let Some(source) = fields.urdf.take() {
+            let urdf: Urdf = Urdf::parse(&source.xml).map_err(|err| format!("Invalid URDF! {err}"))?;
+            let chain: UrdfChain = urdf.chain(&source.base, &source.tip).map_err(|err| format!("Invalid URDF chain! {err}"))?;
+
+            if fields.arm_half_extents.is_empty() && fields.arm_shapes.is_empty() {
+                (fields.arm_shapes, arm_collider_offsets) = urdf.collision_shapes(&chain).map_err(|err| format!("Invalid URDF collisions! {err}"))?;
+            }
+
+            if fields.thetas.is_empty() {
+                fields.thetas = chain.home_thetas();
+            }
 
-        // TODO: something here to let the user know what failed if we didn't get good values in
-        let fields: Fields = serde_json::from_str(field_str).unwrap();
+            // fixed joints are locked by their limits, so the other solvers can treat them as revolute.
+            // Only gd wraps the angles of continuous joints, so they are left for the check below with prismatic joints
+            fields.joint_types = chain.joint_types.iter().map(|joint_type| match joint_type {
+                JointType::Fixed if fields.solver != SolverType::GD => JointType::Revolute,
+                joint_type => *joint_type,
+            }).collect();
+
+            fields.radii = chain.radii();
+            fields.axes = chain.axes;
+            fields.link_offsets = chain.offsets;
+            fields.min_angles = chain.min_angles;
+            fields.max_angles = chain.max_angles;
+        }
 
         // alert(&format!("{:?}", fields));
 
-        let collision_handler: CollisionHandler = CollisionHandler::new(&fields.arm_half_extents, &fields.world_half_extents, &fields. world_offsets);
+        if fields.arm_shapes.is_empty() {
+            fields.arm_shapes = fields.arm_half_extents.iter().map(|half_extents| Shape::Box { half_extents: *half_extents }).collect();
+        }
+        if fields.world_shapes.is_empty() {
+            fields.world_shapes = fields.world_half_extents.iter().map(|half_extents| Shape::Box { half_extents: *half_extents }).collect();
+        }
 
-        InverseKinematics {
-            ik_solver: IKSolverGD::new(fields.origin, &fields.thetas, &fields.axes, &fields.radii, &fields.min_angles, &fields.max_angles, collision_handler.clone())
+        if let Some(err) = fields.arm_shapes.iter().chain(fields.world_shapes.iter()).find_map(|shape| shape.validate().err()) {
+            return Err(format!("Invalid shape! {err}"));
         }
-    }
+        if fields.world_shapes.len() != fields.world_offsets.len() {
+            return Err(format!("Vector lengths unequal! obstacles: {}, obstacle offsets: {}", fields.world_shapes.len(), fields.world_offsets.len()));
+        }
+        if let Some(pose) = fields.world_offsets.iter().find(|pose| pose.to_isometry().is_none()) {
+            return Err(format!("Invalid obstacle pose! {:?} is not a rigid transform", pose));
+        }
+
+        if !fields.arm_offsets.is_empty() {
+            arm_collider_offsets = fields.arm_offsets.iter().map(|pose| pose.to_isometry().ok_or_else(|| format!("Invalid arm offset! {:?} is not a rigid transform", pose))).collect::<Result<_, _>>()?;
+        }
+        if !arm_collider_offsets.is_empty() && arm_collider_offsets.len() != fields.arm_shapes.len() {
+            return Err(format!("Vector lengths unequal! arm offsets: {}, arm colliders: {}", arm_collider_offsets.len(), fields.arm_shapes.len()));
+        }
+
+        if !(fields.safety_margin >= 0.0 && fields.safety_margin.is_finite()) {
+            return Err(format!("Invalid safety margin! {} must be finite and not negative", fields.safety_margin));
+        }
+
+        let mut collision_handler: CollisionHandler = CollisionHandler::with_poses(&fields.arm_shapes, &fields.world_shapes, &fields.world_offsets);
+
+        if !arm_collider_offsets.is_empty() {
+            collision_handler.set_arm_offsets(&arm_collider_offsets);
+        }
+
+        collision_handler.set_safety_margin(fields.safety_margin);
+
+        let joints: usize = fields.thetas.len();
+        let joint_types: Vec<JointType> = if fields.joint_types.is_empty() { vec![JointType::Revolute; joints] } else { fields.joint_types };
+
+        if fields.axes.len() != joints || fields.radii.len() != joints || fields.min_angles.len() != joints || fields.max_angles.len() != joints || joint_types.len() != joints {
+            return Err(format!("Vector lengths unequal! angles: {}, axes: {}, radii: {}, min angles: {}, max angles: {}, joint types: {}",
+            joints, fields.axes.len(), fields.radii.len(), fields.min_angles.len(), fields.max_angles.len(), joint_types.len()));
+        }
+        if !fields.link_offsets.is_empty() && fields.link_offsets.len() != joints {
+            return Err(format!("Vector lengths unequal! angles: {}, offsets: {}", joints, fields.link_offsets.len()));
+        }
+
+        collision_handler.validate_arm(joints).map_err(|err| format!("Invalid arm colliders! {err}"))?;
 
-    pub fn solve(&mut self, target_str: &str, thresh: f32) -> String {
+        if fields.solver != SolverType::GD && joint_types.iter().any(|joint_type| *joint_type != JointType::Revolute) {
+            return Err("Joint types other than revolute are only supported by the gd solver!".to_string());
+        }
+
+        match fields.solver {
+            SolverType::GD => fields.gd_config.validate().map_err(|err| format!("Invalid config! {err}"))?,
+            SolverType::DLS => {
+                fields.dls_config.validate().map_err(|err| format!("Invalid config! {err}"))?;
+                if let Some(err) = fields.objectives.iter().find_map(|objective| objective.validate(joints).err()) {
+                    return Err(format!("Invalid objective! {err}"));
+                }
+            },
+            SolverType::GA => {
+                if fields.ga_config.population_size == 0 || fields.ga_config.elitism > fields.ga_config.population_size {
+                    return Err(format!("Invalid population! population size: {}, elitism: {}", fields.ga_config.population_size, fields.ga_config.elitism));
+                }
+                fields.ga_config.validate().map_err(|err| format!("Invalid config! {err}"))?;
+            },
+            SolverType::CCD | SolverType::FABRIK => {},
+        }
+
+        let link_offsets: Option<&[Isometry3<f32>]> = if fields.link_offsets.is_empty() { None } else { Some(&fields.link_offsets) };
 
-        let target: Matrix4<f32> = serde_json::from_str(target_str).unwrap();
+        let ik_solver: Box<dyn IKSolver> = match fields.solver {
+            SolverType::GD => {
+                let solver: IKSolverGD = IKSolverGD::with_config(fields.origin, &fields.thetas, &fields.axes, &fields.radii, &fields.min_angles, &fields.max_angles, collision_handler, fields.gd_config);
+                let solver: IKSolverGD = match link_offsets { Some(offsets) => solver.with_offsets(offsets), None => solver };
+                Box::new(solver.with_joint_types(&joint_types).with_tool(&tool))
+            },
+            SolverType::DLS => {
+                let solver: IKSolverDLS = IKSolverDLS::new(fields.origin, &fields.thetas, &fields.axes, &fields.radii, &fields.min_angles, &fields.max_angles, collision_handler).with_objectives(&fields.objectives).with_config(fields.dls_config);
+                Box::new(match link_offsets { Some(offsets) => solver.with_offsets(offsets), None => solver }.with_tool(&tool))
+            },
+            SolverType::GA => {
+                let solver: IKSolverGA = IKSolverGA::new(fields.origin, &fields.thetas, &fields.axes, &fields.radii, &fields.min_angles, &fields.max_angles, collision_handler, fields.ga_config);
+                Box::new(match link_offsets { Some(offsets) => solver.with_offsets(offsets), None => solver }.with_tool(&tool))
+            },
+            SolverType::CCD => {
+                let solver: IKSolverCCD = IKSolverCCD::new(fields.origin, &fields.thetas, &fields.axes, &fields.radii, &fields.min_angles, &fields.max_angles, collision_handler);
+                Box::new(match link_offsets { Some(offsets) => solver.with_offsets(offsets), None => solver }.with_tool(&tool))
+            },
+            SolverType::FABRIK => {
+                let solver: IKSolverFABRIK = IKSolverFABRIK::new(fields.origin, &fields.thetas, &fields.axes, &fields.radii, &fields.min_angles, &fields.max_angles, collision_handler);
+                Box::new(match link_offsets { Some(offsets) => solver.with_offsets(offsets), None => solver }.with_tool(&tool))
+            },
+        };
 
-        self.ik_solver.solve(target, thresh);
-    	serde_json::to_string(&self.ik_solver.thetas).unwrap()
+        Ok(InverseKinematics {
+            ik_solver
+        })
     }
-}  
\ No newline at end of file
+}
+
+/// A pose matrix or a tagged target
+fn parse_target(target_str: &str) -> Result<Target, String> {
+    let target: Target = match serde_json::from_str::<Matrix4<f32>>(target_str) {
+        Ok(pose) => Target::from(pose),
+        Err(_) => serde_json::from_str(target_str).map_err(|err| format!("Invalid target! {err}"))?,
+    };
+    target.validate().map_err(|err| format!("Invalid target! {err}"))?;
+    Ok(target)
+}
@@ -0,0 +1,134 @@
+#[macro_use]
+extern crate approx; // For the macro relative_eq!
+extern crate nalgebra as na;
+
+#[cfg(test)]
+mod solver_tests {
+
+    use krust::collision_handler::CollisionHandler;
+    use na::{Vector3, Matrix4};
+    use krust::matrices::{IDENTITY, generate_matrices, generate_forward_matrices};
+    use krust::solver::{IKSolver, SolveResult, TerminationReason};
+    use krust::solver_ccd::IKSolverCCD;
+
+    const TARGET: Matrix4<f32> = Matrix4::new(  
+        1.0,0.0,0.0,0.0,
+        0.0,1.0,0.0,3.0,
+        0.0,0.0,1.0,3.0,
+        0.0,0.0,0.0,1.0  
+    );
+
+    #[test]
+    fn test_new_solver_success() {
+
+        let angles: Vec<f32> = vec![0.0,0.0,0.0];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::y_axis(), *Vector3::z_axis()];
+        let radii: Vec<f32> = vec![5.0,3.0,1.0];
+
+        let min_angles: Vec<f32> = vec![-100.0, -100.0, -100.0];
+        let max_angles: Vec<f32> = vec![100.0, 100.0, 100.0];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let ik_solver: IKSolverCCD = IKSolverCCD::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler);
+
+        assert_eq!(angles, ik_solver.chain.thetas);
+        assert_eq!(axes, ik_solver.chain.axes);
+        assert_eq!(radii, ik_solver.chain.radii);
+        assert_eq!(9.0, ik_solver.chain.arm_length);
+
+    }
+
+    #[test]
+    fn test_solver_solve() {
+
+        let angles: Vec<f32> = vec![0.0,0.0,0.0];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::x_axis(), *Vector3::x_axis()];
+        let radii: Vec<f32> = vec![2.0,2.0,2.0];
+
+        let min_angles: Vec<f32> = vec![-100.0, -100.0, -100.0];
+        let max_angles: Vec<f32> = vec![100.0, 100.0, 100.0];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let mut ik_solver: IKSolverCCD = IKSolverCCD::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler);
+
+        ik_solver.solve(TARGET, 0.00001);
+
+        assert!(ik_solver.loss < 0.00001);
+        assert!(relative_eq!(ik_solver.chain.end_effector, TARGET, epsilon = 0.05));
+
+    }
+
+    #[test]
+    fn test_solver_respects_limits() {
+
+        let angles: Vec<f32> = vec![0.0,0.0,0.0];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::x_axis(), *Vector3::x_axis()];
+        let radii: Vec<f32> = vec![2.0,2.0,2.0];
+
+        let min_angles: Vec<f32> = vec![-0.3, -0.3, -0.3];
+        let max_angles: Vec<f32> = vec![0.3, 0.3, 0.3];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let mut ik_solver: IKSolverCCD = IKSolverCCD::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler);
+
+        ik_solver.solve(TARGET, 0.00001);
+
+        for theta in ik_solver.chain.thetas.iter() {
+            assert!((-0.3..=0.3).contains(theta));
+        }
+
+    }
+
+    #[test]
+    fn test_solver_avoids_collisions() {
+
+        // Same arm as the collision tests, starting from a configuration that is not colliding
+        let angles: Vec<f32> = vec![0.7768, -0.4101, 1.0668, 0.0085, 1.7568, 0.6496, 0.0137, -1.2653, -1.7978, -0.7687];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis(), *Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis(), *Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis(), *Vector3::z_axis()];
+        let radii: Vec<f32> = vec![1.0, 4.0, 4.0, 4.0, 2.0, 4.0, 4.0, 1.0, 2.0, 2.0];
+
+        let min_angles: Vec<f32> = vec![-100.0; 10];
+        let max_angles: Vec<f32> = vec![100.0; 10];
+
+        let arm: Vec<Vector3<f32>> = radii.iter().map(|length| Vector3::new(0.6, 0.6, *length / 2.0)).collect();
+        let collision_handler: CollisionHandler = CollisionHandler::new(&arm, &[], &[]);
+
+        let mut ik_solver: IKSolverCCD = IKSolverCCD::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler.clone());
+
+        // target close to the base
+        let target: Matrix4<f32> = Matrix4::new_translation(&Vector3::new(2.0, 0.0, 1.0));
+        ik_solver.solve(target, 0.00001);
+
+        let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(IDENTITY, &ik_solver.chain.thetas, &axes, &radii));
+        assert!(collision_handler.find_arm_collisions_self(&forward_mats).iter().all(|colliding| !colliding));
+
+    }
+
+    #[test]
+    fn test_solver_collision_blocked() {
+
+        let angles: Vec<f32> = vec![0.0,0.0,0.0];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::x_axis(), *Vector3::x_axis()];
+        let radii: Vec<f32> = vec![2.0,2.0,2.0];
+
+        let min_angles: Vec<f32> = vec![-100.0, -100.0, -100.0];
+        let max_angles: Vec<f32> = vec![100.0, 100.0, 100.0];
+
+        // wall between the arm and the target
+        let arm: Vec<Vector3<f32>> = radii.iter().map(|length| Vector3::new(0.3, 0.3, *length / 2.0)).collect();
+        let collision_handler: CollisionHandler = CollisionHandler::new(&arm, &[Vector3::new(5.0, 0.25, 5.0)], &[Vector3::new(0.0, 1.5, 5.0)]);
+
+        let mut ik_solver: IKSolverCCD = IKSolverCCD::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler);
+
+        let result: SolveResult = ik_solver.solve(TARGET, 0.00001);
+
+        assert!(!result.success);
+        assert_eq!(result.reason, TerminationReason::CollisionBlocked);
+        assert_eq!(result.thetas, angles);
+
+    }
+
+}
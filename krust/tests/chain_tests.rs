@@ -0,0 +1,89 @@
+#[macro_use]
+extern crate approx; // For the macro relative_eq!
+extern crate nalgebra as na;
+
+#[cfg(test)]
+mod chain_tests {
+
+    use na::{Vector3, Matrix4, Isometry3, DMatrix};
+    use std::f32::consts::PI;
+    use krust::chain::Chain;
+    use krust::matrices::{IDENTITY, JointType, generate_matrices, generate_forward_matrices};
+
+    fn chain() -> Chain {
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::y_axis(), *Vector3::z_axis()];
+        Chain::new(IDENTITY, &[0.3, -0.2, 0.1], &axes, &[5.0, 3.0, 1.0], &[-1.0; 3], &[1.0; 3])
+    }
+
+    #[test]
+    fn test_new_chain_matches_generated_matrices() {
+
+        let chain: Chain = chain();
+        let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(IDENTITY, &chain.thetas, &chain.axes, &chain.radii));
+
+        assert_eq!(chain.len(), 3);
+        assert_eq!(chain.arm_length, 9.0);
+        assert!(relative_eq!(chain.end_effector, forward_mats[forward_mats.len() - 1], epsilon = 1e-6));
+        assert!(relative_eq!(chain.forward_matrices(&chain.thetas)[3], chain.end_effector, epsilon = 1e-6));
+
+    }
+
+    #[test]
+    #[should_panic(expected = "Vector lengths unequal!")]
+    fn test_new_chain_unequal_lengths() {
+        Chain::new(IDENTITY, &[0.0, 0.0], &[*Vector3::x_axis()], &[1.0, 1.0], &[-1.0; 2], &[1.0; 2]);
+    }
+
+    #[test]
+    fn test_set_offsets() {
+
+        let mut chain: Chain = chain();
+        chain.set_offsets(&[Isometry3::translation(0.0, 0.0, 1.0), Isometry3::translation(0.0, 3.0, 4.0), Isometry3::translation(0.0, 0.0, 2.0)]);
+
+        assert_eq!(chain.radii, vec![1.0, 5.0, 2.0]);
+        assert_eq!(chain.arm_length, 8.0);
+        assert!(relative_eq!(chain.end_effector, chain.forward_matrices(&chain.thetas)[3], epsilon = 1e-6));
+
+    }
+
+    #[test]
+    fn test_set_tool() {
+
+        let mut chain: Chain = chain();
+        let last_joint: Matrix4<f32> = chain.end_effector;
+        let tool: Isometry3<f32> = Isometry3::translation(0.0, 0.5, 2.0);
+        chain.set_tool(&tool);
+
+        assert!(relative_eq!(chain.end_effector, last_joint * tool.to_homogeneous(), epsilon = 1e-6));
+        assert!(relative_eq!(chain.arm_length, 9.0 + 4.25_f32.sqrt(), epsilon = 1e-6));
+
+        // the jacobian is of the tool, not the last joint
+        let delta: f32 = 0.001;
+        let jacobian: DMatrix<f32> = chain.jacobian(&chain.forward_mats);
+        for i in 0..chain.len() {
+            let mut moved: Vec<f32> = chain.thetas.clone();
+            moved[i] += delta;
+            let velocity: Vector3<f32> = (chain.tool_pose(&chain.forward_matrices(&moved)) - chain.end_effector).fixed_slice::<3, 1>(0, 3) / delta;
+            assert!(relative_eq!(jacobian.fixed_slice::<3, 1>(0, i).into_owned(), velocity, epsilon = 0.01));
+        }
+
+    }
+
+    #[test]
+    fn test_set_joint_types() {
+
+        let mut chain: Chain = chain();
+        chain.thetas[0] = 3.0 * PI;
+        chain.set_joint_types(&[JointType::Continuous, JointType::Revolute, JointType::Prismatic]);
+
+        assert_eq!(chain.min_angles[0], f32::NEG_INFINITY);
+        assert_eq!(chain.max_angles[0], f32::INFINITY);
+        assert!(relative_eq!(chain.thetas[0], PI, epsilon = 1e-5));
+
+        // the prismatic joint slides along z instead of turning about it
+        let slid: Vec<Matrix4<f32>> = chain.forward_matrices(&[0.0, 0.0, 0.5]);
+        assert!(relative_eq!(slid[3][(2, 3)], 9.5, epsilon = 1e-6));
+
+    }
+
+}
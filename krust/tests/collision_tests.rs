@@ -1,11 +1,14 @@
+// Arm angles are recorded from the JS demo at f64 precision
+#![allow(clippy::excessive_precision)]
+
 extern crate nalgebra as na;
 
 #[cfg(test)]
 mod solver_tests {
 
-    use krust::collision_handler::CollisionHandler;
-    use na::{Vector3, Matrix4};
-    use krust::matrices::{IDENTITY, generate_matrices, generate_forward_matrices};
+    use krust::collision_handler::{CollisionHandler, Shape, ColliderPose, Proximity};
+    use na::{Vector3, Matrix4, Isometry3, UnitQuaternion, Translation3};
+    use krust::matrices::{IDENTITY, generate_matrices, generate_matrices_with_joints, generate_matrices_with_offsets, generate_forward_matrices, radius_offsets, JointType};
     
     #[test]
     fn test_arm_collisions_true() {
@@ -17,12 +20,12 @@ mod solver_tests {
 
         let arm: Vec<Vector3<f32>> = radii.iter().map(|length| Vector3::new(0.6, 0.6, *length / 2.0)).collect();
 
-        let collision_handler: CollisionHandler = CollisionHandler::new(&arm, &vec![], &vec![]);
+        let collision_handler: CollisionHandler = CollisionHandler::new(&arm, &[], &[]);
 
         let mats: Vec<Matrix4<f32>> = generate_matrices(IDENTITY, &angles, &axes, &radii);
         let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&mats);
 
-        assert_eq!(collision_handler.is_arm_colliding_self_naive(&forward_mats), true);
+        assert!(collision_handler.is_arm_colliding_self_naive(&forward_mats));
         assert_eq!(collision_handler.find_arm_collisions_self(&forward_mats), vec![false, false, true, false, false, false, false, true, true, false]);
 
     }
@@ -37,12 +40,12 @@ mod solver_tests {
 
         let arm: Vec<Vector3<f32>> = radii.iter().map(|length| Vector3::new(0.6, 0.6, *length / 2.0)).collect();
 
-        let collision_handler: CollisionHandler = CollisionHandler::new(&arm, &vec![], &vec![]);
+        let collision_handler: CollisionHandler = CollisionHandler::new(&arm, &[], &[]);
 
         let mats: Vec<Matrix4<f32>> = generate_matrices(IDENTITY, &angles, &axes, &radii);
         let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&mats);
 
-        assert_eq!(collision_handler.is_arm_colliding_self_naive(&forward_mats), false);
+        assert!(!collision_handler.is_arm_colliding_self_naive(&forward_mats));
         assert_eq!(collision_handler.find_arm_collisions_self(&forward_mats), vec![false, false, false, false, false, false, false, false, false, false]);
 
     }
@@ -65,13 +68,13 @@ mod solver_tests {
         let offset2: Vector3<f32> = Vector3::new(0.0, 5.0, 10.0);
 
         // collision handler
-        let collision_handler: CollisionHandler = CollisionHandler::new(&arm, &vec![obs1, obs2], &vec![offset1, offset2]);
+        let collision_handler: CollisionHandler = CollisionHandler::new(&arm, &[obs1, obs2], &[offset1, offset2]);
 
         // configure arm position
         let mats: Vec<Matrix4<f32>> = generate_matrices(IDENTITY, &angles, &axes, &radii);
         let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&mats);
 
-        assert_eq!(collision_handler.is_arm_colliding_world_naive(&forward_mats), true);
+        assert!(collision_handler.is_arm_colliding_world_naive(&forward_mats));
         assert_eq!(collision_handler.find_arm_collisions_world(&forward_mats), vec![false, false, false, false, false, false, true, true, false, true]);
 
     }
@@ -94,15 +97,284 @@ mod solver_tests {
         let offset2: Vector3<f32> = Vector3::new(0.0, 5.0, 10.0);
 
         // collision handler
-        let collision_handler: CollisionHandler = CollisionHandler::new(&arm, &vec![obs1, obs2], &vec![offset1, offset2]);
+        let collision_handler: CollisionHandler = CollisionHandler::new(&arm, &[obs1, obs2], &[offset1, offset2]);
 
         // configure arm position
         let mats: Vec<Matrix4<f32>> = generate_matrices(IDENTITY, &angles, &axes, &radii);
         let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&mats);
 
-        assert_eq!(collision_handler.is_arm_colliding_world_naive(&forward_mats), false);
+        assert!(!collision_handler.is_arm_colliding_world_naive(&forward_mats));
         assert_eq!(collision_handler.find_arm_collisions_world(&forward_mats), vec![false, false, false, false, false, false, false, false, false, false]);
 
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_telescoping_link() {
+
+        // single link along z that telescopes through a prismatic joint
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis()];
+        let radii: Vec<f32> = vec![1.0];
+        let joint_types: Vec<JointType> = vec![JointType::Prismatic];
+
+        let arm: Vec<Vector3<f32>> = vec![Vector3::new(0.2, 0.2, 0.5)];
+        let obstacle: Vector3<f32> = Vector3::new(1.0, 1.0, 0.5);
+        let offset: Vector3<f32> = Vector3::new(0.0, 0.0, 3.0);
+
+        let mut collision_handler: CollisionHandler = CollisionHandler::new(&arm, &[obstacle], &[offset]);
+
+        let retracted: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices_with_joints(IDENTITY, &[0.0], &axes, &radius_offsets(&radii), &joint_types));
+        let extended: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices_with_joints(IDENTITY, &[2.5], &axes, &radius_offsets(&radii), &joint_types));
+
+        // without joint types the box keeps its length
+        assert!(!collision_handler.is_arm_colliding_world_naive(&extended));
+
+        collision_handler.set_joint_types(&joint_types, &radii);
+        assert!(!collision_handler.is_arm_colliding_world_naive(&retracted));
+        assert!(collision_handler.is_arm_colliding_world_naive(&extended));
+
+    }
+
+    #[test]
+    fn test_link_offset_boxes() {
+
+        // a single link reaching sideways along x instead of up along z
+        let offsets: Vec<Isometry3<f32>> = vec![Isometry3::translation(2.0, 0.0, 0.0)];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis()];
+
+        let arm: Vec<Vector3<f32>> = vec![Vector3::new(0.2, 0.2, 1.0)];
+        let obstacle: Vector3<f32> = Vector3::new(0.3, 0.3, 0.3);
+        let offset: Vector3<f32> = Vector3::new(1.0, 0.0, 0.0);
+
+        let mut collision_handler: CollisionHandler = CollisionHandler::new(&arm, &[obstacle], &[offset]);
+        let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices_with_offsets(IDENTITY, &[0.0], &axes, &offsets));
+
+        // by default the box still points up z
+        assert!(!collision_handler.is_arm_colliding_world_naive(&forward_mats));
+
+        collision_handler.set_link_offsets(&offsets);
+        assert!(collision_handler.is_arm_colliding_world_naive(&forward_mats));
+
+    }
+
+    #[test]
+    fn test_shape_colliders() {
+
+        // a single link reaching sideways along x, so capsules and cylinders run along x too
+        let offsets: Vec<Isometry3<f32>> = vec![Isometry3::translation(2.0, 0.0, 0.0)];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis()];
+        let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices_with_offsets(IDENTITY, &[0.0], &axes, &offsets));
+
+        let capsule: [Shape; 1] = [Shape::Capsule { half_height: 0.8, radius: 0.2 }];
+        let cylinder: [Shape; 1] = [Shape::Cylinder { half_height: 0.8, radius: 0.2 }];
+
+        // a ball just past the end of the link only touches the rounded cap of the capsule
+        let ball: [Shape; 1] = [Shape::Sphere { radius: 0.1 }];
+        let ball_offset: Vector3<f32> = Vector3::new(2.05, 0.0, 0.0);
+
+        let mut collision_handler: CollisionHandler = CollisionHandler::with_shapes(&capsule, &ball, &[ball_offset]);
+        collision_handler.set_link_offsets(&offsets);
+        assert!(collision_handler.is_arm_colliding_world_naive(&forward_mats));
+        assert!(collision_handler.is_arm_colliding_world(0, &forward_mats));
+        assert_eq!(collision_handler.find_arm_collisions_world(&forward_mats), vec![true]);
+
+        let mut collision_handler: CollisionHandler = CollisionHandler::with_shapes(&cylinder, &ball, &[ball_offset]);
+        collision_handler.set_link_offsets(&offsets);
+        assert!(!collision_handler.is_arm_colliding_world_naive(&forward_mats));
+        assert_eq!(collision_handler.find_arm_collisions_world(&forward_mats), vec![false]);
+
+        // a thin upright post beside the link, standing along world z
+        let post: [Shape; 1] = [Shape::Cylinder { half_height: 1.0, radius: 0.1 }];
+
+        let mut collision_handler: CollisionHandler = CollisionHandler::with_shapes(&cylinder, &post, &[Vector3::new(1.0, 0.5, 0.0)]);
+        collision_handler.set_link_offsets(&offsets);
+        assert!(!collision_handler.is_arm_colliding_world_naive(&forward_mats));
+        assert!((collision_handler.world_clearance(&forward_mats) - 0.2).abs() < 1e-3);
+
+        let mut collision_handler: CollisionHandler = CollisionHandler::with_shapes(&cylinder, &post, &[Vector3::new(1.0, 0.25, 0.0)]);
+        collision_handler.set_link_offsets(&offsets);
+        assert!(collision_handler.is_arm_colliding_world_naive(&forward_mats));
+
+    }
+
+    #[test]
+    fn test_shape_json() {
+
+        let shapes: Vec<Shape> = serde_json::from_str(r#"[
+            {"type": "box", "half_extents": [0.1, 0.2, 0.3]},
+            {"type": "sphere", "radius": 0.5},
+            {"type": "capsule", "half_height": 0.4, "radius": 0.1},
+            {"type": "cylinder", "half_height": 1.0, "radius": 0.2}
+        ]"#).unwrap();
+
+        assert_eq!(shapes, vec![
+            Shape::Box { half_extents: Vector3::new(0.1, 0.2, 0.3) },
+            Shape::Sphere { radius: 0.5 },
+            Shape::Capsule { half_height: 0.4, radius: 0.1 },
+            Shape::Cylinder { half_height: 1.0, radius: 0.2 },
+        ]);
+
+        assert!(Shape::Sphere { radius: -1.0 }.validate().is_err());
+        assert_eq!(Shape::Sphere { radius: 0.5 }.stretched(1.0), Shape::Capsule { half_height: 0.5, radius: 0.5 });
+
+    }
+
+    #[test]
+    fn test_rotated_obstacles() {
+
+        // a plank along x above the origin, turned a quarter turn about z it runs along y instead
+        let plank: Vector3<f32> = Vector3::new(1.0, 0.1, 0.1);
+        let turned: Isometry3<f32> = Isometry3::from_parts(Translation3::new(0.0, 0.0, 3.0), UnitQuaternion::from_axis_angle(&Vector3::z_axis(), std::f32::consts::FRAC_PI_2));
+
+        // a short link standing at a point, reaching up to the height of the plank
+        let probe = |collision_handler: &CollisionHandler, x: f32, y: f32| {
+            let mats: Vec<Matrix4<f32>> = generate_matrices(Matrix4::new_translation(&Vector3::new(x, y, 2.8)), &[0.0], &[*Vector3::z_axis()], &[0.4]);
+            collision_handler.is_arm_colliding_world_naive(&generate_forward_matrices(&mats))
+        };
+        let arm: Vec<Vector3<f32>> = vec![Vector3::new(0.05, 0.05, 0.2)];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&arm, &[plank], &[Vector3::new(0.0, 0.0, 3.0)]);
+        assert!(probe(&collision_handler, 0.8, 0.0));
+        assert!(!probe(&collision_handler, 0.0, 0.8));
+
+        for pose in [turned.into(), turned.to_homogeneous().into()] {
+            let collision_handler: CollisionHandler = CollisionHandler::with_poses(&[Shape::Box { half_extents: arm[0] }], &[Shape::Box { half_extents: plank }], &[pose]);
+            assert!(!probe(&collision_handler, 0.8, 0.0));
+            assert!(probe(&collision_handler, 0.0, 0.8));
+        }
+
+        // all three forms in JSON
+        let poses: Vec<ColliderPose> = serde_json::from_str(r#"[
+            [0.0, 0.0, 3.0],
+            {"rotation": [0.0, 0.0, 0.70710677, 0.70710677], "translation": [0.0, 0.0, 3.0]},
+            [0.0, 1.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 3.0, 1.0]
+        ]"#).unwrap();
+
+        assert_eq!(poses[0], ColliderPose::Translation(Vector3::new(0.0, 0.0, 3.0)));
+        for pose in poses[1..].iter() {
+            assert!(pose.to_isometry().unwrap().to_homogeneous().relative_eq(&turned.to_homogeneous(), 1e-6, 1e-6));
+        }
+
+        // a matrix that scales is not a pose
+        assert_eq!(ColliderPose::Matrix(Matrix4::new_scaling(2.0)).to_isometry(), None);
+
+    }
+
+    #[test]
+    fn test_rotated_obstacle_pose() {
+
+        // an upright post beside a two link arm, tipped over about x it lies across the second link
+        let radii: Vec<f32> = vec![1.0, 2.0];
+        let arm: Vec<Vector3<f32>> = radii.iter().map(|length| Vector3::new(0.1, 0.1, *length / 2.0)).collect();
+        let post: Vector3<f32> = Vector3::new(0.05, 0.05, 1.6);
+        let position: Vector3<f32> = Vector3::new(0.0, 1.5, 2.0);
+
+        let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(IDENTITY, &[0.0, 0.0], &[*Vector3::z_axis(), *Vector3::y_axis()], &radii));
+
+        let upright: CollisionHandler = CollisionHandler::new(&arm, &[post], &[position]);
+        assert_eq!(upright.find_arm_collisions_world(&forward_mats), vec![false, false]);
+
+        let arm_shapes: Vec<Shape> = arm.iter().map(|half_extents| Shape::Box { half_extents: *half_extents }).collect();
+        let tipped: Isometry3<f32> = Isometry3::from_parts(Translation3::from(position), UnitQuaternion::from_axis_angle(&Vector3::x_axis(), std::f32::consts::FRAC_PI_2));
+
+        // a translation alone matches new, the rotation is what brings the post onto the arm
+        let translated: CollisionHandler = CollisionHandler::with_poses(&arm_shapes, &[Shape::Box { half_extents: post }], &[ColliderPose::from(position)]);
+        assert_eq!(translated.find_arm_collisions_world(&forward_mats), vec![false, false]);
+
+        for pose in [tipped.into(), tipped.to_homogeneous().into()] {
+            let collision_handler: CollisionHandler = CollisionHandler::with_poses(&arm_shapes, &[Shape::Box { half_extents: post }], &[pose]);
+            assert_eq!(collision_handler.find_arm_collisions_world(&forward_mats), vec![false, true]);
+        }
+
+    }
+
+    #[test]
+    fn test_off_axis_link_colliders() {
+
+        // a housing beside a link standing up z, long along x unless turned a quarter turn about z
+        let housing: Vector3<f32> = Vector3::new(0.3, 0.05, 0.05);
+        let obstacle: Vector3<f32> = Vector3::new(0.05, 0.05, 0.05);
+
+        let mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(IDENTITY, &[0.0], &[*Vector3::z_axis()], &[1.0]));
+
+        // by default the housing sits on the link axis
+        let mut collision_handler: CollisionHandler = CollisionHandler::new(&[housing], &[obstacle], &[Vector3::new(0.0, 0.75, 0.5)]);
+        assert!(!collision_handler.is_arm_colliding_world_naive(&mats));
+
+        let beside: ColliderPose = Vector3::new(0.0, 0.5, 0.5).into();
+        collision_handler.set_arm_offsets(&[beside.to_isometry().unwrap()]);
+        assert!(!collision_handler.is_arm_colliding_world_naive(&mats));
+
+        let turned: ColliderPose = Isometry3::from_parts(Translation3::new(0.0, 0.5, 0.5), UnitQuaternion::from_axis_angle(&Vector3::z_axis(), std::f32::consts::FRAC_PI_2)).into();
+        collision_handler.set_arm_offsets(&[turned.to_isometry().unwrap()]);
+        assert!(collision_handler.is_arm_colliding_world_naive(&mats));
+        assert_eq!(collision_handler.find_arm_collisions_world(&mats), vec![true]);
+
+    }
+
+    #[test]
+    fn test_closest_approach() {
+
+        // a link standing up z, its box spanning -0.25 to 0.75, beside a block
+        let arm: Vec<Vector3<f32>> = vec![Vector3::new(0.1, 0.1, 0.5)];
+        let obstacles: Vec<Vector3<f32>> = vec![Vector3::new(0.2, 0.2, 0.2), Vector3::new(0.2, 0.2, 0.2)];
+        let mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(IDENTITY, &[0.0], &[*Vector3::z_axis()], &[1.0]));
+
+        assert_eq!(CollisionHandler::new(&arm, &[], &[]).closest_world(&mats), None);
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&arm, &obstacles, &[Vector3::new(0.0, 2.0, 0.0), Vector3::new(0.5, 0.0, 0.3)]);
+        let proximity: Proximity = collision_handler.closest_world(&mats).unwrap();
+
+        assert_eq!((proximity.link, proximity.other), (0, 1));
+        assert!((proximity.distance - 0.2).abs() < 1e-4);
+        assert!((proximity.link_point.x - 0.1).abs() < 1e-4 && (proximity.other_point.x - 0.3).abs() < 1e-4);
+        assert_eq!(collision_handler.world_clearance(&mats), proximity.distance);
+
+        // overlapping by 0.05 gives a negative distance
+        let collision_handler: CollisionHandler = CollisionHandler::new(&arm, &obstacles[..1], &[Vector3::new(0.25, 0.0, 0.3)]);
+        let proximity: Proximity = collision_handler.closest_world(&mats).unwrap();
+        assert!((proximity.distance + 0.05).abs() < 1e-4);
+        assert!((proximity.link_point.x - 0.1).abs() < 1e-4 && (proximity.other_point.x - 0.05).abs() < 1e-4);
+
+        // a folded arm comes closest to itself between the first and third links
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::y_axis(); 3];
+        let folded: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(IDENTITY, &[0.0, 2.8, 2.8], &axes, &[1.0, 1.0, 1.0]));
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[Vector3::new(0.05, 0.05, 0.5); 3], &[], &[]);
+        let proximity: Proximity = collision_handler.closest_self(&folded).unwrap();
+        assert_eq!((proximity.link, proximity.other), (0, 2));
+        assert_eq!(proximity.distance <= 0.0, collision_handler.is_arm_colliding_self_naive(&folded));
+
+    }
+
+    #[test]
+    fn test_safety_margin() {
+
+        // the box of a link standing up z ends 0.015 short of a block
+        let arm: Vec<Vector3<f32>> = vec![Vector3::new(0.1, 0.1, 0.5)];
+        let mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(IDENTITY, &[0.0], &[*Vector3::z_axis()], &[1.0]));
+
+        let mut collision_handler: CollisionHandler = CollisionHandler::new(&arm, &[Vector3::new(0.2, 0.2, 0.2)], &[Vector3::new(0.315, 0.0, 0.3)]);
+        assert!(!collision_handler.is_arm_colliding_world(0, &mats));
+        assert!(!collision_handler.is_arm_colliding_world_naive(&mats));
+        assert_eq!(collision_handler.find_arm_collisions_world(&mats), vec![false]);
+
+        collision_handler.set_safety_margin(0.02);
+        assert!(collision_handler.is_arm_colliding_world(0, &mats));
+        assert!(collision_handler.is_arm_colliding_world_naive(&mats));
+        assert_eq!(collision_handler.find_arm_collisions_world(&mats), vec![true]);
+
+        // the same between two links of a folded arm
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::y_axis(); 3];
+        let mut collision_handler: CollisionHandler = CollisionHandler::new(&[Vector3::new(0.05, 0.05, 0.5); 3], &[], &[]);
+        let folded: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(IDENTITY, &[0.0, 2.0, 2.0], &axes, &[1.0, 1.0, 1.0]));
+        let distance: f32 = collision_handler.closest_self(&folded).unwrap().distance;
+        assert!(distance > 0.0);
+
+        assert!(!collision_handler.is_arm_colliding_self_naive(&folded));
+        collision_handler.set_safety_margin(distance + 0.01);
+        assert!(collision_handler.is_arm_colliding_self_naive(&folded));
+        assert!(collision_handler.is_arm_colliding_self(2, &folded));
+        assert_eq!(collision_handler.find_arm_collisions_self(&folded), vec![true, false, true]);
+
+    }
+
+}
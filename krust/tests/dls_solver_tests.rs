@@ -0,0 +1,190 @@
+#[macro_use]
+extern crate approx; // For the macro relative_eq!
+extern crate nalgebra as na;
+
+#[cfg(test)]
+mod solver_tests {
+
+    use krust::collision_handler::CollisionHandler;
+    use na::{Vector3, Matrix4, Isometry3};
+    use krust::matrices::{IDENTITY, generate_matrices, generate_matrices_with_offsets, generate_forward_matrices, radius_offsets, offset_length, dh_chain, DHParameters, DHConvention};
+    use std::f32::consts::PI;
+    use krust::solver::{IKSolver, SolveResult, TerminationReason, Tolerance};
+    use krust::solver_dls::IKSolverDLS;
+
+    fn six_axis_arm() -> (Vec<Vector3<f32>>, Vec<f32>) {
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis(), *Vector3::z_axis(), *Vector3::y_axis(), *Vector3::z_axis()];
+        let radii: Vec<f32> = vec![1.0, 3.0, 3.0, 1.0, 1.0, 0.5];
+        (axes, radii)
+    }
+
+    #[test]
+    fn test_new_solver_success() {
+
+        let angles: Vec<f32> = vec![0.0,0.0,0.0];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::y_axis(), *Vector3::z_axis()];
+        let radii: Vec<f32> = vec![5.0,3.0,1.0];
+
+        let min_angles: Vec<f32> = vec![-100.0, -100.0, -100.0];
+        let max_angles: Vec<f32> = vec![100.0, 100.0, 100.0];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let ik_solver: IKSolverDLS = IKSolverDLS::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler);
+
+        assert_eq!(angles, ik_solver.chain.thetas);
+        assert_eq!(axes, ik_solver.chain.axes);
+        assert_eq!(radii, ik_solver.chain.radii);
+        assert_eq!(9.0, ik_solver.chain.arm_length);
+
+    }
+
+    #[test]
+    fn test_solver_solve_pose() {
+
+        let (axes, radii) = six_axis_arm();
+
+        // Target pose generated from a known configuration so it is reachable
+        let goal: Vec<f32> = vec![0.4, -0.5, 1.1, 0.3, 0.8, -0.2];
+        let goal_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(IDENTITY, &goal, &axes, &radii));
+        let target: Matrix4<f32> = goal_mats[goal_mats.len() - 1];
+
+        let angles: Vec<f32> = vec![0.1, -0.2, 0.6, 0.0, 0.4, 0.0];
+        let min_angles: Vec<f32> = vec![-3.0; 6];
+        let max_angles: Vec<f32> = vec![3.0; 6];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let mut ik_solver: IKSolverDLS = IKSolverDLS::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler);
+
+        let result: SolveResult = ik_solver.solve(target, 0.00001);
+
+        assert!(result.success);
+        assert_eq!(result.reason, TerminationReason::Converged);
+        assert!(result.position_error < 0.01);
+        assert!(result.orientation_error < 0.01);
+
+        assert!(ik_solver.loss < 0.00001);
+        assert!(ik_solver.iterations < 20);
+        assert!(relative_eq!(ik_solver.chain.end_effector, target, epsilon = 0.01));
+
+    }
+
+    #[test]
+    fn test_solver_solve_within() {
+
+        let (axes, radii) = six_axis_arm();
+        let goal: Vec<f32> = vec![0.4, -0.5, 1.1, 0.3, 0.8, -0.2];
+        let tolerance: Tolerance = Tolerance::from_millimetres_degrees(1.0, 0.5);
+
+        assert!(relative_eq!(tolerance.orientation, 0.5 * PI / 180.0));
+
+        // the same tolerance means the same accuracy on an arm in metres and one ten times longer
+        for scale in [0.1, 1.0] {
+
+            let radii: Vec<f32> = radii.iter().map(|radius| radius * scale).collect();
+            let goal_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(IDENTITY, &goal, &axes, &radii));
+            let target: Matrix4<f32> = goal_mats[goal_mats.len() - 1];
+
+            let mut ik_solver: IKSolverDLS = IKSolverDLS::new(IDENTITY, &[0.1, -0.2, 0.6, 0.0, 0.4, 0.0], &axes, &radii, &[-3.0; 6], &[3.0; 6], CollisionHandler::new(&[], &[], &[]));
+            let result: SolveResult = ik_solver.solve_within(target.into(), tolerance);
+
+            assert_eq!(result.reason, TerminationReason::Converged);
+            assert!(tolerance.contains((result.position_error, result.orientation_error)));
+        }
+
+    }
+
+    #[test]
+    fn test_solver_respects_limits() {
+
+        let (axes, radii) = six_axis_arm();
+
+        let goal: Vec<f32> = vec![0.4, -0.5, 1.1, 0.3, 0.8, -0.2];
+        let goal_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(IDENTITY, &goal, &axes, &radii));
+        let target: Matrix4<f32> = goal_mats[goal_mats.len() - 1];
+
+        // The elbow cannot bend as far as the goal configuration needs
+        let angles: Vec<f32> = vec![0.0; 6];
+        let min_angles: Vec<f32> = vec![-3.0, -3.0, -0.5, -3.0, -3.0, -3.0];
+        let max_angles: Vec<f32> = vec![3.0, 3.0, 0.5, 3.0, 3.0, 3.0];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let mut ik_solver: IKSolverDLS = IKSolverDLS::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler);
+
+        let result: SolveResult = ik_solver.solve(target, 0.00001);
+        assert!(!result.success);
+
+        for (i, theta) in ik_solver.chain.thetas.iter().enumerate() {
+            assert!(*theta >= min_angles[i] && *theta <= max_angles[i]);
+        }
+
+    }
+
+    #[test]
+    fn test_solver_link_offsets() {
+
+        let (axes, radii) = six_axis_arm();
+
+        // offset shoulder and elbow, like most industrial arms
+        let mut offsets: Vec<Isometry3<f32>> = radius_offsets(&radii);
+        offsets[1] = Isometry3::new(Vector3::new(0.0, 0.4, 0.2), Vector3::new(0.0, 0.0, 0.3));
+        offsets[2] = Isometry3::translation(0.3, -0.2, 3.0);
+
+        let goal: Vec<f32> = vec![0.4, -0.5, 1.1, 0.3, 0.8, -0.2];
+        let goal_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices_with_offsets(IDENTITY, &goal, &axes, &offsets));
+        let target: Matrix4<f32> = goal_mats[goal_mats.len() - 1];
+
+        let angles: Vec<f32> = vec![0.1, -0.2, 0.6, 0.0, 0.4, 0.0];
+        let min_angles: Vec<f32> = vec![-3.0; 6];
+        let max_angles: Vec<f32> = vec![3.0; 6];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let mut ik_solver: IKSolverDLS = IKSolverDLS::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler).with_offsets(&offsets);
+
+        let result: SolveResult = ik_solver.solve(target, 0.0000001);
+
+        assert!(result.success);
+        assert!(result.position_error < 0.01);
+        assert!(relative_eq!(ik_solver.chain.radii[2], (0.09_f32 + 0.04 + 9.0).sqrt()));
+
+    }
+
+    #[test]
+    fn test_solver_dh_ur5() {
+
+        let parameters: Vec<DHParameters> = vec![
+            DHParameters { a: 0.0, alpha: PI / 2.0, d: 0.089159, theta_offset: 0.0 },
+            DHParameters { a: -0.425, alpha: 0.0, d: 0.0, theta_offset: 0.0 },
+            DHParameters { a: -0.39225, alpha: 0.0, d: 0.0, theta_offset: 0.0 },
+            DHParameters { a: 0.0, alpha: PI / 2.0, d: 0.10915, theta_offset: 0.0 },
+            DHParameters { a: 0.0, alpha: -PI / 2.0, d: 0.09465, theta_offset: 0.0 },
+            DHParameters { a: 0.0, alpha: 0.0, d: 0.0823, theta_offset: 0.0 },
+        ];
+        let (axes, offsets, tool) = dh_chain(&parameters, DHConvention::Classic);
+        let radii: Vec<f32> = offsets.iter().map(offset_length).collect();
+
+        // the trailing classic DH transform is the tool after the last joint
+        let goal: Vec<f32> = vec![0.3, -1.2, 1.5, -0.4, 1.1, 0.7];
+        let goal_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices_with_offsets(IDENTITY, &goal, &axes, &offsets));
+        let target: Matrix4<f32> = goal_mats[goal_mats.len() - 1] * tool.to_homogeneous();
+
+        let angles: Vec<f32> = vec![0.1, -1.0, 1.2, -0.2, 1.0, 0.5];
+        let min_angles: Vec<f32> = vec![-PI; 6];
+        let max_angles: Vec<f32> = vec![PI; 6];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let mut ik_solver: IKSolverDLS = IKSolverDLS::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler).with_offsets(&offsets).with_tool(&tool);
+
+        let result: SolveResult = ik_solver.solve(target, 0.0000001);
+
+        assert!(result.success);
+        assert!(result.position_error < 0.001);
+        assert_eq!(result.thetas.len(), 6);
+
+    }
+
+}
@@ -0,0 +1,134 @@
+#[macro_use]
+extern crate approx; // For the macro relative_eq!
+extern crate nalgebra as na;
+
+#[cfg(test)]
+mod solver_tests {
+
+    use krust::collision_handler::CollisionHandler;
+    use na::{Vector3, Matrix4, Isometry3};
+    use krust::matrices::IDENTITY;
+    use krust::solver::{IKSolver, SolveResult, TerminationReason, Tolerance};
+    use krust::target::Target;
+    use krust::solver_fabrik::IKSolverFABRIK;
+
+    #[test]
+    fn test_new_solver_success() {
+
+        let angles: Vec<f32> = vec![0.0,0.0,0.0];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::y_axis(), *Vector3::z_axis()];
+        let radii: Vec<f32> = vec![5.0,3.0,1.0];
+
+        let min_angles: Vec<f32> = vec![-100.0, -100.0, -100.0];
+        let max_angles: Vec<f32> = vec![100.0, 100.0, 100.0];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let ik_solver: IKSolverFABRIK = IKSolverFABRIK::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler);
+
+        assert_eq!(angles, ik_solver.chain.thetas);
+        assert_eq!(axes, ik_solver.chain.axes);
+        assert_eq!(radii, ik_solver.chain.radii);
+        assert_eq!(9.0, ik_solver.chain.arm_length);
+
+    }
+
+    #[test]
+    fn test_solver_solve_position() {
+
+        let angles: Vec<f32> = vec![0.0, 0.2, 0.2, 0.0, 0.2, 0.0];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis(), *Vector3::z_axis(), *Vector3::y_axis(), *Vector3::z_axis()];
+        let radii: Vec<f32> = vec![1.0, 3.0, 3.0, 1.0, 1.0, 0.5];
+
+        let min_angles: Vec<f32> = vec![-3.0; 6];
+        let max_angles: Vec<f32> = vec![3.0; 6];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let mut ik_solver: IKSolverFABRIK = IKSolverFABRIK::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler);
+
+        // only the position of the target matters
+        let target: Matrix4<f32> = Matrix4::new_translation(&Vector3::new(2.0, 3.0, 4.0)) * Matrix4::new_rotation(Vector3::new(0.0, 1.0, 0.0));
+        ik_solver.solve(target, 0.00001);
+
+        assert!(ik_solver.loss < 0.00001);
+        assert!(relative_eq!(ik_solver.chain.end_effector.fixed_slice::<3, 1>(0, 3), target.fixed_slice::<3, 1>(0, 3), epsilon = 0.05));
+
+    }
+
+    #[test]
+    fn test_solver_solve_tool() {
+
+        let angles: Vec<f32> = vec![0.0, 0.2, 0.2];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis()];
+        let radii: Vec<f32> = vec![1.0, 2.0, 2.0];
+
+        let min_angles: Vec<f32> = vec![-3.0; 3];
+        let max_angles: Vec<f32> = vec![3.0; 3];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        // the last joint swings the tool tip, so it has a position to reach for too
+        let tool: Isometry3<f32> = Isometry3::translation(1.0, 0.0, 0.0);
+        let mut ik_solver: IKSolverFABRIK = IKSolverFABRIK::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler).with_tool(&tool);
+        assert_eq!(ik_solver.chain.arm_length, 6.0);
+
+        let target: Matrix4<f32> = ik_solver.chain.tool_pose(&ik_solver.chain.forward_matrices(&[0.5, 0.8, -0.6]));
+        let result: SolveResult = ik_solver.solve(target, 0.0000001);
+
+        assert!(result.success);
+        assert!(result.position_error < 0.01);
+        assert!(relative_eq!(ik_solver.end_effector(), ik_solver.chain.tool_pose(&ik_solver.chain.forward_mats)));
+
+    }
+
+    #[test]
+    fn test_solver_respects_limits() {
+
+        let angles: Vec<f32> = vec![0.0,0.0,0.0];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::x_axis(), *Vector3::x_axis()];
+        let radii: Vec<f32> = vec![2.0,2.0,2.0];
+
+        let min_angles: Vec<f32> = vec![-0.3, -0.3, -0.3];
+        let max_angles: Vec<f32> = vec![0.3, 0.3, 0.3];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let mut ik_solver: IKSolverFABRIK = IKSolverFABRIK::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler);
+
+        let target: Matrix4<f32> = Matrix4::new_translation(&Vector3::new(0.0, 4.0, 2.0));
+        ik_solver.solve(target, 0.00001);
+
+        for theta in ik_solver.chain.thetas.iter() {
+            assert!((-0.3..=0.3).contains(theta));
+        }
+
+    }
+
+    #[test]
+    fn test_solver_orientation_tolerance() {
+
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::x_axis(), *Vector3::x_axis()];
+        let radii: Vec<f32> = vec![2.0,2.0,2.0];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let mut ik_solver: IKSolverFABRIK = IKSolverFABRIK::new(IDENTITY, &[0.0; 3], &axes, &radii, &[-3.0; 3], &[3.0; 3], collision_handler);
+
+        // FABRIK never turns the hand towards an orientation, so it gives up on one straight away
+        let target: Matrix4<f32> = Matrix4::new_translation(&Vector3::new(0.0, 3.0, 3.0));
+        let result: SolveResult = ik_solver.solve_within(Target::from(target), Tolerance::new(0.01, 0.01));
+
+        assert_eq!(result.reason, TerminationReason::OrientationUnsupported);
+        assert_eq!(result.iterations, 0);
+
+        // with the orientation left free, or a position target, only the position has to be met
+        let result: SolveResult = ik_solver.solve_within(Target::from(target), Tolerance::new(0.01, f32::INFINITY));
+        assert!(result.success);
+
+        let result: SolveResult = ik_solver.solve_within(Target::Position { position: Vector3::new(0.0, 3.0, 3.0) }, Tolerance::new(0.01, 0.01));
+        assert!(result.success);
+
+    }
+
+}
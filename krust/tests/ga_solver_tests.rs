@@ -0,0 +1,150 @@
+extern crate nalgebra as na;
+
+#[cfg(test)]
+mod solver_tests {
+
+    use krust::collision_handler::CollisionHandler;
+    use na::{Vector3, Matrix4};
+    use krust::matrices::{IDENTITY, generate_matrices, generate_forward_matrices};
+    use krust::solver::IKSolver;
+    use krust::solver_ga::{IKSolverGA, GAConfig};
+
+    const TARGET: Matrix4<f32> = Matrix4::new(  
+        1.0,0.0,0.0,0.0,
+        0.0,1.0,0.0,3.0,
+        0.0,0.0,1.0,3.0,
+        0.0,0.0,0.0,1.0  
+    );
+
+    fn seeded_config() -> GAConfig {
+        GAConfig { seed: Some(42), ..GAConfig::default() }
+    }
+
+    #[test]
+    fn test_new_solver_success() {
+
+        let angles: Vec<f32> = vec![0.0,0.0,0.0];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::y_axis(), *Vector3::z_axis()];
+        let radii: Vec<f32> = vec![5.0,3.0,1.0];
+
+        let min_angles: Vec<f32> = vec![-1.0, -1.0, -1.0];
+        let max_angles: Vec<f32> = vec![1.0, 1.0, 1.0];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let ik_solver: IKSolverGA = IKSolverGA::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler, seeded_config());
+
+        assert_eq!(angles, ik_solver.chain.thetas);
+        assert_eq!(9.0, ik_solver.chain.arm_length);
+        assert_eq!(100, ik_solver.population.len());
+
+        // the starting configuration seeds the population and every individual respects the limits
+        assert_eq!(angles, ik_solver.population[0]);
+        for individual in ik_solver.population.iter() {
+            assert!(individual.iter().all(|theta| (-1.0..=1.0).contains(theta)));
+        }
+
+    }
+
+    #[test]
+    #[should_panic(expected="Invalid population! population size: 10, elitism: 20")]
+    fn test_new_solver_fail() {
+
+        let angles: Vec<f32> = vec![0.0,0.0,0.0];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::y_axis(), *Vector3::z_axis()];
+        let radii: Vec<f32> = vec![5.0,3.0,1.0];
+
+        let min_angles: Vec<f32> = vec![-1.0, -1.0, -1.0];
+        let max_angles: Vec<f32> = vec![1.0, 1.0, 1.0];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let config: GAConfig = GAConfig { population_size: 10, elitism: 20, ..seeded_config() };
+        IKSolverGA::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler, config);
+
+    }
+
+    #[test]
+    fn test_config_validate() {
+
+        assert!(seeded_config().validate().is_ok());
+        assert!(GAConfig { crossover_rate: 7.0, ..seeded_config() }.validate().is_err());
+        assert!(GAConfig { mutation_rate: f32::NAN, ..seeded_config() }.validate().is_err());
+        assert!(GAConfig { mutation_scale: -1.0, ..seeded_config() }.validate().is_err());
+
+    }
+
+    #[test]
+    fn test_solver_solve() {
+
+        let angles: Vec<f32> = vec![0.0,0.0,0.0];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::x_axis(), *Vector3::x_axis()];
+        let radii: Vec<f32> = vec![2.0,2.0,2.0];
+
+        let min_angles: Vec<f32> = vec![-100.0, -100.0, -100.0];
+        let max_angles: Vec<f32> = vec![100.0, 100.0, 100.0];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let mut ik_solver: IKSolverGA = IKSolverGA::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler, seeded_config());
+
+        ik_solver.solve(TARGET, 0.001);
+
+        assert!(ik_solver.loss < 0.001);
+
+    }
+
+    #[test]
+    fn test_solver_seeded() {
+
+        let angles: Vec<f32> = vec![0.0,0.0,0.0];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::x_axis(), *Vector3::x_axis()];
+        let radii: Vec<f32> = vec![2.0,2.0,2.0];
+
+        let min_angles: Vec<f32> = vec![-100.0, -100.0, -100.0];
+        let max_angles: Vec<f32> = vec![100.0, 100.0, 100.0];
+
+        let mut solver_a: IKSolverGA = IKSolverGA::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, CollisionHandler::new(&[], &[], &[]), seeded_config());
+        let mut solver_b: IKSolverGA = IKSolverGA::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, CollisionHandler::new(&[], &[], &[]), seeded_config());
+
+        solver_a.set_target(TARGET.into());
+        solver_b.set_target(TARGET.into());
+
+        for _ in 0..5 {
+            solver_a.update();
+            solver_b.update();
+        }
+
+        assert_eq!(solver_a.chain.thetas, solver_b.chain.thetas);
+
+    }
+
+    #[test]
+    fn test_solver_avoids_obstacles() {
+
+        let angles: Vec<f32> = vec![0.0,0.0,0.0];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::x_axis(), *Vector3::x_axis()];
+        let radii: Vec<f32> = vec![2.0,2.0,2.0];
+
+        let min_angles: Vec<f32> = vec![-100.0, -100.0, -100.0];
+        let max_angles: Vec<f32> = vec![100.0, 100.0, 100.0];
+
+        let arm: Vec<Vector3<f32>> = radii.iter().map(|length| Vector3::new(0.2, 0.2, *length / 2.0)).collect();
+
+        // wall between the straight arm and the target
+        let obstacles: Vec<Vector3<f32>> = vec![Vector3::new(2.0, 0.1, 1.0)];
+        let offsets: Vec<Vector3<f32>> = vec![Vector3::new(0.0, 1.5, 4.5)];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&arm, &obstacles, &offsets);
+
+        let mut ik_solver: IKSolverGA = IKSolverGA::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler.clone(), seeded_config());
+
+        ik_solver.solve(TARGET, 0.001);
+
+        let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(IDENTITY, &ik_solver.chain.thetas, &axes, &radii));
+        assert!(!collision_handler.is_arm_colliding_world_naive(&forward_mats));
+        assert!(!collision_handler.is_arm_colliding_self_naive(&forward_mats));
+
+    }
+
+}
@@ -8,8 +8,10 @@ mod solver_tests {
     use krust::collision_handler::CollisionHandler;
     use na::{Vector3, Matrix4};
     use std::time::Instant;
-    use krust::matrices::{IDENTITY};
-    use krust::solver_gd::IKSolverGD;
+    use std::f32::consts::PI;
+    use krust::matrices::{IDENTITY, JointType, PoseWeights, generate_matrices, generate_forward_matrices, angle_difference};
+    use krust::solver::{IKSolver, SolveResult, TerminationReason};
+    use krust::solver_gd::{IKSolverGD, GDConfig, LearnRateSchedule, Optimizer, LimitStrategy};
 
     const TARGET: Matrix4<f32> = Matrix4::new(  
         1.0,0.0,0.0,0.0,
@@ -29,14 +31,14 @@ mod solver_tests {
         let min_angles: Vec<f32> = vec![-100.0, -100.0, -100.0];
         let max_angles: Vec<f32> = vec![100.0, 100.0, 100.0];
 
-        let collision_handler: CollisionHandler = CollisionHandler::new(&vec![], &vec![], &vec![]);
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
 
         let ik_solver: IKSolverGD = IKSolverGD::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler);
 
-        assert_eq!(angles, ik_solver.thetas);
-        assert_eq!(axes, ik_solver.axes);
-        assert_eq!(radii, ik_solver.radii);
-        assert_eq!(9.0, ik_solver.arm_length);
+        assert_eq!(angles, ik_solver.chain.thetas);
+        assert_eq!(axes, ik_solver.chain.axes);
+        assert_eq!(radii, ik_solver.chain.radii);
+        assert_eq!(9.0, ik_solver.chain.arm_length);
 
     }
 
@@ -52,12 +54,26 @@ mod solver_tests {
         let min_angles: Vec<f32> = vec![-100.0, -100.0, -100.0];
         let max_angles: Vec<f32> = vec![100.0, 100.0, 100.0];
 
-        let collision_handler: CollisionHandler = CollisionHandler::new(&vec![], &vec![], &vec![]);
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
 
         IKSolverGD::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler);
 
     }
 
+    #[test]
+    #[should_panic(expected="Invalid arm colliders! 4 colliders for 2 joints")]
+    fn test_too_many_arm_colliders() {
+
+        let angles: Vec<f32> = vec![0.0, 0.0];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::x_axis()];
+        let radii: Vec<f32> = vec![1.0, 1.0];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[Vector3::new(0.1, 0.1, 0.5); 4], &[], &[]);
+
+        IKSolverGD::new(IDENTITY, &angles, &axes, &radii, &[-1.0; 2], &[1.0; 2], collision_handler);
+
+    }
+
     #[test]
     fn test_solver_run() {
 
@@ -68,13 +84,13 @@ mod solver_tests {
         let min_angles: Vec<f32> = vec![-100.0, -100.0, -100.0];
         let max_angles: Vec<f32> = vec![100.0, 100.0, 100.0];
 
-        let collision_handler: CollisionHandler = CollisionHandler::new(&vec![], &vec![], &vec![]);
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
 
         let mut ik_solver: IKSolverGD = IKSolverGD::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler);
 
-        ik_solver.target = Some(TARGET);
+        ik_solver.target = Some(TARGET.into());
 
-        for i in 0..10 {
+        for _ in 0..10 {
             ik_solver.update();
         }
 
@@ -90,15 +106,354 @@ mod solver_tests {
         let min_angles: Vec<f32> = vec![-100.0, -100.0, -100.0];
         let max_angles: Vec<f32> = vec![100.0, 100.0, 100.0];
 
-        let collision_handler: CollisionHandler = CollisionHandler::new(&vec![], &vec![], &vec![]);
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
 
         let mut ik_solver: IKSolverGD = IKSolverGD::new(IDENTITY, &angles, &axes, &radii, &min_angles, &max_angles, collision_handler);
 
         let start = Instant::now();
-        ik_solver.solve(TARGET, 0.000000001);
+        let result: SolveResult = ik_solver.solve(TARGET, 0.000000001);
         let duration = start.elapsed();
         println!("Elapsed time: {:?}", duration);
-        println!("Thetas: {:?}", ik_solver.thetas);
+        println!("Result: {:?}", result);
+
+        assert_eq!(result.thetas, ik_solver.chain.thetas);
+        assert_eq!(result.iterations, ik_solver.iterations);
+    }
+
+
+    /// The 3 link arm bending about x that the solver tests start from, every joint limited to +-limit
+    fn three_link_arm(thetas: &[f32], limit: f32, config: GDConfig) -> IKSolverGD {
+
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::x_axis(), *Vector3::x_axis()];
+        let radii: Vec<f32> = vec![2.0,2.0,2.0];
+
+        IKSolverGD::with_config(IDENTITY, thetas, &axes, &radii, &[-limit; 3], &[limit; 3], CollisionHandler::new(&[], &[], &[]), config)
+    }
+
+    #[test]
+    fn test_solver_trait_object() {
+
+        let mut ik_solver: Box<dyn IKSolver> = Box::new(three_link_arm(&[0.0; 3], 100.0, GDConfig::default()));
+
+        ik_solver.set_target(TARGET.into());
+        ik_solver.update();
+
+        // The first update evaluates the starting pose, a straight arm along z
+        let start: Matrix4<f32> = Matrix4::new_translation(&Vector3::new(0.0, 0.0, 6.0));
+        assert!(relative_eq!(ik_solver.end_effector(), start));
+
+        let start_loss: f32 = ik_solver.loss();
+        for _ in 0..10 {
+            ik_solver.update();
+        }
+        assert!(ik_solver.loss() < start_loss);
+        assert_ne!(ik_solver.thetas(), &[0.0; 3]);
+
+        ik_solver.reset_params();
+        assert_eq!(ik_solver.loss(), 100.0);
+
+    }
+
+    #[test]
+    fn test_solver_max_iterations() {
+
+        let mut ik_solver: IKSolverGD = three_link_arm(&[0.0; 3], 100.0, GDConfig::default());
+        ik_solver.limits.max_iterations = 3;
+
+        let result: SolveResult = ik_solver.solve(TARGET, 0.000000001);
+
+        assert!(!result.success);
+        assert_eq!(result.reason, TerminationReason::MaxIterations);
+        assert_eq!(result.iterations, 3);
+        assert!(result.position_error > 0.0);
+    }
+
+    #[test]
+    fn test_solver_time_budget() {
+
+        let mut ik_solver: IKSolverGD = three_link_arm(&[0.0; 3], 100.0, GDConfig::default());
+        ik_solver.limits_mut().time_budget = Some(0.0);
+
+        let result: SolveResult = ik_solver.solve(TARGET, 0.000000001);
+
+        assert_eq!(result.reason, TerminationReason::TimeBudget);
+        assert_eq!(result.iterations, 0);
+        assert_eq!(result.thetas, vec![0.0; 3]);
+    }
+
+    #[test]
+    fn test_config_builder() {
+
+        let config: GDConfig = GDConfig::builder().learn_rate(0.3).momentum_retain(0.5).max_steps(40).build().unwrap();
+
+        assert_eq!(config.learn_rate, 0.3);
+        assert_eq!(config.momentum_retain, 0.5);
+        assert_eq!(config.max_steps, 40);
+        assert_eq!(config.schedule, GDConfig::default().schedule);
+
+        assert!(GDConfig::builder().learn_rate(-1.0).build().is_err());
+        assert!(GDConfig::builder().momentum_retain(1.0).build().is_err());
+        assert!(GDConfig::builder().max_steps(0).build().is_err());
+        assert!(GDConfig::builder().rot_correction(f32::NAN).build().is_err());
+        assert!(GDConfig::builder().weights(PoseWeights::new(0.0, 0.0)).build().is_err());
+
+    }
+
+    #[test]
+    fn test_config_deserialize() {
+
+        // missing fields fall back to the defaults
+        let config: GDConfig = serde_json::from_str(r#"{"learn_rate": 0.4, "max_steps": 25}"#).unwrap();
+
+        assert_eq!(config, GDConfig::builder().learn_rate(0.4).max_steps(25).build().unwrap());
+
+    }
+
+    #[test]
+    fn test_solver_with_config() {
+
+        let config: GDConfig = GDConfig::builder().max_steps(200).build().unwrap();
+        let mut ik_solver: IKSolverGD = three_link_arm(&[0.0; 3], 100.0, config);
+
+        assert_eq!(ik_solver.limits.max_iterations, 200);
+
+        let result: SolveResult = ik_solver.solve(TARGET, 0.0001);
+
+        assert!(result.success);
+        assert!(result.iterations > 10);
+
+    }
+
+    #[test]
+    #[should_panic(expected="Invalid config! learn rate must be positive")]
+    fn test_solver_invalid_config() {
+
+        three_link_arm(&[0.0; 3], 100.0, GDConfig { learn_rate: 0.0, ..GDConfig::default() });
+
+    }
+
+    #[test]
+    fn test_learn_rate_schedules() {
+
+        assert_eq!(LearnRateSchedule::Constant.rate(0.5, 100), 0.5);
+        assert!(relative_eq!(LearnRateSchedule::InverseTime { decay: 0.1 }.rate(0.5, 10), 0.25));
+        assert!(relative_eq!(LearnRateSchedule::Exponential { gamma: 0.5 }.rate(0.5, 3), 0.0625));
+        assert!(relative_eq!(LearnRateSchedule::Step { step_size: 10, gamma: 0.5 }.rate(0.5, 19), 0.25));
+        assert!(relative_eq!(LearnRateSchedule::Step { step_size: 10, gamma: 0.5 }.rate(0.5, 20), 0.125));
+
+        let cosine: LearnRateSchedule = LearnRateSchedule::Cosine { period: 10, min_rate: 0.1 };
+        assert!(relative_eq!(cosine.rate(0.5, 0), 0.5));
+        assert!(relative_eq!(cosine.rate(0.5, 5), 0.3));
+        assert!(relative_eq!(cosine.rate(0.5, 10), 0.1));
+        assert!(relative_eq!(cosine.rate(0.5, 50), 0.1));
+
+        assert!(GDConfig::builder().schedule(LearnRateSchedule::Exponential { gamma: 1.5 }).build().is_err());
+        assert!(GDConfig::builder().optimizer(Optimizer::Adam { beta1: 1.0, beta2: 0.999, epsilon: 1e-8 }).build().is_err());
+
+    }
+
+    #[test]
+    fn test_config_deserialize_schedule() {
+
+        let config: GDConfig = serde_json::from_str(r#"{
+            "schedule": {"type": "cosine", "period": 100, "min_rate": 0.01},
+            "optimizer": {"type": "adam", "beta1": 0.9, "beta2": 0.999, "epsilon": 1e-8}
+        }"#).unwrap();
+
+        assert_eq!(config.schedule, LearnRateSchedule::Cosine { period: 100, min_rate: 0.01 });
+        assert_eq!(config.optimizer, Optimizer::Adam { beta1: 0.9, beta2: 0.999, epsilon: 1e-8 });
+
+    }
+
+    /// Solve TARGET with the 3 link arm, allowing enough steps to tell configs apart
+    fn solve_with(config: GDConfig) -> SolveResult {
+
+        let mut ik_solver: IKSolverGD = three_link_arm(&[0.0; 3], 100.0, GDConfig { max_steps: 500, ..config });
+        ik_solver.limits.stall_iterations = 500;
+
+        ik_solver.solve(TARGET, 0.000001)
+    }
+
+    #[test]
+    fn test_solver_decay_applied() {
+
+        // a large constant rate overshoots forever, decaying it settles into the minimum
+        let constant: SolveResult = solve_with(GDConfig { learn_rate: 8.0, schedule: LearnRateSchedule::Constant, ..GDConfig::default() });
+        let decayed: SolveResult = solve_with(GDConfig { learn_rate: 8.0, schedule: LearnRateSchedule::Exponential { gamma: 0.99 }, ..GDConfig::default() });
+
+        assert!(!constant.success);
+        assert!(decayed.success);
+
+    }
+
+    #[test]
+    fn test_solver_adaptive_optimizers() {
+
+        let momentum: SolveResult = solve_with(GDConfig::default());
+        let adam: SolveResult = solve_with(GDConfig { learn_rate: 0.1, schedule: LearnRateSchedule::Constant, optimizer: Optimizer::Adam { beta1: 0.9, beta2: 0.999, epsilon: 1e-8 }, ..GDConfig::default() });
+        let rmsprop: SolveResult = solve_with(GDConfig { learn_rate: 0.05, schedule: LearnRateSchedule::Exponential { gamma: 0.98 }, optimizer: Optimizer::RMSProp { rho: 0.9, epsilon: 1e-8 }, ..GDConfig::default() });
+
+        assert!(momentum.success && adam.success && rmsprop.success);
+        assert!(adam.iterations < momentum.iterations);
+        assert!(rmsprop.iterations < momentum.iterations);
+
+    }
+
+    #[test]
+    fn test_solver_prismatic_joints() {
+
+        // gantry with x and y rails and a telescoping z axis, then a wrist
+        let values: Vec<f32> = vec![0.0, 0.0, 0.0, 0.0];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::y_axis(), *Vector3::z_axis(), *Vector3::z_axis()];
+        let radii: Vec<f32> = vec![0.0, 0.0, 1.0, 0.5];
+        let joint_types: Vec<JointType> = vec![JointType::Prismatic, JointType::Prismatic, JointType::Prismatic, JointType::Revolute];
+
+        // metres for the rails, radians for the wrist
+        let min_angles: Vec<f32> = vec![-2.0, -2.0, 0.0, -3.0];
+        let max_angles: Vec<f32> = vec![2.0, 2.0, 1.0, 3.0];
+
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let config: GDConfig = GDConfig::builder().max_steps(200).build().unwrap();
+        let mut ik_solver: IKSolverGD = IKSolverGD::with_config(IDENTITY, &values, &axes, &radii, &min_angles, &max_angles, collision_handler, config).with_joint_types(&joint_types);
+
+        let target: Matrix4<f32> = Matrix4::new_translation(&Vector3::new(0.5, -0.3, 1.8)) * Matrix4::new_rotation(Vector3::new(0.0, 0.0, 0.4));
+        let result: SolveResult = ik_solver.solve(target, 0.00001);
+
+        assert!(result.success);
+        assert!(result.position_error < 0.01);
+        assert!(relative_eq!(result.thetas[0], 0.5, epsilon = 0.01));
+        assert!(relative_eq!(result.thetas[1], -0.3, epsilon = 0.01));
+        assert!(relative_eq!(result.thetas[2], 0.3, epsilon = 0.01));
+
+    }
+
+    #[test]
+    fn test_solver_fixed_joints() {
+
+        let joint_types: Vec<JointType> = vec![JointType::Revolute, JointType::Fixed, JointType::Revolute];
+
+        let mut ik_solver: IKSolverGD = three_link_arm(&[0.0, 0.2, 0.0], 100.0, GDConfig::default()).with_joint_types(&joint_types);
+
+        let result: SolveResult = ik_solver.solve(TARGET, 0.0001);
+
+        assert_eq!(result.thetas[1], 0.2);
+
+    }
+
+    #[test]
+    fn test_solver_weighted_loss() {
+
+        // the weighted loss is not normalized, so the learn rate and d_loss clamp scale with the weights
+        let config: GDConfig = GDConfig::builder()
+        .weights(PoseWeights::from_tolerances(0.01, 0.01))
+        .learn_rate(1e-6)
+        .schedule(LearnRateSchedule::Constant)
+        .max_d_loss(f32::INFINITY)
+        .max_steps(1000)
+        .build().unwrap();
+
+        let mut ik_solver: IKSolverGD = three_link_arm(&[0.0; 3], 100.0, config);
+        ik_solver.limits.stall_iterations = 1000;
+
+        // a loss under 1 puts the hand within 1cm and 0.01 rad of the target
+        let result: SolveResult = ik_solver.solve(TARGET, 1.0);
+
+        assert!(result.success);
+        assert!(result.position_error < 0.01);
+        assert!(result.orientation_error < 0.01);
+
+        let config: GDConfig = serde_json::from_str(r#"{"weights": {"position": 10000.0, "orientation": 10000.0}}"#).unwrap();
+        assert_eq!(config.weights, Some(PoseWeights::from_tolerances(0.01, 0.01)));
+
+    }
+
+    #[test]
+    fn test_limit_strategies() {
+
+        assert_eq!(LimitStrategy::Skip.apply(1.2, -1.0, 1.0), None);
+        assert_eq!(LimitStrategy::Skip.apply(0.5, -1.0, 1.0), Some(0.5));
+        assert_eq!(LimitStrategy::Clamp.apply(1.2, -1.0, 1.0), Some(1.0));
+        assert!(relative_eq!(LimitStrategy::Reflect.apply(1.2, -1.0, 1.0).unwrap(), 0.8));
+        assert!(relative_eq!(LimitStrategy::Reflect.apply(-1.5, -1.0, 1.0).unwrap(), -0.5));
+
+        // nothing outside the margin, a quadratic rise towards the limit and a push back inside it
+        let penalty: LimitStrategy = LimitStrategy::Penalty { weight: 2.0, margin: 0.5 };
+        assert_eq!(penalty.penalty(0.0, -1.0, 1.0), (0.0, 0.0));
+        assert!(relative_eq!(penalty.penalty(0.75, -1.0, 1.0).0, 0.5));
+        assert!(penalty.penalty(0.75, -1.0, 1.0).1 > 0.0);
+        assert!(penalty.penalty(-0.75, -1.0, 1.0).1 < 0.0);
+        assert_eq!(penalty.penalty(0.0, f32::NEG_INFINITY, f32::INFINITY), (0.0, 0.0));
+
+        assert!(GDConfig::builder().limit_strategy(LimitStrategy::Penalty { weight: 1.0, margin: 0.0 }).build().is_err());
+
+        // skipping stays the default so existing configs behave as they did
+        assert_eq!(GDConfig::default().limit_strategy, LimitStrategy::Skip);
+
+    }
+
+    #[test]
+    fn test_solver_limit_strategies() {
+
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::x_axis(), *Vector3::x_axis(), *Vector3::x_axis()];
+        let radii: Vec<f32> = vec![2.0,2.0,2.0];
+
+        // reaching the target needs the first joint past its limit
+        let target: Matrix4<f32> = *generate_forward_matrices(&generate_matrices(IDENTITY, &[1.0, 0.0, 0.0], &axes, &radii)).last().unwrap();
+
+        let solve = |limit_strategy: LimitStrategy| {
+            let config: GDConfig = GDConfig::builder().limit_strategy(limit_strategy).max_steps(500).build().unwrap();
+            three_link_arm(&[0.0; 3], 0.5, config).solve(target, 0.000001)
+        };
+
+        // skipped steps leave the joint short of its limit, clamping takes it all the way
+        let skip: SolveResult = solve(LimitStrategy::Skip);
+        let clamp: SolveResult = solve(LimitStrategy::Clamp);
+        let reflect: SolveResult = solve(LimitStrategy::Reflect);
+
+        assert!(!skip.at_limits[0]);
+        assert!(clamp.at_limits[0]);
+        assert_eq!(clamp.thetas[0], 0.5);
+        assert!(clamp.position_error < skip.position_error);
+        assert!(reflect.thetas.iter().all(|theta| (-0.5..=0.5).contains(theta)));
+
+        // the penalty keeps the joint off the limit
+        let penalty: SolveResult = solve(LimitStrategy::Penalty { weight: 0.05, margin: 0.2 });
+        assert!(!penalty.at_limits[0]);
+        assert!(penalty.thetas[0] < 0.45);
+
+    }
+
+    #[test]
+    fn test_solver_continuous_joints() {
+
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis()];
+        let radii: Vec<f32> = vec![1.0,2.0,2.0];
+        let joint_types: Vec<JointType> = vec![JointType::Continuous, JointType::Revolute, JointType::Revolute];
+
+        // the base has to turn across the wrap around from just under half a turn to just over it
+        let goal: Vec<f32> = vec![-3.0, 0.4, 0.6];
+        let target: Matrix4<f32> = *generate_forward_matrices(&generate_matrices(IDENTITY, &goal, &axes, &radii)).last().unwrap();
+
+        let config: GDConfig = GDConfig::builder().max_steps(500).build().unwrap();
+        let mut ik_solver: IKSolverGD = IKSolverGD::with_config(IDENTITY, &[3.0, 0.3, 0.5], &axes, &radii, &[-1.0, -2.0, -2.0], &[1.0, 2.0, 2.0], CollisionHandler::new(&[], &[], &[]), config)
+        .with_joint_types(&joint_types);
+
+        // continuous joints drop their limits
+        assert_eq!(ik_solver.chain.min_angles[0], f32::NEG_INFINITY);
+
+        let result: SolveResult = ik_solver.solve(target, 0.000001);
+
+        assert!(result.success);
+        assert!(result.thetas[0] > -PI && result.thetas[0] <= PI);
+        assert!(angle_difference(3.0, result.thetas[0]).abs() < 0.5);
+        assert!(relative_eq!(result.thetas[0], -3.0, epsilon = 0.01));
+        assert!(!result.at_limits[0]);
+
+        // a seed wound up many turns is brought back into range
+        let ik_solver: IKSolverGD = IKSolverGD::new(IDENTITY, &[20.0, 0.0, 0.0], &axes, &radii, &[-1.0; 3], &[1.0; 3], CollisionHandler::new(&[], &[], &[])).with_joint_types(&joint_types);
+        assert!(relative_eq!(ik_solver.chain.thetas[0], 20.0 - 6.0 * PI, epsilon = 1e-5));
+
     }
 
-}
\ No newline at end of file
+}
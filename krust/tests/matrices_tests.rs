@@ -1,3 +1,6 @@
+// Expected matrices are recorded from the JS implementation at f64 precision
+#![allow(clippy::excessive_precision, clippy::approx_constant)]
+
 #[macro_use]
 extern crate approx; // For the macro relative_eq!
 extern crate nalgebra as na;
@@ -5,9 +8,9 @@ extern crate nalgebra as na;
 #[cfg(test)]
 mod matrices_tests {
 
-    use na::{Vector3, Matrix4};
+    use na::{Vector3, Matrix3, Matrix4, DMatrix, Isometry3};
     use std::{f32::consts::PI};
-    use krust::matrices::{transform_matrix, generate_matrices, generate_forward_matrices, generate_backward_matrices, transform_loss, IDENTITY};
+    use krust::matrices::{transform_matrix, generate_matrices, generate_forward_matrices, generate_backward_matrices, transform_loss, transform_loss_gradient, jacobian, jacobian_with_joints, generate_matrices_with_joints, generate_matrices_with_offsets, radius_offsets, offset_length, dh_chain, DHParameters, DHConvention, position_error, orientation_error, rotation_error, weighted_pose_loss, weighted_pose_loss_gradient, PoseWeights, wrap_angle, angle_difference, wrap_continuous, chain_parents, generate_forward_matrices_tree, tree_ancestors, jacobian_tree, JointType, IDENTITY};
 
     const ORIGIN: Matrix4<f32> = Matrix4::new(  
         1.0,0.0,0.0,0.0,
@@ -93,7 +96,7 @@ mod matrices_tests {
         assert_eq!(test_mats.len(), 4);
 
         // asset first matrix is what we expect
-        assert!(relative_eq!(*test_mats.get(0).unwrap(), ORIGIN));
+        assert!(relative_eq!(*test_mats.first().unwrap(), ORIGIN));
         assert!(relative_eq!(*test_mats.get(1).unwrap(), mat_1));
         assert!(relative_eq!(*test_mats.get(2).unwrap(), mat_2));
         assert!(relative_eq!(*test_mats.get(3).unwrap(), mat_3));
@@ -236,5 +239,400 @@ mod matrices_tests {
 
     }
 
+    const ANGLES: [f32; 6] = [0.3, -0.7, 1.0, -0.25, 1.2, 0.9];
+    const RADII: [f32; 6] = [1.0, 4.0, 4.0, 2.0, 1.0, 2.0];
+
+    fn end_effector(angles: &[f32], axes: &[Vector3<f32>]) -> Matrix4<f32> {
+        let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(ORIGIN, angles, axes, &RADII));
+        forward_mats[forward_mats.len() - 1]
+    }
+
+    #[test]
+    fn test_jacobian() {
+
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis(), *Vector3::z_axis(), *Vector3::y_axis(), *Vector3::x_axis()];
+        let d: f32 = 0.001;
+
+        let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(ORIGIN, &ANGLES, &axes, &RADII));
+        let jac: DMatrix<f32> = jacobian(&forward_mats, &axes);
+
+        assert_eq!(jac.shape(), (6, 6));
+
+        for i in 0..ANGLES.len() {
+
+            // central differences of the end effector pose
+            let mut plus: Vec<f32> = ANGLES.to_vec();
+            let mut minus: Vec<f32> = ANGLES.to_vec();
+            plus[i] += d;
+            minus[i] -= d;
+
+            let ee_plus: Matrix4<f32> = end_effector(&plus, &axes);
+            let ee_minus: Matrix4<f32> = end_effector(&minus, &axes);
+
+            let linear: Vector3<f32> = (ee_plus.fixed_slice::<3, 1>(0, 3) - ee_minus.fixed_slice::<3, 1>(0, 3)) / (2.0 * d);
+
+            // skew-symmetric part of the small rotation between the two poses, acos is too noisy in f32
+            let rotation: Matrix3<f32> = ee_plus.fixed_slice::<3, 3>(0, 0) * ee_minus.fixed_slice::<3, 3>(0, 0).transpose();
+            let skew: Matrix3<f32> = (rotation - rotation.transpose()) / 2.0;
+            let angular: Vector3<f32> = Vector3::new(skew[(2, 1)], skew[(0, 2)], skew[(1, 0)]) / (2.0 * d);
+
+            assert!(relative_eq!(jac.fixed_slice::<3, 1>(0, i).into_owned(), linear, epsilon = 0.01));
+            assert!(relative_eq!(jac.fixed_slice::<3, 1>(3, i).into_owned(), angular, epsilon = 0.01));
+        }
+
+    }
+
+    #[test]
+    fn test_transform_loss_gradient() {
+
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis(), *Vector3::z_axis(), *Vector3::y_axis(), *Vector3::x_axis()];
+        let d: f32 = 0.001;
+
+        let target: Matrix4<f32> = Matrix4::new(
+            0.0,-1.0,0.0,2.0,
+            1.0,0.0,0.0,3.0,
+            0.0,0.0,1.0,5.0,
+            0.0,0.0,0.0,1.0
+        );
+
+        let dist_correction: f32 = RADII.iter().sum();
+        let rot_correction: f32 = PI;
+
+        let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(ORIGIN, &ANGLES, &axes, &RADII));
+        let actual: Matrix4<f32> = forward_mats[forward_mats.len() - 1];
+        let gradient: Vec<f32> = transform_loss_gradient(&actual, &target, &jacobian(&forward_mats, &axes), dist_correction, rot_correction);
+
+        for i in 0..ANGLES.len() {
+
+            let mut plus: Vec<f32> = ANGLES.to_vec();
+            let mut minus: Vec<f32> = ANGLES.to_vec();
+            plus[i] += d;
+            minus[i] -= d;
+
+            let loss_plus: f32 = transform_loss(&end_effector(&plus, &axes), &target, dist_correction, rot_correction);
+            let loss_minus: f32 = transform_loss(&end_effector(&minus, &axes), &target, dist_correction, rot_correction);
+
+            assert!(relative_eq!(gradient[i], (loss_plus - loss_minus) / (2.0 * d), epsilon = 0.001));
+        }
+
+    }
+
+    #[test]
+    fn test_position_orientation_error() {
+
+        let actual: Matrix4<f32> = transform_matrix(0.3, &Vector3::x(), &Vector3::new(1.0, 2.0, 3.0));
+        let expected: Matrix4<f32> = transform_matrix(-0.2, &Vector3::x(), &Vector3::new(4.0, 6.0, 3.0));
+
+        assert!(relative_eq!(position_error(&actual, &expected), 5.0, epsilon = 1e-5));
+        assert!(relative_eq!(orientation_error(&actual, &expected), 0.5, epsilon = 1e-5));
+
+        assert_eq!(position_error(&actual, &actual), 0.0);
+        assert!(orientation_error(&actual, &actual) < 1e-6);
+
+        // half turn
+        let flipped: Matrix4<f32> = transform_matrix(PI, &Vector3::z(), &Vector3::zeros());
+        assert!(relative_eq!(orientation_error(&IDENTITY, &flipped), PI, epsilon = 1e-5));
+
+    }
+
+    #[test]
+    fn test_rotation_error() {
+
+        let actual: Matrix4<f32> = transform_matrix(0.3, &Vector3::x(), &Vector3::zeros());
+
+        // a world frame rotation vector, accurate down to tiny angles
+        let expected: Matrix4<f32> = transform_matrix(-0.2, &Vector3::y(), &Vector3::zeros()) * actual;
+        assert!(relative_eq!(rotation_error(&actual, &expected), Vector3::new(0.0, -0.2, 0.0), epsilon = 1e-5));
+
+        let nudged: Matrix4<f32> = transform_matrix(1e-4, &Vector3::z(), &Vector3::zeros()) * actual;
+        assert!(relative_eq!(rotation_error(&actual, &nudged), Vector3::new(0.0, 0.0, 1e-4), epsilon = 1e-6));
+
+        let flipped: Matrix4<f32> = transform_matrix(PI, &Vector3::z(), &Vector3::zeros());
+        assert!(relative_eq!(rotation_error(&IDENTITY, &flipped).norm(), PI, epsilon = 1e-5));
+
+        // metres and radians, weighted
+        let expected: Matrix4<f32> = transform_matrix(0.5, &Vector3::x(), &Vector3::new(0.0, 0.3, 0.4));
+        assert!(relative_eq!(weighted_pose_loss(&actual, &expected, &PoseWeights::new(2.0, 4.0)), 2.0 * 0.25 + 4.0 * 0.04, epsilon = 1e-5));
+        assert!(relative_eq!(weighted_pose_loss(&actual, &expected, &PoseWeights::from_tolerances(0.5, 0.2)), 2.0, epsilon = 1e-4));
+
+        assert!(PoseWeights::new(0.0, 0.0).validate().is_err());
+        assert!(PoseWeights::new(-1.0, 1.0).validate().is_err());
+
+    }
+
+    #[test]
+    fn test_weighted_pose_loss_gradient() {
+
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis(), *Vector3::z_axis(), *Vector3::y_axis(), *Vector3::x_axis()];
+        let d: f32 = 0.001;
+
+        let target: Matrix4<f32> = Matrix4::new(
+            0.0,-1.0,0.0,2.0,
+            1.0,0.0,0.0,3.0,
+            0.0,0.0,1.0,5.0,
+            0.0,0.0,0.0,1.0
+        );
+        let weights: PoseWeights = PoseWeights::new(0.01, 0.5);
+
+        let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(ORIGIN, &ANGLES, &axes, &RADII));
+        let actual: Matrix4<f32> = forward_mats[forward_mats.len() - 1];
+        let gradient: Vec<f32> = weighted_pose_loss_gradient(&actual, &target, &jacobian(&forward_mats, &axes), &weights);
+
+        for i in 0..ANGLES.len() {
+
+            let mut plus: Vec<f32> = ANGLES.to_vec();
+            let mut minus: Vec<f32> = ANGLES.to_vec();
+            plus[i] += d;
+            minus[i] -= d;
+
+            let loss_plus: f32 = weighted_pose_loss(&end_effector(&plus, &axes), &target, &weights);
+            let loss_minus: f32 = weighted_pose_loss(&end_effector(&minus, &axes), &target, &weights);
+
+            assert!(relative_eq!(gradient[i], (loss_plus - loss_minus) / (2.0 * d), epsilon = 0.005));
+        }
+
+    }
+
+    #[test]
+    fn test_wrap_angle() {
+
+        assert!(relative_eq!(wrap_angle(1.5 * PI), -0.5 * PI));
+        assert!(relative_eq!(wrap_angle(-7.0), 2.0 * PI - 7.0, epsilon = 1e-5));
+        assert_eq!(wrap_angle(PI), PI);
+        assert_eq!(wrap_angle(-PI), PI);
+        assert_eq!(wrap_angle(0.5), 0.5);
+
+        // across the wrap the short way round
+        assert!(relative_eq!(angle_difference(3.0, -3.0), 2.0 * PI - 6.0, epsilon = 1e-5));
+        assert!(relative_eq!(angle_difference(-3.0, 3.0), 6.0 - 2.0 * PI, epsilon = 1e-5));
+
+        let mut values: Vec<f32> = vec![4.0, 4.0, 4.0];
+        wrap_continuous(&mut values, &[JointType::Continuous, JointType::Revolute, JointType::Prismatic]);
+        assert!(relative_eq!(values.as_slice(), [4.0 - 2.0 * PI, 4.0, 4.0].as_slice()));
+
+    }
+
+    #[test]
+    fn test_joint_types() {
+
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis(), *Vector3::x_axis(), *Vector3::y_axis()];
+        let values: Vec<f32> = vec![0.5, 0.75, 1.5];
+        let radii: Vec<f32> = vec![1.0, 2.0, 0.5];
+
+        // all revolute matches the original chain
+        let revolute: Vec<JointType> = vec![JointType::Revolute; 3];
+        assert_eq!(generate_matrices_with_joints(ORIGIN, &values, &axes, &radius_offsets(&radii), &revolute), generate_matrices(ORIGIN, &values, &axes, &radii));
+
+        // prismatic slides along the axis after the link, fixed ignores the value
+        let joint_types: Vec<JointType> = vec![JointType::Revolute, JointType::Prismatic, JointType::Fixed];
+        let mats: Vec<Matrix4<f32>> = generate_matrices_with_joints(ORIGIN, &values, &axes, &radius_offsets(&radii), &joint_types);
+
+        assert_eq!(mats[2], Matrix4::new_translation(&Vector3::new(0.75, 0.0, 2.0)));
+        assert_eq!(mats[3], Matrix4::new_translation(&Vector3::new(0.0, 0.0, 0.5)));
+
+    }
+
+    #[test]
+    fn test_jacobian_with_joints() {
+
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis(), *Vector3::y_axis(), *Vector3::z_axis(), *Vector3::y_axis()];
+        let joint_types: Vec<JointType> = vec![JointType::Revolute, JointType::Prismatic, JointType::Fixed, JointType::Revolute];
+        let radii: Vec<f32> = vec![1.0, 2.0, 1.0, 1.0];
+        let values: Vec<f32> = vec![0.4, 0.3, 0.2, -0.6];
+        let d: f32 = 0.001;
+
+        let end_effector = |values: &[f32]| -> Matrix4<f32> {
+            let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices_with_joints(ORIGIN, values, &axes, &radius_offsets(&radii), &joint_types));
+            forward_mats[forward_mats.len() - 1]
+        };
+
+        let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices_with_joints(ORIGIN, &values, &axes, &radius_offsets(&radii), &joint_types));
+        let jac: DMatrix<f32> = jacobian_with_joints(&forward_mats, &axes, &joint_types);
+
+        for i in 0..values.len() {
+
+            let mut plus: Vec<f32> = values.to_vec();
+            let mut minus: Vec<f32> = values.to_vec();
+            plus[i] += d;
+            minus[i] -= d;
+
+            let linear: Vector3<f32> = (end_effector(&plus).fixed_slice::<3, 1>(0, 3) - end_effector(&minus).fixed_slice::<3, 1>(0, 3)) / (2.0 * d);
+            assert!(relative_eq!(jac.fixed_slice::<3, 1>(0, i).into_owned(), linear, epsilon = 0.01));
+        }
+
+        // prismatic and fixed joints don't rotate the end effector
+        assert_eq!(jac.fixed_slice::<3, 1>(3, 1).norm(), 0.0);
+        assert_eq!(jac.column(2).norm(), 0.0);
+
+    }
+
+    #[test]
+    fn test_link_offsets() {
+
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis()];
+        let angles: Vec<f32> = vec![0.3, -0.7, 1.2];
+        let radii: Vec<f32> = vec![1.0, 2.0, 0.5];
+
+        // straight offsets match the original chain
+        assert!(relative_eq!(
+            generate_matrices_with_offsets(ORIGIN, &angles, &axes, &radius_offsets(&radii))[..],
+            generate_matrices(ORIGIN, &angles, &axes, &radii)[..],
+            epsilon = 1e-6
+        ));
+
+        // shoulder offset sideways and rolled a quarter turn about x
+        let offset: Isometry3<f32> = Isometry3::new(Vector3::new(0.5, 0.0, 1.0), Vector3::new(PI / 2.0, 0.0, 0.0));
+        assert!(relative_eq!(offset_length(&offset), 1.25_f32.sqrt()));
+
+        let mats: Vec<Matrix4<f32>> = generate_matrices_with_offsets(ORIGIN, &[PI / 2.0], &[*Vector3::z_axis()], &[offset]);
+        let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&mats);
+
+        // the joint turns about the rolled z axis, which points along -y
+        let x: Vector3<f32> = forward_mats[1].fixed_slice::<3, 3>(0, 0) * Vector3::x();
+        assert!(relative_eq!(forward_mats[1].fixed_slice::<3, 1>(0, 3).into_owned(), Vector3::new(0.5, 0.0, 1.0), epsilon = 1e-6));
+        assert!(relative_eq!(x, Vector3::new(0.0, 0.0, 1.0), epsilon = 1e-6));
+
+    }
+
+    fn dh_end_effector(parameters: &[DHParameters], convention: DHConvention, angles: &[f32]) -> Matrix4<f32> {
+        let (axes, offsets, tool) = dh_chain(parameters, convention);
+        let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices_with_offsets(ORIGIN, angles, &axes, &offsets));
+        forward_mats[forward_mats.len() - 1] * tool.to_homogeneous()
+    }
+
+    #[test]
+    fn test_dh_classic_ur5() {
+
+        let parameters: Vec<DHParameters> = vec![
+            DHParameters { a: 0.0, alpha: PI / 2.0, d: 0.089159, theta_offset: 0.0 },
+            DHParameters { a: -0.425, alpha: 0.0, d: 0.0, theta_offset: 0.0 },
+            DHParameters { a: -0.39225, alpha: 0.0, d: 0.0, theta_offset: 0.0 },
+            DHParameters { a: 0.0, alpha: PI / 2.0, d: 0.10915, theta_offset: 0.0 },
+            DHParameters { a: 0.0, alpha: -PI / 2.0, d: 0.09465, theta_offset: 0.0 },
+            DHParameters { a: 0.0, alpha: 0.0, d: 0.0823, theta_offset: 0.0 },
+        ];
+
+        // one joint per row, the trailing fixed transform is the tool
+        let (axes, offsets, tool) = dh_chain(&parameters, DHConvention::Classic);
+        assert_eq!(axes.len(), 6);
+        assert_eq!(offsets.len(), 6);
+        assert!(relative_eq!(tool.translation.vector, Vector3::new(0.0, 0.0, 0.0823), epsilon = 1e-6));
+
+        // published zero pose of the UR5
+        let zero: Matrix4<f32> = Matrix4::new(
+            1.0, 0.0, 0.0, -0.81725,
+            0.0, 0.0, -1.0, -0.19145,
+            0.0, 1.0, 0.0, -0.005491,
+            0.0, 0.0, 0.0, 1.0
+        );
+        assert!(relative_eq!(dh_end_effector(&parameters, DHConvention::Classic, &[0.0; 6]), zero, epsilon = 1e-5));
+
+        let posed: Matrix4<f32> = Matrix4::new(
+            0.592657, -0.374490, -0.713103, -0.540577,
+            -0.530170, 0.485130, -0.695391, -0.320549,
+            0.606364, 0.790194, 0.088972, 0.282503,
+            0.0, 0.0, 0.0, 1.0
+        );
+        assert!(relative_eq!(dh_end_effector(&parameters, DHConvention::Classic, &[0.3, -1.2, 1.5, -0.4, 1.1, 0.7]), posed, epsilon = 1e-5));
+
+    }
+
+    #[test]
+    fn test_dh_modified_puma560() {
+
+        let parameters: Vec<DHParameters> = vec![
+            DHParameters { a: 0.0, alpha: 0.0, d: 0.0, theta_offset: 0.0 },
+            DHParameters { a: 0.0, alpha: -PI / 2.0, d: 0.0, theta_offset: 0.0 },
+            DHParameters { a: 0.4318, alpha: 0.0, d: 0.15005, theta_offset: 0.0 },
+            DHParameters { a: 0.0203, alpha: -PI / 2.0, d: 0.4318, theta_offset: 0.0 },
+            DHParameters { a: 0.0, alpha: PI / 2.0, d: 0.0, theta_offset: 0.0 },
+            DHParameters { a: 0.0, alpha: -PI / 2.0, d: 0.0, theta_offset: 0.0 },
+        ];
+
+        let (axes, offsets, tool) = dh_chain(&parameters, DHConvention::Modified);
+        assert_eq!(axes.len(), 6);
+        assert_eq!(offsets.len(), 6);
+        assert_eq!(tool, Isometry3::identity());
+
+        // zero pose from Craig, wrist centre at (a2 + a3, d3, -d4)
+        let zero: Matrix4<f32> = Matrix4::new(
+            1.0, 0.0, 0.0, 0.4521,
+            0.0, -1.0, 0.0, 0.15005,
+            0.0, 0.0, -1.0, -0.4318,
+            0.0, 0.0, 0.0, 1.0
+        );
+        assert!(relative_eq!(dh_end_effector(&parameters, DHConvention::Modified, &[0.0; 6]), zero, epsilon = 1e-5));
+
+        let posed: Matrix4<f32> = Matrix4::new(
+            0.463584, 0.430092, -0.774668, 0.001756,
+            -0.336284, -0.723472, -0.602911, 0.157608,
+            -0.819758, 0.540009, -0.190757, -0.016059,
+            0.0, 0.0, 0.0, 1.0
+        );
+        assert!(relative_eq!(dh_end_effector(&parameters, DHConvention::Modified, &[0.3, -1.2, 1.5, -0.4, 1.1, 0.7]), posed, epsilon = 1e-5));
+
+    }
+
+    #[test]
+    fn test_dh_theta_offset() {
+
+        // an offset is the same as turning the joint
+        let offset: Vec<DHParameters> = vec![DHParameters { a: 1.0, alpha: 0.3, d: 0.5, theta_offset: 0.4 }, DHParameters { a: 0.5, alpha: 0.0, d: 0.0, theta_offset: 0.0 }];
+        let turned: Vec<DHParameters> = vec![DHParameters { a: 1.0, alpha: 0.3, d: 0.5, theta_offset: 0.0 }, DHParameters { a: 0.5, alpha: 0.0, d: 0.0, theta_offset: 0.0 }];
+
+        for convention in [DHConvention::Classic, DHConvention::Modified] {
+            assert!(relative_eq!(dh_end_effector(&offset, convention, &[0.2, 0.1]), dh_end_effector(&turned, convention, &[0.6, 0.1]), epsilon = 1e-6));
+        }
+
+    }
+
+    #[test]
+    fn test_forward_matrices_tree() {
+
+        let angles: Vec<f32> = vec![0.3, -0.5, 0.8, 1.1];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis(), *Vector3::x_axis()];
+        let radii: Vec<f32> = vec![1.0, 2.0, 1.5, 0.5];
+        let mats: Vec<Matrix4<f32>> = generate_matrices(IDENTITY, &angles, &axes, &radii);
+
+        // a chain is a tree where every joint hangs off the one before
+        assert_eq!(chain_parents(4), vec![None, Some(0), Some(1), Some(2)]);
+        assert_eq!(generate_forward_matrices_tree(&mats, &chain_parents(4)), generate_forward_matrices(&mats));
+
+        // joints 2 and 3 both hang off joint 0
+        let parents: Vec<Option<usize>> = vec![None, Some(0), Some(0), Some(2)];
+        let tree: Vec<Matrix4<f32>> = generate_forward_matrices_tree(&mats, &parents);
+
+        assert_eq!(tree[2], mats[0] * mats[1] * mats[2]);
+        assert_eq!(tree[3], mats[0] * mats[1] * mats[3]);
+        assert_eq!(tree[4], mats[0] * mats[1] * mats[3] * mats[4]);
+        assert_eq!(tree_ancestors(&parents, 3), vec![3, 2, 0]);
+
+    }
+
+    #[test]
+    fn test_jacobian_tree() {
+
+        let angles: Vec<f32> = vec![0.3, -0.5, 0.8, 1.1];
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis(), *Vector3::x_axis()];
+        let radii: Vec<f32> = vec![1.0, 2.0, 1.5, 0.5];
+        let joint_types: Vec<JointType> = vec![JointType::Revolute; 4];
+        let parents: Vec<Option<usize>> = vec![None, Some(0), Some(0), Some(2)];
+
+        let mats: Vec<Matrix4<f32>> = generate_matrices(IDENTITY, &angles, &axes, &radii);
+        let tree: Vec<Matrix4<f32>> = generate_forward_matrices_tree(&mats, &parents);
+        let tree_jacobian: DMatrix<f32> = jacobian_tree(&tree, &axes, &joint_types, &parents, 3);
+
+        // the branch through joints 0, 2 and 3 as a chain of its own
+        let branch: Vec<Matrix4<f32>> = generate_forward_matrices(&[mats[0], mats[1], mats[3], mats[4]]);
+        let chain_jacobian: DMatrix<f32> = jacobian(&branch, &[axes[0], axes[2], axes[3]]);
+
+        assert!(relative_eq!(tree_jacobian.column(0), chain_jacobian.column(0), epsilon = 1e-6));
+        assert!(relative_eq!(tree_jacobian.column(2), chain_jacobian.column(1), epsilon = 1e-6));
+        assert!(relative_eq!(tree_jacobian.column(3), chain_jacobian.column(2), epsilon = 1e-6));
+
+        // joint 1 is on the other branch
+        assert!(tree_jacobian.column(1).iter().all(|value| *value == 0.0));
+
+    }
 
-}
\ No newline at end of file
+}
@@ -0,0 +1,210 @@
+extern crate nalgebra as na;
+
+#[cfg(test)]
+mod mesh_tests {
+
+    use krust::collision_handler::{CollisionHandler, Shape};
+    use na::{Vector3, Matrix4, Isometry3, UnitQuaternion};
+    use krust::matrices::{generate_matrices, generate_forward_matrices};
+    use krust::mesh::{Mesh, MeshError};
+
+    // unit cube from 0 to 1, with quads and every vertex reference style
+    const CUBE: &str = "# cube
+o cube
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0 0 1
+v 1 0 1
+v 1 1 1
+v 0 1 1
+vn 0 0 1
+f 1 4 3 2
+f 5 6 7 8
+f 1/1 2/1 6/1 5/1
+f 2//1 3//1 7//1 6//1
+f 3/1/1 4/1/1 8/1/1 7/1/1
+f -4 -8 -5 -1
+";
+
+    // an L extruded along z, the notch at x, y > 1 is outside the mesh but partly inside its convex hull
+    const L_PRISM: &str = "v 0 0 0
+v 2 0 0
+v 2 1 0
+v 1 1 0
+v 1 2 0
+v 0 2 0
+v 0 0 1
+v 2 0 1
+v 2 1 1
+v 1 1 1
+v 1 2 1
+v 0 2 1
+f 4 3 2 1 6 5
+f 10 11 12 7 8 9
+f 1 2 8 7
+f 2 3 9 8
+f 3 4 10 9
+f 4 5 11 1
+f 5 6 12 11
+f 6 1 7 12
+";
+
+    /// Binary STL of the triangles, with a header that starts like an ASCII file
+    fn binary_stl(mesh: &Mesh) -> Vec<u8> {
+        let mut bytes: Vec<u8> = b"solid but actually binary".to_vec();
+        bytes.resize(80, 0);
+        bytes.extend((mesh.indices.len() as u32).to_le_bytes());
+        for triangle in mesh.indices.iter() {
+            bytes.extend([0u8; 12]);
+            for index in triangle {
+                mesh.vertices[*index].iter().for_each(|coordinate| bytes.extend(coordinate.to_le_bytes()));
+            }
+            bytes.extend([0u8; 2]);
+        }
+        bytes
+    }
+
+    /// Whether a small ball on a single link at the point hits an obstacle
+    fn probe(collision_handler: &CollisionHandler, point: Vector3<f32>) -> bool {
+        let mats: Vec<Matrix4<f32>> = generate_matrices(Matrix4::new_translation(&point), &[0.0], &[*Vector3::z_axis()], &[1.0]);
+        collision_handler.is_arm_colliding_world_naive(&generate_forward_matrices(&mats))
+    }
+
+    #[test]
+    fn test_obj() {
+
+        let mesh: Mesh = Mesh::from_obj(CUBE).unwrap();
+
+        assert_eq!(mesh.vertices.len(), 8);
+        assert_eq!(mesh.indices.len(), 12);
+        assert_eq!(mesh.indices[0], [0, 3, 2]);
+        assert_eq!(mesh.indices[11], [4, 3, 7]);
+
+        assert_eq!(Mesh::from_obj("v 0 0 0\nv 1 0 0\nf 1 2 3\n"), Err(MeshError::Invalid { line: 3, message: "Vertex 3 is out of range".to_string() }));
+        assert_eq!(Mesh::from_obj("v 0 0\n"), Err(MeshError::Invalid { line: 1, message: "Expected three coordinates".to_string() }));
+        assert_eq!(Mesh::from_obj("v 0 0 0\n"), Err(MeshError::Empty));
+
+    }
+
+    #[test]
+    fn test_stl() {
+
+        let mesh: Mesh = Mesh::from_obj(CUBE).unwrap();
+
+        // the binary header starts with "solid", the size gives it away. Welding numbers the vertices in the order they are met
+        let bytes: Vec<u8> = binary_stl(&mesh);
+        let corners = |mesh: &Mesh| mesh.indices.iter().map(|triangle| triangle.map(|index| mesh.vertices[index])).collect::<Vec<[Vector3<f32>; 3]>>();
+        let loaded: Mesh = Mesh::from_stl(&bytes).unwrap();
+        assert_eq!(loaded.vertices.len(), 8);
+        assert_eq!(corners(&loaded), corners(&mesh));
+        assert!(matches!(Mesh::from_stl(&bytes[..bytes.len() - 1]), Err(MeshError::Invalid { line: 1, .. })));
+        assert_eq!(Mesh::from_stl(&[0; 90]), Err(MeshError::Truncated { expected: 84, actual: 90 }));
+
+        // a corrupt triangle count is reported, not overflowed
+        let mut corrupt: Vec<u8> = vec![0; 84];
+        corrupt[80..84].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(Mesh::from_stl(&corrupt), Err(MeshError::Truncated { actual: 84, .. })));
+
+        let ascii: &str = "solid tetrahedron
+  facet normal 0 0 -1
+    outer loop
+      vertex 0 0 0
+      vertex 0 1 0
+      vertex 1 0 0
+    endloop
+  endfacet
+  facet normal 0 -1 0
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 0 0 1
+    endloop
+  endfacet
+  facet normal -1 0 0
+    outer loop
+      vertex 0 0 0
+      vertex 0 0 1
+      vertex 0 1 0
+    endloop
+  endfacet
+  facet normal 1 1 1
+    outer loop
+      vertex 1 0 0
+      vertex 0 1 0
+      vertex 0 0 1
+    endloop
+  endfacet
+endsolid tetrahedron
+";
+
+        // shared corners are welded
+        let mesh: Mesh = Mesh::from_stl(ascii.as_bytes()).unwrap();
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3], [0, 3, 1], [2, 1, 3]]);
+
+        assert_eq!(Mesh::from_stl(ascii.replace("      vertex 0 0 1\n    endloop", "    endloop").as_bytes()), Err(MeshError::Invalid { line: 13, message: "Loop has 2 vertices".to_string() }));
+
+    }
+
+    #[test]
+    fn test_mesh_obstacles() {
+
+        let arm: Vec<Shape> = vec![Shape::Sphere { radius: 0.1 }];
+        let mesh: Mesh = Mesh::from_obj(CUBE).unwrap().scaled(&Vector3::new(2.0, 2.0, 2.0));
+
+        // the cube spans 2 to 4 along x after the pose
+        let pose: Isometry3<f32> = Isometry3::translation(2.0, 0.0, 0.0);
+
+        let mut collision_handler: CollisionHandler = CollisionHandler::with_shapes(&arm, &[], &[]);
+        collision_handler.add_obstacle(&mesh.convex_hull(), &pose);
+        assert!(probe(&collision_handler, Vector3::new(3.0, 1.0, 1.0)));
+        assert!(probe(&collision_handler, Vector3::new(1.95, 1.0, 1.0)));
+        assert!(!probe(&collision_handler, Vector3::new(1.8, 1.0, 1.0)));
+        assert!((collision_handler.world_clearance(&generate_forward_matrices(&generate_matrices(Matrix4::new_translation(&Vector3::new(1.0, 1.0, 1.0)), &[0.0], &[*Vector3::z_axis()], &[1.0]))) - 0.9).abs() < 1e-4);
+
+        // only the surface of a triangle mesh collides
+        let mut collision_handler: CollisionHandler = CollisionHandler::with_shapes(&arm, &[], &[]);
+        collision_handler.add_obstacle(&mesh.tri_mesh(), &pose);
+        assert!(!probe(&collision_handler, Vector3::new(3.0, 1.0, 1.0)));
+        assert!(probe(&collision_handler, Vector3::new(3.0, 1.0, 0.05)));
+        assert!(!probe(&collision_handler, Vector3::new(3.0, 1.0, -0.2)));
+        assert!((collision_handler.world_clearance(&generate_forward_matrices(&generate_matrices(Matrix4::new_translation(&Vector3::new(3.0, 1.0, 1.0)), &[0.0], &[*Vector3::z_axis()], &[1.0]))) - 0.9).abs() < 1e-4);
+
+        // cylinders go through the same mesh queries
+        let mut collision_handler: CollisionHandler = CollisionHandler::with_shapes(&[Shape::Cylinder { half_height: 0.1, radius: 0.1 }], &[], &[]);
+        collision_handler.add_obstacle(&mesh.tri_mesh(), &pose);
+        assert!(probe(&collision_handler, Vector3::new(3.0, 1.0, 1.9)));
+        assert!(!probe(&collision_handler, Vector3::new(3.0, 1.0, 1.0)));
+
+        // a full pose turns the cube a quarter turn about z, so it spans 0 to 2 along y and -2 to 0 along x
+        let pose: Isometry3<f32> = Isometry3::from_parts(na::Translation3::identity(), UnitQuaternion::from_axis_angle(&Vector3::z_axis(), std::f32::consts::FRAC_PI_2));
+        let mut collision_handler: CollisionHandler = CollisionHandler::with_shapes(&arm, &[], &[]);
+        collision_handler.add_obstacle(&mesh.convex_hull(), &pose);
+        assert!(probe(&collision_handler, Vector3::new(-1.0, 1.0, 1.0)));
+        assert!(!probe(&collision_handler, Vector3::new(1.0, 1.0, 1.0)));
+
+    }
+
+    #[test]
+    fn test_convex_decomposition() {
+
+        let mesh: Mesh = Mesh::from_obj(L_PRISM).unwrap();
+        let arm: Vec<Shape> = vec![Shape::Sphere { radius: 0.1 }];
+        let notch: Vector3<f32> = Vector3::new(1.3, 1.3, 0.5);
+
+        let hull: CollisionHandler = CollisionHandler::with_shapes(&arm, &[mesh.convex_hull()], &[Vector3::zeros()]);
+        assert!(probe(&hull, notch));
+
+        let decomposition: Shape = mesh.convex_decomposition(0.03, 1);
+        assert!(matches!(&decomposition, Shape::Compound { parts } if parts.len() >= 2));
+
+        let parts: CollisionHandler = CollisionHandler::with_shapes(&arm, &[decomposition], &[Vector3::zeros()]);
+        assert!(!probe(&parts, notch));
+        assert!(probe(&parts, Vector3::new(0.5, 1.5, 0.5)));
+        assert!(probe(&parts, Vector3::new(1.5, 0.5, 0.5)));
+
+    }
+
+}
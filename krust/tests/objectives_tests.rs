@@ -0,0 +1,171 @@
+extern crate nalgebra as na;
+
+#[cfg(test)]
+mod objectives_tests {
+
+    use krust::collision_handler::CollisionHandler;
+    use na::{Vector3, Matrix4, DMatrix};
+    use krust::matrices::{IDENTITY, generate_matrices, generate_forward_matrices};
+    use krust::solver::{IKSolver, SolveResult};
+    use krust::solver_dls::{IKSolverDLS, DLSConfig};
+    use krust::objectives::{Objective, Posture};
+    use krust::target::Target;
+
+    const START: [f32; 10] = [0.3, 0.4, 0.3, 0.2, -0.3, 0.4, 0.2, 0.3, -0.2, 0.1];
+
+    // a redundant 10 joint arm, a position target leaves it 7 spare degrees of freedom
+    fn redundant_arm() -> (Vec<Vector3<f32>>, Vec<f32>) {
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis(), *Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis(), *Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis(), *Vector3::z_axis()];
+        let radii: Vec<f32> = vec![1.0, 2.0, 2.0, 1.0, 1.0, 2.0, 1.0, 1.0, 1.0, 0.5];
+        (axes, radii)
+    }
+
+    fn target() -> Target {
+        let (axes, radii) = redundant_arm();
+        let goal: Vec<f32> = vec![0.8, 0.9, 0.6, -0.4, 0.5, 0.3, 0.2, -0.4, 0.6, 0.0];
+        let pose: Matrix4<f32> = *generate_forward_matrices(&generate_matrices(IDENTITY, &goal, &axes, &radii)).last().unwrap();
+        Target::Position { position: pose.fixed_slice::<3, 1>(0, 3).into_owned() }
+    }
+
+    fn solver(collision_handler: CollisionHandler, objectives: &[Objective]) -> IKSolverDLS {
+        let (axes, radii) = redundant_arm();
+        let mut ik_solver: IKSolverDLS = IKSolverDLS::new(IDENTITY, &START, &axes, &radii, &[-2.0; 10], &[2.0; 10], collision_handler).with_objectives(objectives);
+        ik_solver.limits.max_iterations = 200;
+        ik_solver
+    }
+
+    /// Cost of the objective after solving without it and with it, both solves have to reach the target
+    fn costs(collision_handler: CollisionHandler, objective: Objective) -> (f32, f32) {
+
+        let mut plain: IKSolverDLS = solver(collision_handler.clone(), &[]);
+        let mut guided: IKSolverDLS = solver(collision_handler, &[objective]);
+
+        let plain_result: SolveResult = plain.solve_target(target(), 1e-7);
+        let guided_result: SolveResult = guided.solve_target(target(), 1e-7);
+
+        assert!(plain_result.success && guided_result.success);
+        assert!(guided_result.position_error < 0.01);
+
+        (guided.objectives_cost(&plain_result.thetas), guided.objectives_cost(&guided_result.thetas))
+    }
+
+    #[test]
+    fn test_rest_posture() {
+        let (plain, guided) = costs(CollisionHandler::new(&[], &[], &[]), Objective::RestPosture { thetas: START.to_vec(), weight: 1.0 });
+        assert!(guided < plain, "{} vs {}", guided, plain);
+    }
+
+    #[test]
+    fn test_joint_limits() {
+        let (plain, guided) = costs(CollisionHandler::new(&[], &[], &[]), Objective::JointLimits { weight: 1.0 });
+        assert!(guided < plain, "{} vs {}", guided, plain);
+    }
+
+    #[test]
+    fn test_manipulability() {
+        let (plain, guided) = costs(CollisionHandler::new(&[], &[], &[]), Objective::Manipulability { weight: 0.01 });
+        assert!(guided < plain, "{} vs {}", guided, plain);
+    }
+
+    #[test]
+    fn test_clearance() {
+
+        // a post standing next to the elbow
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[Vector3::new(0.1, 0.1, 0.5); 10], &[Vector3::new(0.3, 0.3, 3.0)], &[Vector3::new(2.5, 3.0, 0.0)]);
+
+        let (plain, guided) = costs(collision_handler, Objective::Clearance { distance: 2.0, weight: 1.0 });
+        assert!(guided < plain, "{} vs {}", guided, plain);
+
+    }
+
+    #[test]
+    fn test_rest_posture_at_target() {
+
+        // start already on the target, only the null space can move the arm towards its rest posture
+        let goal: [f32; 10] = [0.8, 0.9, 0.6, -0.4, 0.5, 0.3, 0.2, -0.4, 0.6, 0.0];
+        let (axes, radii) = redundant_arm();
+        let objective: Objective = Objective::RestPosture { thetas: START.to_vec(), weight: 1.0 };
+        let mut ik_solver: IKSolverDLS = IKSolverDLS::new(IDENTITY, &goal, &axes, &radii, &[-2.0; 10], &[2.0; 10], CollisionHandler::new(&[], &[], &[])).with_objectives(&[objective]);
+        ik_solver.limits.max_iterations = 200;
+
+        let result: SolveResult = ik_solver.solve_target(target(), 1e-7);
+
+        assert!(result.success, "{:?}", result);
+        assert!(result.position_error < 0.01);
+        assert!(ik_solver.objectives_cost(&result.thetas) < ik_solver.objectives_cost(&goal), "{:?}", result.thetas);
+
+    }
+
+    #[test]
+    fn test_analytic_gradient() {
+
+        const MIN_ANGLES: [f32; 10] = [-2.0, -1.0, -2.0, -2.0, f32::NEG_INFINITY, -2.0, -2.0, -0.5, -2.0, -2.0];
+        const MAX_ANGLES: [f32; 10] = [2.0, 1.0, 0.5, 2.0, f32::INFINITY, 2.0, 2.0, 2.0, 2.0, 2.0];
+        const DELTA: f32 = 0.001;
+
+        let (axes, radii) = redundant_arm();
+        let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(IDENTITY, &START, &axes, &radii));
+        let jacobian: DMatrix<f32> = DMatrix::zeros(3, 10);
+        let collision_handler: CollisionHandler = CollisionHandler::new(&[], &[], &[]);
+
+        let cost = |objective: &Objective, thetas: &[f32]| objective.cost(&Posture { thetas, min_angles: &MIN_ANGLES, max_angles: &MAX_ANGLES, forward_mats: &forward_mats, jacobian: &jacobian, collision_handler: &collision_handler });
+        let gradient = |objective: &Objective, thetas: &[f32]| objective.gradient(&Posture { thetas, min_angles: &MIN_ANGLES, max_angles: &MAX_ANGLES, forward_mats: &forward_mats, jacobian: &jacobian, collision_handler: &collision_handler });
+
+        // the analytic gradients match central differences of the costs
+        for objective in [Objective::RestPosture { thetas: vec![0.1; 10], weight: 2.0 }, Objective::JointLimits { weight: 1.5 }] {
+
+            let analytic: Vec<f32> = gradient(&objective, &START).unwrap();
+
+            for (i, derivative) in analytic.iter().enumerate() {
+                let mut plus: [f32; 10] = START;
+                let mut minus: [f32; 10] = START;
+                plus[i] += DELTA;
+                minus[i] -= DELTA;
+                let numeric: f32 = (cost(&objective, &plus) - cost(&objective, &minus)) / (2.0 * DELTA);
+                assert!((derivative - numeric).abs() < 0.01, "{:?} joint {}: {} vs {}", objective, i, derivative, numeric);
+            }
+
+        }
+
+        assert!(gradient(&Objective::Manipulability { weight: 1.0 }, &START).is_none());
+
+    }
+
+    #[test]
+    fn test_null_space_gain() {
+
+        // without any gain the objectives never move the arm off the plain solution
+        let mut plain: IKSolverDLS = solver(CollisionHandler::new(&[], &[], &[]), &[]);
+        let mut idle: IKSolverDLS = solver(CollisionHandler::new(&[], &[], &[]), &[Objective::RestPosture { thetas: START.to_vec(), weight: 1.0 }])
+        .with_config(DLSConfig { null_space_gain: 0.0 });
+
+        let plain_result: SolveResult = plain.solve_target(target(), 1e-7);
+        let idle_result: SolveResult = idle.solve_target(target(), 1e-7);
+
+        assert!(idle_result.success);
+        assert_eq!(idle_result.thetas, plain_result.thetas);
+
+    }
+
+    #[test]
+    #[should_panic(expected="Invalid objective! rest posture has 3 angles for 10 joints")]
+    fn test_invalid_objective() {
+        solver(CollisionHandler::new(&[], &[], &[]), &[Objective::RestPosture { thetas: vec![0.0; 3], weight: 1.0 }]);
+    }
+
+    #[test]
+    fn test_objective_json() {
+
+        let objectives: Vec<Objective> = serde_json::from_str(r#"[
+            {"type": "rest_posture", "thetas": [0.0, 0.5], "weight": 0.5},
+            {"type": "joint_limits", "weight": 1.0},
+            {"type": "manipulability", "weight": 0.1},
+            {"type": "clearance", "distance": 0.2, "weight": 2.0}
+        ]"#).unwrap();
+
+        assert_eq!(objectives[0], Objective::RestPosture { thetas: vec![0.0, 0.5], weight: 0.5 });
+        assert_eq!(objectives[3], Objective::Clearance { distance: 0.2, weight: 2.0 });
+
+    }
+
+}
@@ -0,0 +1,202 @@
+#[macro_use]
+extern crate approx; // For the macro relative_eq!
+extern crate nalgebra as na;
+
+#[cfg(test)]
+mod target_tests {
+
+    use krust::collision_handler::CollisionHandler;
+    use na::{Vector3, Matrix4, DMatrix};
+    use krust::matrices::{IDENTITY, PoseWeights, generate_matrices, generate_forward_matrices, jacobian};
+    use std::f32::consts::PI;
+    use krust::solver::{IKSolver, SolveResult};
+    use krust::solver_gd::{IKSolverGD, GDConfig};
+    use krust::solver_dls::IKSolverDLS;
+    use krust::solver_ccd::IKSolverCCD;
+    use krust::solver_fabrik::IKSolverFABRIK;
+    use krust::target::Target;
+
+    const GOAL: [f32; 6] = [0.4, -0.5, 0.9, 0.3, -0.7, 0.2];
+
+    fn six_axis_arm() -> (Vec<Vector3<f32>>, Vec<f32>) {
+        let axes: Vec<Vector3<f32>> = vec![*Vector3::z_axis(), *Vector3::y_axis(), *Vector3::y_axis(), *Vector3::z_axis(), *Vector3::y_axis(), *Vector3::z_axis()];
+        let radii: Vec<f32> = vec![1.0, 3.0, 3.0, 1.0, 1.0, 0.5];
+        (axes, radii)
+    }
+
+    fn end_effector(thetas: &[f32]) -> Matrix4<f32> {
+        let (axes, radii) = six_axis_arm();
+        *generate_forward_matrices(&generate_matrices(IDENTITY, thetas, &axes, &radii)).last().unwrap()
+    }
+
+    // each kind of target, all reachable from GOAL
+    fn targets() -> Vec<Target> {
+        let pose: Matrix4<f32> = end_effector(&GOAL);
+        let position: Vector3<f32> = pose.fixed_slice::<3, 1>(0, 3).into_owned();
+        vec![
+            Target::Pose { pose },
+            Target::Position { position },
+            Target::Axis { position, tool_axis: Vector3::z(), direction: pose.fixed_slice::<3, 1>(0, 2).into_owned() },
+            Target::Masked { pose, position_mask: [true, true, false], rotation_mask: [false, false, true] },
+        ]
+    }
+
+    fn assert_solved(result: &SolveResult) {
+        assert!(result.success);
+        assert!(result.position_error < 0.01, "position error {}", result.position_error);
+        assert!(result.orientation_error < 0.01, "orientation error {}", result.orientation_error);
+    }
+
+    #[test]
+    fn test_loss_gradient() {
+
+        let (axes, radii) = six_axis_arm();
+        let thetas: Vec<f32> = vec![0.1, 0.2, -0.3, 0.5, 0.4, -0.2];
+
+        let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices(&generate_matrices(IDENTITY, &thetas, &axes, &radii));
+        let jacobian: DMatrix<f32> = jacobian(&forward_mats, &axes);
+        let actual: Matrix4<f32> = forward_mats[forward_mats.len() - 1];
+
+        for target in targets() {
+
+            let gradient: Vec<f32> = target.loss_gradient(&actual, &jacobian, 9.5, PI);
+
+            // central differences of the loss
+            for i in 0..thetas.len() {
+                let mut plus: Vec<f32> = thetas.clone();
+                let mut minus: Vec<f32> = thetas.clone();
+                plus[i] += 1e-3;
+                minus[i] -= 1e-3;
+                let numeric: f32 = (target.loss(&end_effector(&plus), 9.5, PI) - target.loss(&end_effector(&minus), 9.5, PI)) / 2e-3;
+                assert!(relative_eq!(gradient[i], numeric, epsilon = 1e-3, max_relative = 0.05), "{:?} joint {}: {} vs {}", target, i, gradient[i], numeric);
+            }
+
+            let weights: PoseWeights = PoseWeights::new(0.1, 1.0);
+            let gradient: Vec<f32> = target.weighted_loss_gradient(&actual, &jacobian, &weights);
+
+            for i in 0..thetas.len() {
+                let mut plus: Vec<f32> = thetas.clone();
+                let mut minus: Vec<f32> = thetas.clone();
+                plus[i] += 1e-3;
+                minus[i] -= 1e-3;
+                let numeric: f32 = (target.weighted_loss(&end_effector(&plus), &weights) - target.weighted_loss(&end_effector(&minus), &weights)) / 2e-3;
+                assert!(relative_eq!(gradient[i], numeric, epsilon = 1e-2, max_relative = 0.05), "weighted {:?} joint {}: {} vs {}", target, i, gradient[i], numeric);
+            }
+        }
+
+    }
+
+    #[test]
+    fn test_errors_ignore_free_axes() {
+
+        let pose: Matrix4<f32> = end_effector(&GOAL);
+        let position: Vector3<f32> = pose.fixed_slice::<3, 1>(0, 3).into_owned();
+
+        // spinning about the tool axis and moving along z don't count
+        let spun: Matrix4<f32> = pose * Matrix4::new_rotation(Vector3::new(0.0, 0.0, 0.8));
+        let lifted: Matrix4<f32> = Matrix4::new_translation(&Vector3::new(0.0, 0.0, 0.5)) * spun;
+
+        assert_eq!(Target::Position { position }.errors(&spun), (0.0, 0.0));
+        assert!(relative_eq!(Target::Axis { position, tool_axis: Vector3::z(), direction: pose.fixed_slice::<3, 1>(0, 2).into_owned() }.errors(&spun).1, 0.0, epsilon = 1e-5));
+        assert!(relative_eq!(Target::Pose { pose }.errors(&spun).1, 0.8, epsilon = 1e-5));
+
+        let masked: Target = Target::Masked { pose, position_mask: [true, true, false], rotation_mask: [true, true, false] };
+        assert!(relative_eq!(masked.errors(&lifted).0, 0.0, epsilon = 1e-5));
+        assert!(relative_eq!(masked.errors(&lifted).1, 0.0, epsilon = 1e-5));
+        assert!(masked.loss(&lifted, 9.5, PI) < 1e-9);
+
+    }
+
+    #[test]
+    fn test_gd_targets() {
+
+        let (axes, radii) = six_axis_arm();
+
+        for target in targets() {
+            let config: GDConfig = GDConfig::builder().max_steps(1000).build().unwrap();
+            let mut ik_solver: IKSolverGD = IKSolverGD::with_config(IDENTITY, &[0.1; 6], &axes, &radii, &[-PI; 6], &[PI; 6], CollisionHandler::new(&[], &[], &[]), config);
+            ik_solver.limits.max_iterations = 2000;
+            ik_solver.limits.stall_iterations = 50;
+            assert_solved(&ik_solver.solve_target(target, 1e-7));
+        }
+
+    }
+
+    #[test]
+    fn test_dls_targets() {
+
+        let (axes, radii) = six_axis_arm();
+
+        for target in targets() {
+            let mut ik_solver: IKSolverDLS = IKSolverDLS::new(IDENTITY, &[0.1; 6], &axes, &radii, &[-PI; 6], &[PI; 6], CollisionHandler::new(&[], &[], &[]));
+            assert_solved(&ik_solver.solve_target(target, 1e-7));
+        }
+
+    }
+
+    #[test]
+    fn test_ccd_targets() {
+
+        let (axes, radii) = six_axis_arm();
+
+        for target in targets() {
+            let mut ik_solver: IKSolverCCD = IKSolverCCD::new(IDENTITY, &[0.1; 6], &axes, &radii, &[-PI; 6], &[PI; 6], CollisionHandler::new(&[], &[], &[]));
+            ik_solver.limits.max_iterations = 1000;
+            assert_solved(&ik_solver.solve_target(target, 1e-7));
+        }
+
+    }
+
+    #[test]
+    fn test_fabrik_position_target() {
+
+        let (axes, radii) = six_axis_arm();
+        let target: Target = targets()[1];
+
+        let mut ik_solver: IKSolverFABRIK = IKSolverFABRIK::new(IDENTITY, &[0.1; 6], &axes, &radii, &[-PI; 6], &[PI; 6], CollisionHandler::new(&[], &[], &[]));
+        assert_solved(&ik_solver.solve_target(target, 1e-7));
+
+    }
+
+    #[test]
+    fn test_target_json() {
+
+        let position: Target = serde_json::from_str(r#"{"type": "position", "position": [1.0, 2.0, 3.0]}"#).unwrap();
+        assert_eq!(position, Target::Position { position: Vector3::new(1.0, 2.0, 3.0) });
+
+        let axis: Target = serde_json::from_str(r#"{"type": "axis", "position": [1.0, 2.0, 3.0], "tool_axis": [0.0, 0.0, 1.0], "direction": [0.0, 0.0, -1.0]}"#).unwrap();
+        assert_eq!(axis, Target::Axis { position: Vector3::new(1.0, 2.0, 3.0), tool_axis: Vector3::z(), direction: -Vector3::z() });
+
+        let masked: Target = serde_json::from_str(r#"{"type": "masked", "pose": [1,0,0,0,0,1,0,0,0,0,1,0,1,2,3,1], "position_mask": [true, true, false], "rotation_mask": [false, false, true]}"#).unwrap();
+        assert_eq!(masked, Target::Masked { pose: Matrix4::new_translation(&Vector3::new(1.0, 2.0, 3.0)), position_mask: [true, true, false], rotation_mask: [false, false, true] });
+
+        let pose: Target = Target::from(IDENTITY);
+        assert_eq!(serde_json::from_str::<Target>(&serde_json::to_string(&pose).unwrap()).unwrap(), pose);
+
+    }
+
+    #[test]
+    fn test_validate() {
+
+        for target in targets() {
+            assert_eq!(target.validate(), Ok(()));
+        }
+
+        let position: Vector3<f32> = Vector3::new(1.0, 2.0, 3.0);
+
+        // a zero axis would normalize to NaN
+        assert!(Target::Axis { position, tool_axis: Vector3::zeros(), direction: Vector3::z() }.validate().is_err());
+        assert!(Target::Axis { position, tool_axis: Vector3::z(), direction: Vector3::zeros() }.validate().is_err());
+
+        let mut scaled: Matrix4<f32> = IDENTITY;
+        scaled[(0, 0)] = 2.0;
+        assert!(Target::Pose { pose: scaled }.validate().is_err());
+        assert!(Target::Masked { pose: scaled, position_mask: [true; 3], rotation_mask: [false; 3] }.validate().is_err());
+
+        // nothing left to reach for, which used to count as solved straight away
+        assert!(Target::Masked { pose: IDENTITY, position_mask: [false; 3], rotation_mask: [false; 3] }.validate().is_err());
+        assert_eq!(Target::Masked { pose: IDENTITY, position_mask: [false; 3], rotation_mask: [false, true, false] }.validate(), Ok(()));
+
+    }
+
+}
@@ -0,0 +1,169 @@
+#[macro_use]
+extern crate approx; // For the macro relative_eq!
+extern crate nalgebra as na;
+
+#[cfg(test)]
+mod solver_tests {
+
+    use krust::collision_handler::CollisionHandler;
+    use na::{Vector3, Matrix4, Isometry3};
+    use krust::matrices::{IDENTITY, JointType, generate_matrices_with_joints, generate_forward_matrices_tree};
+    use krust::solver::{IKSolver, SolveResult, TerminationReason, Tolerance};
+    use krust::solver_tree::IKSolverTree;
+
+    type Tree = (Vec<Vector3<f32>>, Vec<Isometry3<f32>>, Vec<Option<usize>>);
+
+    // a twisting torso with a shoulder, elbow and wrist on each side
+    fn torso() -> Tree {
+        let axes: Vec<Vector3<f32>> = vec![
+            *Vector3::z_axis(),
+            *Vector3::y_axis(), *Vector3::x_axis(), *Vector3::z_axis(),
+            *Vector3::y_axis(), *Vector3::x_axis(), *Vector3::z_axis(),
+        ];
+        let offsets: Vec<Isometry3<f32>> = vec![
+            Isometry3::translation(0.0, 0.0, 1.0),
+            Isometry3::translation(0.0, 0.5, 0.0), Isometry3::translation(0.0, 0.0, 1.0), Isometry3::translation(0.0, 0.0, 1.0),
+            Isometry3::translation(0.0, -0.5, 0.0), Isometry3::translation(0.0, 0.0, 1.0), Isometry3::translation(0.0, 0.0, 1.0),
+        ];
+        let parents: Vec<Option<usize>> = vec![None, Some(0), Some(1), Some(2), Some(0), Some(4), Some(5)];
+        (axes, offsets, parents)
+    }
+
+    fn solver(thetas: &[f32], collision_handler: CollisionHandler) -> IKSolverTree {
+        let (axes, offsets, parents) = torso();
+        let radii: Vec<f32> = vec![1.0; axes.len()];
+        let min_angles: Vec<f32> = vec![-3.0; axes.len()];
+        let max_angles: Vec<f32> = vec![3.0; axes.len()];
+        IKSolverTree::new(IDENTITY, thetas, &axes, &radii, &parents, &[3, 6], &min_angles, &max_angles, collision_handler).with_offsets(&offsets)
+    }
+
+    fn hand_poses(thetas: &[f32]) -> (Matrix4<f32>, Matrix4<f32>) {
+        let (axes, offsets, parents) = torso();
+        let joint_types: Vec<JointType> = vec![JointType::Revolute; axes.len()];
+        let forward_mats: Vec<Matrix4<f32>> = generate_forward_matrices_tree(&generate_matrices_with_joints(IDENTITY, thetas, &axes, &offsets, &joint_types), &parents);
+        (forward_mats[4], forward_mats[7])
+    }
+
+    #[test]
+    fn test_new_solver_success() {
+
+        let ik_solver: IKSolverTree = solver(&[0.0; 7], CollisionHandler::new(&[], &[], &[]));
+
+        assert_eq!(ik_solver.chain.forward_mats.len(), 8);
+        assert_eq!(ik_solver.reaches, vec![3.5, 3.5]);
+
+        // both hands straight up either side of the torso
+        let poses: Vec<Matrix4<f32>> = ik_solver.end_effector_poses();
+        assert!(relative_eq!(poses[0], Matrix4::new_translation(&Vector3::new(0.0, 0.5, 3.0))));
+        assert!(relative_eq!(poses[1], Matrix4::new_translation(&Vector3::new(0.0, -0.5, 3.0))));
+
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_parent_after_child_fail() {
+
+        let (axes, _, _) = torso();
+        let parents: Vec<Option<usize>> = vec![Some(1), None, Some(1), Some(2), Some(0), Some(4), Some(5)];
+        IKSolverTree::new(IDENTITY, &[0.0; 7], &axes, &[1.0; 7], &parents, &[3, 6], &[-3.0; 7], &[3.0; 7], CollisionHandler::new(&[], &[], &[]));
+
+    }
+
+    #[test]
+    fn test_solver_both_hands() {
+
+        let goal: Vec<f32> = vec![0.4, -0.6, 0.3, 0.2, 0.5, -0.4, -0.3];
+        let (left, right) = hand_poses(&goal);
+
+        let mut ik_solver: IKSolverTree = solver(&[0.0; 7], CollisionHandler::new(&[], &[], &[]));
+        let result: SolveResult = ik_solver.solve_targets(&[Some(left.into()), Some(right.into())], 0.00001);
+
+        assert!(result.success);
+        assert!(result.position_error < 0.01);
+        assert!(result.orientation_error < 0.01);
+
+        let poses: Vec<Matrix4<f32>> = ik_solver.end_effector_poses();
+        assert!(relative_eq!(poses[0], left, epsilon = 0.01));
+        assert!(relative_eq!(poses[1], right, epsilon = 0.01));
+
+    }
+
+    #[test]
+    fn test_solver_both_hands_within() {
+
+        let goal: Vec<f32> = vec![0.4, -0.6, 0.3, 0.2, 0.5, -0.4, -0.3];
+        let (left, right) = hand_poses(&goal);
+
+        // converged only once both hands are within tolerance
+        let tolerance: Tolerance = Tolerance::new(0.001, 0.001);
+        let mut ik_solver: IKSolverTree = solver(&[0.0; 7], CollisionHandler::new(&[], &[], &[]));
+        let result: SolveResult = ik_solver.solve_targets_within(&[Some(left.into()), Some(right.into())], tolerance);
+
+        assert!(result.success);
+        assert!(tolerance.contains(ik_solver.errors()));
+        assert_eq!((result.position_error, result.orientation_error), ik_solver.errors());
+
+    }
+
+    #[test]
+    fn test_solver_free_end_effector() {
+
+        // only the left hand has a target, the right arm is left where it started
+        let goal: Vec<f32> = vec![0.4, -0.6, 0.3, 0.2, 0.0, 0.0, 0.0];
+        let (left, _) = hand_poses(&goal);
+
+        let mut ik_solver: IKSolverTree = solver(&[0.0; 7], CollisionHandler::new(&[], &[], &[]));
+        let result: SolveResult = ik_solver.solve_targets(&[Some(left.into()), None], 0.00001);
+
+        assert!(result.success);
+        assert_eq!(&result.thetas[4..], &[0.0, 0.0, 0.0]);
+
+    }
+
+    #[test]
+    fn test_solver_trait_object() {
+
+        let goal: Vec<f32> = vec![0.0, -0.6, 0.3, 0.2, 0.0, 0.0, 0.0];
+        let (left, _) = hand_poses(&goal);
+
+        let mut ik_solver: Box<dyn IKSolver> = Box::new(solver(&[0.0; 7], CollisionHandler::new(&[], &[], &[])));
+        let result: SolveResult = ik_solver.solve(left, 0.00001);
+
+        assert_eq!(result.reason, TerminationReason::Converged);
+        assert!(result.position_error < 0.01);
+
+    }
+
+    #[test]
+    fn test_arm_collisions_tree() {
+
+        // a box along each link, the shoulders are siblings off the torso
+        let arm: Vec<Vector3<f32>> = vec![
+            Vector3::new(0.2, 0.2, 0.5),
+            Vector3::new(0.1, 0.1, 0.25), Vector3::new(0.1, 0.1, 0.5), Vector3::new(0.1, 0.1, 0.5),
+            Vector3::new(0.1, 0.1, 0.25), Vector3::new(0.1, 0.1, 0.5), Vector3::new(0.1, 0.1, 0.5),
+        ];
+        let ik_solver: IKSolverTree = solver(&[0.0; 7], CollisionHandler::new(&arm, &[], &[]));
+
+        let pose = |thetas: &[f32]| {
+            let joint_types: Vec<JointType> = vec![JointType::Revolute; 7];
+            generate_forward_matrices_tree(&generate_matrices_with_joints(IDENTITY, thetas, &ik_solver.chain.axes, &ik_solver.chain.offsets, &joint_types), &ik_solver.chain.parents)
+        };
+
+        // standing straight nothing touches, even though the shoulder boxes meet the torso
+        assert!(!ik_solver.collision_handler.is_arm_colliding_self_naive(&pose(&[0.0; 7])));
+        assert_eq!(ik_solver.collision_handler.find_arm_collisions_self(&pose(&[0.0; 7])), vec![false; 7]);
+
+        // both forearms folded in over the head cross each other
+        let folded: Vec<Matrix4<f32>> = pose(&[0.0, 0.0, 1.2, 0.0, 0.0, -1.2, 0.0]);
+        assert!(ik_solver.collision_handler.is_arm_colliding_self_naive(&folded));
+        assert_eq!(ik_solver.collision_handler.find_arm_collisions_self(&folded), vec![false, false, false, true, false, false, true]);
+
+        // the left elbow moves the left hand into the right one, the torso moves both hands together
+        assert!(ik_solver.collision_handler.is_arm_colliding_self(2, &folded));
+        assert!(ik_solver.collision_handler.is_arm_colliding_self(5, &folded));
+        assert!(!ik_solver.collision_handler.is_arm_colliding_self(0, &folded));
+
+    }
+
+}
@@ -0,0 +1,240 @@
+#[macro_use]
+extern crate approx; // For the macro relative_eq!
+extern crate nalgebra as na;
+
+#[cfg(test)]
+mod urdf_tests {
+
+    use krust::collision_handler::{CollisionHandler, Shape};
+    use na::{Vector3, Matrix4, Isometry3};
+    use std::f32::consts::FRAC_PI_2;
+    use krust::matrices::{IDENTITY, JointType, generate_matrices_with_joints, generate_forward_matrices};
+    use krust::solver::{IKSolver, SolveResult};
+    use krust::solver_gd::{IKSolverGD, GDConfig};
+    use krust::urdf::{Urdf, UrdfChain, UrdfError, UrdfGeometry};
+
+    // a turntable, a shoulder pitched onto its side, a slide and a tool flange
+    const ARM: &str = r#"
+    <robot name="test_arm">
+        <link name="base_link">
+            <collision>
+                <origin xyz="0 0 0.05"/>
+                <geometry><box size="0.2 0.2 0.1"/></geometry>
+            </collision>
+        </link>
+        <link name="link1">
+            <collision>
+                <origin xyz="0 0 0.25"/>
+                <geometry><box size="0.1 0.1 0.5"/></geometry>
+            </collision>
+        </link>
+        <link name="link2">
+            <collision>
+                <origin xyz="0 0 0.1"/>
+                <geometry><box size="0.1 0.1 0.2"/></geometry>
+            </collision>
+        </link>
+        <link name="link3">
+            <collision>
+                <origin xyz="0 0 0.05"/>
+                <geometry><box size="0.05 0.05 0.1"/></geometry>
+            </collision>
+        </link>
+        <link name="tool0"/>
+
+        <joint name="joint1" type="revolute">
+            <parent link="base_link"/>
+            <child link="link1"/>
+            <origin xyz="0 0 0.1"/>
+            <axis xyz="0 0 1"/>
+            <limit lower="-3" upper="3" effort="10" velocity="1"/>
+        </joint>
+        <joint name="joint2" type="revolute">
+            <parent link="link1"/>
+            <child link="link2"/>
+            <origin xyz="0 0 0.5" rpy="0 1.5707963 0"/>
+            <axis xyz="0 1 0"/>
+            <limit lower="-2" upper="2" effort="10" velocity="1"/>
+        </joint>
+        <joint name="joint3" type="prismatic">
+            <parent link="link2"/>
+            <child link="link3"/>
+            <origin xyz="0 0 0.2"/>
+            <axis xyz="0 0 2"/>
+            <limit lower="0" upper="0.3" effort="10" velocity="1"/>
+        </joint>
+        <joint name="flange" type="fixed">
+            <parent link="link3"/>
+            <child link="tool0"/>
+            <origin xyz="0 0 0.1"/>
+        </joint>
+    </robot>
+    "#;
+
+    fn end_effector(chain: &UrdfChain, values: &[f32]) -> Vector3<f32> {
+        let mats: Vec<Matrix4<f32>> = generate_matrices_with_joints(IDENTITY, values, &chain.axes, &chain.offsets, &chain.joint_types);
+        generate_forward_matrices(&mats).last().unwrap().fixed_slice::<3, 1>(0, 3).into_owned()
+    }
+
+    #[test]
+    fn test_parse_chain() {
+
+        let urdf: Urdf = Urdf::parse(ARM).unwrap();
+        assert_eq!(urdf.name, "test_arm");
+        assert_eq!(urdf.links.len(), 5);
+        assert_eq!(urdf.joints.len(), 4);
+
+        let chain: UrdfChain = urdf.chain("base_link", "tool0").unwrap();
+
+        assert_eq!(chain.joint_names, vec!["joint1", "joint2", "joint3", "flange"]);
+        assert_eq!(chain.links, vec!["base_link", "link1", "link2", "link3", "tool0"]);
+        assert_eq!(chain.joint_types, vec![JointType::Revolute, JointType::Revolute, JointType::Prismatic, JointType::Fixed]);
+        assert_eq!(chain.min_angles, vec![-3.0, -2.0, 0.0, 0.0]);
+        assert_eq!(chain.max_angles, vec![3.0, 2.0, 0.3, 0.0]);
+        assert_eq!(chain.axes[2], Vector3::z());
+        assert!(relative_eq!(chain.radii().as_slice(), [0.1, 0.5, 0.2, 0.1].as_slice(), epsilon = 1e-6));
+
+        // a sub chain starts at its own base
+        let sub_chain: UrdfChain = urdf.chain("link1", "link3").unwrap();
+        assert_eq!(sub_chain.joint_names, vec!["joint2", "joint3"]);
+
+    }
+
+    #[test]
+    fn test_chain_forward_kinematics() {
+
+        let chain: UrdfChain = Urdf::parse(ARM).unwrap().chain("base_link", "tool0").unwrap();
+
+        // the pitched shoulder lays the rest of the arm along x
+        assert!(relative_eq!(end_effector(&chain, &[0.0, 0.0, 0.0, 0.0]), Vector3::new(0.3, 0.0, 0.6), epsilon = 1e-5));
+        assert!(relative_eq!(end_effector(&chain, &[FRAC_PI_2, 0.0, 0.1, 0.0]), Vector3::new(0.0, 0.4, 0.6), epsilon = 1e-5));
+        assert!(relative_eq!(end_effector(&chain, &[0.0, FRAC_PI_2, 0.0, 0.0]), Vector3::new(0.0, 0.0, 0.3), epsilon = 1e-5));
+
+    }
+
+    #[test]
+    fn test_collision_boxes() {
+
+        let urdf: Urdf = Urdf::parse(ARM).unwrap();
+        let chain: UrdfChain = urdf.chain("base_link", "tool0").unwrap();
+        let (shapes, offsets): (Vec<Shape>, Vec<Isometry3<f32>>) = urdf.collision_shapes(&chain).unwrap();
+
+        // one box per link, the tool has none
+        assert_eq!(shapes.len(), chain.links.len());
+        assert_eq!(shapes[1], Shape::Box { half_extents: Vector3::new(0.05, 0.05, 0.25) });
+        assert_eq!(shapes[4], Shape::Box { half_extents: Vector3::zeros() });
+        assert_eq!(offsets[1], Isometry3::translation(0.0, 0.0, 0.25));
+
+        // a small block beside the base that the slide swings into
+        let mut collision_handler: CollisionHandler = CollisionHandler::with_shapes(&shapes, &[Shape::Box { half_extents: Vector3::new(0.05, 0.05, 0.05) }], &[Vector3::new(0.0, 0.3, 0.6)]);
+        collision_handler.set_arm_offsets(&offsets);
+
+        let pose = |values: &[f32]| generate_forward_matrices(&generate_matrices_with_joints(IDENTITY, values, &chain.axes, &chain.offsets, &chain.joint_types));
+
+        assert!(!collision_handler.is_arm_colliding_world_naive(&pose(&[0.0, 0.0, 0.0, 0.0])));
+        assert!(collision_handler.is_arm_colliding_world_naive(&pose(&[FRAC_PI_2, 0.0, 0.0, 0.0])));
+        assert!(!collision_handler.is_arm_colliding_self_naive(&pose(&[0.0, 0.0, 0.0, 0.0])));
+
+    }
+
+    #[test]
+    fn test_collision_shapes() {
+
+        let urdf: Urdf = Urdf::parse(&ARM.replace(r#"<box size="0.1 0.1 0.5"/>"#, r#"<cylinder radius="0.05" length="0.5"/>"#)).unwrap();
+        let chain: UrdfChain = urdf.chain("base_link", "tool0").unwrap();
+        let (shapes, offsets): (Vec<Shape>, Vec<Isometry3<f32>>) = urdf.collision_shapes(&chain).unwrap();
+
+        assert_eq!(shapes.len(), chain.links.len());
+        assert_eq!(shapes[1], Shape::Cylinder { half_height: 0.25, radius: 0.05 });
+        assert_eq!(shapes[4], Shape::Box { half_extents: Vector3::zeros() });
+        assert_eq!(offsets[1], Isometry3::translation(0.0, 0.0, 0.25));
+
+        // the cylinder link swings into the block just like the box did
+        let mut collision_handler: CollisionHandler = CollisionHandler::with_shapes(&shapes, &[Shape::Box { half_extents: Vector3::new(0.05, 0.05, 0.05) }], &[Vector3::new(0.0, 0.3, 0.6)]);
+        collision_handler.set_arm_offsets(&offsets);
+
+        let pose = |values: &[f32]| generate_forward_matrices(&generate_matrices_with_joints(IDENTITY, values, &chain.axes, &chain.offsets, &chain.joint_types));
+        assert!(!collision_handler.is_arm_colliding_world_naive(&pose(&[0.0, 0.0, 0.0, 0.0])));
+        assert!(collision_handler.is_arm_colliding_world_naive(&pose(&[FRAC_PI_2, 0.0, 0.0, 0.0])));
+
+    }
+
+    #[test]
+    fn test_solver_from_urdf() {
+
+        let urdf: Urdf = Urdf::parse(ARM).unwrap();
+        let chain: UrdfChain = urdf.chain("base_link", "tool0").unwrap();
+        let (shapes, offsets): (Vec<Shape>, Vec<Isometry3<f32>>) = urdf.collision_shapes(&chain).unwrap();
+
+        let mut collision_handler: CollisionHandler = CollisionHandler::with_shapes(&shapes, &[], &[]);
+        collision_handler.set_arm_offsets(&offsets);
+
+        let config: GDConfig = GDConfig::builder().max_steps(200).build().unwrap();
+        let mut ik_solver: IKSolverGD = IKSolverGD::with_config(IDENTITY, &chain.home_thetas(), &chain.axes, &chain.radii(), &chain.min_angles, &chain.max_angles, collision_handler, config)
+        .with_offsets(&chain.offsets)
+        .with_joint_types(&chain.joint_types);
+
+        let goal: Vec<f32> = vec![0.5, -0.3, 0.1, 0.0];
+        let target: Matrix4<f32> = *generate_forward_matrices(&generate_matrices_with_joints(IDENTITY, &goal, &chain.axes, &chain.offsets, &chain.joint_types)).last().unwrap();
+
+        let result: SolveResult = ik_solver.solve(target, 0.00001);
+
+        assert!(result.success);
+        assert!(result.position_error < 0.01);
+        assert!(relative_eq!(result.thetas.as_slice(), goal.as_slice(), epsilon = 0.01));
+
+    }
+
+    #[test]
+    fn test_continuous_joint_unlimited() {
+
+        let urdf: Urdf = Urdf::parse(&ARM.replace(r#"name="joint1" type="revolute""#, r#"name="joint1" type="continuous""#)).unwrap();
+        let chain: UrdfChain = urdf.chain("base_link", "tool0").unwrap();
+
+        assert_eq!(chain.joint_types[0], JointType::Continuous);
+        assert_eq!(chain.min_angles[0], f32::NEG_INFINITY);
+        assert_eq!(chain.max_angles[0], f32::INFINITY);
+
+    }
+
+    #[test]
+    fn test_chain_errors() {
+
+        let urdf: Urdf = Urdf::parse(ARM).unwrap();
+
+        assert_eq!(urdf.chain("base_link", "gripper"), Err(UrdfError::UnknownLink("gripper".to_string())));
+        assert_eq!(urdf.chain("tool0", "base_link"), Err(UrdfError::NotAChain { base: "tool0".to_string(), tip: "base_link".to_string() }));
+
+        let floating: Urdf = Urdf::parse(&ARM.replace(r#"type="prismatic""#, r#"type="floating""#)).unwrap();
+        assert_eq!(floating.chain("base_link", "tool0"), Err(UrdfError::UnsupportedJoint { joint: "joint3".to_string(), joint_type: "floating".to_string() }));
+
+        // unsupported joints outside the chain are fine
+        assert!(floating.chain("base_link", "link2").is_ok());
+
+        let mimic: Urdf = Urdf::parse(&ARM.replace(r#"<axis xyz="0 1 0"/>"#, r#"<axis xyz="0 1 0"/><mimic joint="joint1"/>"#)).unwrap();
+        assert_eq!(mimic.chain("base_link", "tool0"), Err(UrdfError::UnsupportedElement { joint: "joint2".to_string(), element: "mimic".to_string() }));
+
+    }
+
+    #[test]
+    fn test_geometry_errors() {
+
+        let urdf: Urdf = Urdf::parse(&ARM.replace(r#"<box size="0.1 0.1 0.2"/>"#, r#"<mesh filename="package://arm/link2.stl"/>"#)).unwrap();
+        let chain: UrdfChain = urdf.chain("base_link", "tool0").unwrap();
+
+        assert_eq!(urdf.links[2].collisions[0].geometry, UrdfGeometry::Mesh { filename: "package://arm/link2.stl".to_string(), scale: Vector3::new(1.0, 1.0, 1.0) });
+        assert_eq!(urdf.collision_shapes(&chain), Err(UrdfError::UnsupportedGeometry { link: "link2".to_string(), geometry: "<mesh>".to_string() }));
+
+    }
+
+    #[test]
+    fn test_parse_errors() {
+
+        assert!(matches!(Urdf::parse("<robot><link name=\"a\"></robot>"), Err(UrdfError::Xml(_))));
+        assert!(matches!(Urdf::parse("<model/>"), Err(UrdfError::Missing { .. })));
+        assert_eq!(Urdf::parse(&ARM.replace(r#"xyz="0 0 0.5""#, r#"xyz="0 0.5""#)), Err(UrdfError::InvalidAttribute { element: "origin".to_string(), attribute: "xyz".to_string(), value: "0 0.5".to_string() }));
+        assert_eq!(Urdf::parse(&ARM.replace(r#"<child link="link1"/>"#, "")), Err(UrdfError::Missing { element: "joint".to_string(), name: "<child>".to_string() }));
+
+    }
+
+}
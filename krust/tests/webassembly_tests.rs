@@ -0,0 +1,170 @@
+extern crate nalgebra as na;
+
+#[cfg(test)]
+mod webassembly_tests {
+
+    use na::{Vector3, Matrix4};
+    use std::f32::consts::PI;
+    use krust::matrices::{IDENTITY, generate_matrices, generate_matrices_with_offsets, generate_forward_matrices, dh_chain, DHParameters, DHConvention};
+    use krust::solver::{SolveResult, TerminationReason};
+    use krust::webassembly::InverseKinematics;
+
+    #[test]
+    fn test_dh_without_limits() {
+
+        let parameters: Vec<DHParameters> = vec![
+            DHParameters { a: 0.0, alpha: PI / 2.0, d: 0.089159, theta_offset: 0.0 },
+            DHParameters { a: -0.425, alpha: 0.0, d: 0.0, theta_offset: 0.0 },
+            DHParameters { a: -0.39225, alpha: 0.0, d: 0.0, theta_offset: 0.0 },
+            DHParameters { a: 0.0, alpha: PI / 2.0, d: 0.10915, theta_offset: 0.0 },
+            DHParameters { a: 0.0, alpha: -PI / 2.0, d: 0.09465, theta_offset: 0.0 },
+            DHParameters { a: 0.0, alpha: 0.0, d: 0.0823, theta_offset: 0.0 },
+        ];
+        let (axes, offsets, tool) = dh_chain(&parameters, DHConvention::Classic);
+
+        let goal: Vec<f32> = vec![0.3, -1.2, 1.5, -0.4, 1.1, 0.7];
+        let target: Matrix4<f32> = generate_forward_matrices(&generate_matrices_with_offsets(IDENTITY, &goal, &axes, &offsets)).last().unwrap() * tool.to_homogeneous();
+
+        // no min_angles or max_angles, so every joint turns all the way round
+        let fields: String = serde_json::json!({
+            "origin": IDENTITY,
+            "thetas": [0.1, -1.0, 1.2, -0.2, 1.0, 0.5],
+            "dh": { "convention": "classic", "parameters": parameters },
+            "solver": "dls",
+        }).to_string();
+
+        let mut ik: InverseKinematics = InverseKinematics::from_json(&fields).unwrap();
+        let result: SolveResult = serde_json::from_str(&ik.solve(&serde_json::to_string(&target).unwrap(), 0.0000001, None).unwrap()).unwrap();
+
+        assert!(result.success);
+        assert!(result.position_error < 0.001);
+        assert_eq!(result.thetas.len(), 6);
+        assert!(result.at_limits.iter().all(|at_limit| !at_limit));
+
+        // one angle per row, or none to start at zero
+        let fields = |thetas: &[f32]| serde_json::json!({
+            "origin": IDENTITY,
+            "thetas": thetas,
+            "dh": { "convention": "classic", "parameters": parameters },
+        }).to_string();
+
+        assert!(InverseKinematics::from_json(&fields(&[])).is_ok());
+        assert_eq!(InverseKinematics::from_json(&fields(&[0.0; 7])).err(), Some("Vector lengths unequal! angles: 7, DH rows: 6".to_string()));
+
+    }
+
+    #[test]
+    fn test_invalid_fields() {
+
+        // a turntable and a slide
+        let xml: &str = r#"
+        <robot name="slide">
+            <link name="base_link"/>
+            <link name="link1"/>
+            <link name="link2"/>
+            <joint name="joint1" type="revolute">
+                <parent link="base_link"/>
+                <child link="link1"/>
+                <axis xyz="0 0 1"/>
+                <limit lower="-3" upper="3" effort="10" velocity="1"/>
+            </joint>
+            <joint name="joint2" type="prismatic">
+                <parent link="link1"/>
+                <child link="link2"/>
+                <origin xyz="0 0 0.5"/>
+                <axis xyz="1 0 0"/>
+                <limit lower="0" upper="0.3" effort="10" velocity="1"/>
+            </joint>
+        </robot>
+        "#;
+        let fields = |solver: &str| serde_json::json!({
+            "origin": IDENTITY,
+            "thetas": [],
+            "urdf": { "xml": xml, "base": "base_link", "tip": "link2" },
+            "solver": solver,
+        }).to_string();
+
+        // only gd moves prismatic joints, the other solvers report it instead of panicking
+        assert!(InverseKinematics::from_json(&fields("gd")).is_ok());
+        assert_eq!(InverseKinematics::from_json(&fields("dls")).err(), Some("Joint types other than revolute are only supported by the gd solver!".to_string()));
+
+        // continuous joints are only wrapped by gd, so the others reject them rather than treat them as unwrapped revolute joints
+        let continuous = |solver: &str| fields(solver).replace(r#"type="prismatic""#, r#"type="continuous""#);
+        assert!(InverseKinematics::from_json(&continuous("gd")).is_ok());
+        for solver in ["dls", "ga", "ccd", "fabrik"] {
+            assert_eq!(InverseKinematics::from_json(&continuous(solver)).err(), Some("Joint types other than revolute are only supported by the gd solver!".to_string()));
+        }
+
+        assert!(InverseKinematics::from_json(r#"{"thetas": [0.0]}"#).err().is_some_and(|err| err.starts_with("Invalid fields!")));
+        assert!(InverseKinematics::from_json(&fields("gd").replace("link2\"", "link3\"")).err().is_some_and(|err| err.starts_with("Invalid URDF chain!")));
+        assert!(InverseKinematics::from_json(&serde_json::json!({
+            "origin": IDENTITY,
+            "thetas": [0.0, 0.0],
+            "axes": [[1.0, 0.0, 0.0]],
+            "radii": [1.0, 1.0],
+        }).to_string()).err().is_some_and(|err| err.starts_with("Vector lengths unequal!")));
+        assert!(InverseKinematics::from_json(&serde_json::json!({
+            "origin": IDENTITY,
+            "thetas": [0.0],
+            "axes": [[1.0, 0.0, 0.0]],
+            "radii": [1.0],
+            "min_angles": [-1.0],
+            "max_angles": [1.0],
+            "solver": "ga",
+            "ga_config": { "crossover_rate": 7.0 },
+        }).to_string()).err().is_some_and(|err| err.starts_with("Invalid config! crossover rate")));
+
+        // a collider for each joint and one for the last link at most, more used to panic when solving
+        let colliders = |count: usize| serde_json::json!({
+            "origin": IDENTITY,
+            "thetas": [0.0, 0.0],
+            "axes": [[1.0, 0.0, 0.0], [1.0, 0.0, 0.0]],
+            "radii": [1.0, 1.0],
+            "min_angles": [-1.0, -1.0],
+            "max_angles": [1.0, 1.0],
+            "arm_half_extents": vec![[0.1, 0.1, 0.5]; count],
+        }).to_string();
+        assert!(InverseKinematics::from_json(&colliders(3)).is_ok());
+        assert_eq!(InverseKinematics::from_json(&colliders(4)).err(), Some("Invalid arm colliders! 4 colliders for 2 joints, at most one per joint and one for the last link".to_string()));
+
+    }
+
+    #[test]
+    fn test_off_axis_arm_offsets() {
+
+        // the second link turns about x towards -y to reach the target, where a housing half a unit
+        // to its +y side would sit in a block the link itself stays clear of
+        let target: Matrix4<f32> = *generate_forward_matrices(&generate_matrices(IDENTITY, &[1.0, 0.0], &[*Vector3::x_axis(); 2], &[1.0, 1.0])).last().unwrap();
+        let fields = |arm_offsets: serde_json::Value| serde_json::json!({
+            "origin": IDENTITY,
+            "thetas": [0.0, 0.0],
+            "axes": [[1.0, 0.0, 0.0], [1.0, 0.0, 0.0]],
+            "radii": [1.0, 1.0],
+            "min_angles": [-2.0, -2.0],
+            "max_angles": [2.0, 2.0],
+            "arm_half_extents": [[0.1, 0.1, 0.5], [0.1, 0.1, 0.1]],
+            "arm_offsets": arm_offsets,
+            "world_half_extents": [[0.05, 0.05, 0.05]],
+            "world_offsets": [[0.0, -0.15, 1.69]],
+            "solver": "ccd",
+        }).to_string();
+        let solve = |fields: &str| -> SolveResult {
+            let mut ik: InverseKinematics = InverseKinematics::from_json(fields).unwrap();
+            serde_json::from_str(&ik.solve(&serde_json::to_string(&target).unwrap(), 0.00001, None).unwrap()).unwrap()
+        };
+
+        // on the link axis by default
+        let free: SolveResult = solve(&fields(serde_json::json!([])));
+        assert!(free.success);
+        assert!(free.position_error < 0.01);
+
+        let blocked: SolveResult = solve(&fields(serde_json::json!([[0.0, 0.0, 0.5], [0.0, 0.5, 0.5]])));
+        assert!(!blocked.success);
+        assert_eq!(blocked.reason, TerminationReason::CollisionBlocked);
+
+        // one pose per arm collider
+        assert_eq!(InverseKinematics::from_json(&fields(serde_json::json!([[0.0, 0.5, 0.5]]))).err(), Some("Vector lengths unequal! arm offsets: 1, arm colliders: 2".to_string()));
+
+    }
+
+}